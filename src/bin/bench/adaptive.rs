@@ -0,0 +1,201 @@
+//! `--adaptive-concurrency`: searches for the number of in-flight requests with the highest
+//! throughput whose p99 latency stays under a target, adjusting it AIMD-style (adding one request
+//! per window within the target, halving otherwise).
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bitcoin::BlockHash;
+
+use crate::{backend::BlockUrl, fetch_into, mb_per_sec, Endpoint, Result};
+
+/// Requests completed before the limit is adjusted: at least `MIN_WINDOW`, and `WINDOW_PER_REQUEST`
+/// per allowed in-flight request (so the p99 isn't dominated by the first few responses).
+const MIN_WINDOW: usize = 50;
+const WINDOW_PER_REQUEST: usize = 4;
+
+/// Nearest-rank percentile `p` of `sorted`.
+pub fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied().unwrap_or_default()
+}
+
+/// The results of one evaluation window.
+#[derive(Clone, Copy)]
+struct Window {
+    limit: usize,
+    rps: f64,
+    mb_per_sec: f64,
+    p99: Duration,
+}
+
+struct State {
+    /// Allowed in-flight requests
+    limit: usize,
+    in_flight: usize,
+    started: Instant,
+    latencies: Vec<Duration>,
+    bytes: usize,
+    windows: usize,
+    /// The highest-throughput window within the target
+    best: Option<Window>,
+    /// Set by the first failed request, stopping all workers
+    error: Option<String>,
+}
+
+struct Controller {
+    target: Duration,
+    max: usize,
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+impl Controller {
+    /// Waits until another request may be sent. Returns `false` if a request has failed.
+    fn acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.in_flight >= state.limit && state.error.is_none() {
+            state = self.released.wait(state).unwrap();
+        }
+        if state.error.is_some() {
+            return false;
+        }
+        state.in_flight += 1;
+        true
+    }
+
+    fn release(&self, result: std::result::Result<(Duration, usize), String>) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        match result {
+            Ok((latency, bytes)) => {
+                state.latencies.push(latency);
+                state.bytes += bytes;
+                if state.latencies.len() >= MIN_WINDOW.max(WINDOW_PER_REQUEST * state.limit) {
+                    self.adjust(&mut state);
+                }
+            }
+            Err(e) => {
+                state.error.get_or_insert(e);
+            }
+        }
+        drop(state);
+        self.released.notify_all();
+    }
+
+    fn adjust(&self, state: &mut State) {
+        let elapsed = state.started.elapsed();
+        state.latencies.sort_unstable();
+        let n = state.latencies.len();
+        let window = Window {
+            limit: state.limit,
+            rps: n as f64 / elapsed.as_secs_f64(),
+            mb_per_sec: mb_per_sec(state.bytes, elapsed),
+            p99: percentile(&state.latencies, 99),
+        };
+        let within = window.p99 <= self.target;
+        if within && state.best.is_none_or(|best| window.rps > best.rps) {
+            state.best = Some(window);
+        }
+        state.limit = if within {
+            (state.limit + 1).min(self.max)
+        } else {
+            (state.limit / 2).max(1)
+        };
+        tracing::info!(
+            "adaptive: {} in flight: {:.1}[req/s] {:.1}[MB/s], p99 {:.1}[ms] -> {}",
+            window.limit,
+            window.rps,
+            window.mb_per_sec,
+            window.p99.as_secs_f64() * 1e3,
+            state.limit
+        );
+        state.windows += 1;
+        state.latencies.clear();
+        state.bytes = 0;
+        state.started = Instant::now();
+    }
+}
+
+/// Fetches every block once from `prefix` (without decoding), on up to `max` threads.
+pub fn run(
+    endpoint: &Endpoint,
+    name: &str,
+    prefix: &BlockUrl,
+    blocks: &[(usize, BlockHash)],
+    target: Duration,
+    max: usize,
+) -> Result<()> {
+    let controller = Controller {
+        target,
+        max,
+        state: Mutex::new(State {
+            limit: 1,
+            in_flight: 0,
+            started: Instant::now(),
+            latencies: Vec::new(),
+            bytes: 0,
+            windows: 0,
+            best: None,
+            error: None,
+        }),
+        released: Condvar::new(),
+    };
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|s| {
+        for _ in 0..max {
+            s.spawn(|| {
+                let (mut raw, mut data) = (Vec::new(), Vec::new());
+                while let Some((height, hash)) = blocks.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if !controller.acquire() {
+                        return;
+                    }
+                    let url = prefix.get(*height, hash);
+                    endpoint.client.throttle();
+                    let t = Instant::now();
+                    let result = fetch_into(endpoint.client, &url, &mut raw, &mut data)
+                        .map(|fetch| (t.elapsed(), fetch.received))
+                        .map_err(|e| e.to_string());
+                    controller.release(result);
+                }
+            });
+        }
+    });
+
+    let state = controller.state.into_inner().unwrap();
+    if let Some(e) = state.error {
+        return Err(e.into());
+    }
+    match state.best {
+        Some(best) => tracing::info!(
+            "adaptive: {} {}: optimum {} in flight, {:.1}[req/s] {:.1}[MB/s] with p99 {:.1}[ms] \
+             (target {:?}, {} windows)",
+            name,
+            endpoint.label,
+            best.limit,
+            best.rps,
+            best.mb_per_sec,
+            best.p99.as_secs_f64() * 1e3,
+            target,
+            state.windows
+        ),
+        None if state.windows == 0 => tracing::warn!(
+            "adaptive: {} {}: too few blocks for a single window of {} requests",
+            name,
+            endpoint.label,
+            MIN_WINDOW
+        ),
+        None => tracing::warn!(
+            "adaptive: {} {}: even a single request in flight exceeds a p99 of {:?}",
+            name,
+            endpoint.label,
+            target
+        ),
+    }
+    Ok(())
+}
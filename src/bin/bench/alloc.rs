@@ -0,0 +1,96 @@
+use std::ops::{Add, Sub};
+
+/// Heap allocation counters, only maintained with the `alloc-stats` feature.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Allocs {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl Add for Allocs {
+    type Output = Allocs;
+
+    fn add(self, other: Allocs) -> Allocs {
+        Allocs {
+            count: self.count + other.count,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+impl Sub for Allocs {
+    type Output = Allocs;
+
+    fn sub(self, other: Allocs) -> Allocs {
+        Allocs {
+            count: self.count - other.count,
+            bytes: self.bytes - other.bytes,
+        }
+    }
+}
+
+pub const ENABLED: bool = cfg!(feature = "alloc-stats");
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    pub static COUNT: AtomicU64 = AtomicU64::new(0);
+    pub static BYTES: AtomicU64 = AtomicU64::new(0);
+
+    fn record(size: usize) {
+        COUNT.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record(layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            record(layout.size());
+            unsafe { System.alloc_zeroed(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record(new_size);
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+}
+
+/// Allocations made by the whole process so far (zero without the `alloc-stats` feature).
+pub fn allocs() -> Allocs {
+    #[cfg(feature = "alloc-stats")]
+    {
+        use std::sync::atomic::Ordering;
+        Allocs {
+            count: counting::COUNT.load(Ordering::Relaxed),
+            bytes: counting::BYTES.load(Ordering::Relaxed),
+        }
+    }
+    #[cfg(not(feature = "alloc-stats"))]
+    Allocs::default()
+}
+
+/// Peak resident set size in bytes, as reported by Linux.
+pub fn peak_rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
@@ -0,0 +1,333 @@
+//! `--backend`: the API served at the `--url`s, locating each block's responses and resolving
+//! the range's block hashes.
+
+use std::io::Read;
+
+use bitcoin::{
+    block::Header, blockdata::constants::genesis_block, consensus::Decodable, hex::FromHex,
+    io::Cursor, BlockHash,
+};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{fetch_chaininfo, rpc, ChainInfo, Client, Network, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    /// Bitcoin Core's REST interface (`-rest`)
+    Rest,
+    /// Bitcoin Core's JSON-RPC (on the same port), serving headers and blocks as hex
+    Rpc,
+    /// Esplora's HTTP API (e.g. electrs' `--http-addr`), which only serves raw blocks
+    Esplora,
+    /// The Electrum protocol (at `tcp://host:port` URLs), serving headers and blocks assembled
+    /// from their transactions
+    Electrum,
+}
+
+/// Where an endpoint serves the response of each block: `prefix`, its hash (or height), then
+/// `suffix`.
+pub struct BlockUrl {
+    prefix: String,
+    suffix: &'static str,
+    /// Electrum finds blocks by height only
+    by_height: bool,
+}
+
+impl BlockUrl {
+    pub fn get(&self, height: usize, hash: &BlockHash) -> String {
+        if self.by_height {
+            format!("{}{}{}", self.prefix, height, self.suffix)
+        } else {
+            format!("{}{}{}", self.prefix, hash, self.suffix)
+        }
+    }
+}
+
+/// An entry of Esplora's `/blocks/:start_height` (listing up to 10 blocks, from that height
+/// down).
+#[derive(Deserialize)]
+struct BlockSummary {
+    id: String,
+    height: usize,
+}
+
+impl Backend {
+    /// Locates the responses of `endpoint` (a REST path, e.g. `block`) at `base_url`.
+    pub fn block_url(self, base_url: &str, endpoint: &str) -> Result<BlockUrl> {
+        let base_url = base_url.trim_end_matches('/');
+        let (prefix, suffix, by_height) = match (self, endpoint) {
+            (Backend::Rest, _) => (format!("{}/rest/{}/", base_url, endpoint), ".bin", false),
+            (Backend::Rpc, "block") => (format!("{}{}/block/", rpc::SCHEME, base_url), "", false),
+            (Backend::Rpc, "headers/1") => {
+                (format!("{}{}/header/", rpc::SCHEME, base_url), "", false)
+            }
+            (Backend::Esplora, "block") => (format!("{}/block/", base_url), "/raw", false),
+            (Backend::Electrum, "block") => (format!("{}/block/", base_url), "", true),
+            (Backend::Electrum, "headers/1") => (format!("{}/header/", base_url), "", true),
+            _ => {
+                return Err(format!(
+                    "--backend {} doesn't serve `{}` responses",
+                    self.name(),
+                    endpoint
+                )
+                .into())
+            }
+        };
+        Ok(BlockUrl {
+            prefix,
+            suffix,
+            by_height,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Rest => "rest",
+            Backend::Rpc => "rpc",
+            Backend::Esplora => "esplora",
+            Backend::Electrum => "electrum",
+        }
+    }
+
+    /// Height of the node's tip.
+    pub fn tip(self, client: &Client, base_url: &str) -> Result<usize> {
+        match self {
+            Backend::Rest => Ok(fetch_chaininfo(client, base_url)?.blocks),
+            Backend::Rpc => {
+                let url = format!("{}{}/call/getblockcount", rpc::SCHEME, base_url);
+                Ok(client.get_string(&url)?.parse()?)
+            }
+            Backend::Esplora => {
+                let url = format!("{}/blocks/tip/height", base_url);
+                Ok(client.get_string(&url)?.trim().parse()?)
+            }
+            Backend::Electrum => {
+                let url = format!("{}/rpc/blockchain.headers.subscribe", base_url);
+                let tip: HeaderNotification = serde_json::from_str(&client.get_string(&url)?)?;
+                Ok(tip.height)
+            }
+        }
+    }
+
+    pub fn blockhash(self, client: &Client, base_url: &str, height: usize) -> Result<BlockHash> {
+        let url = match self {
+            Backend::Rest => format!("{}/rest/blockhashbyheight/{}.hex", base_url, height),
+            Backend::Rpc => {
+                let url = format!("{}{}/call/getblockhash/{}", rpc::SCHEME, base_url, height);
+                let hash: String = serde_json::from_str(&client.get_string(&url)?)?;
+                return Ok(hash.parse()?);
+            }
+            Backend::Esplora => format!("{}/block-height/{}", base_url, height),
+            Backend::Electrum => {
+                let url = format!("{}/header/{}", base_url, height);
+                let mut data = Vec::new();
+                client.get(&url)?.read_to_end(&mut data)?;
+                return Ok(Header::consensus_decode(&mut Cursor::new(data))?.block_hash());
+            }
+        };
+        Ok(client.get_string(&url)?.trim().parse()?)
+    }
+
+    /// Neither Esplora nor Electrum report their chain (nor pruning or initial block download),
+    /// so it's told by the genesis block.
+    pub fn chaininfo(self, client: &Client, base_url: &str, network: Network) -> Result<ChainInfo> {
+        if self == Backend::Rpc {
+            let url = format!("{}{}/call/getblockchaininfo", rpc::SCHEME, base_url);
+            return Ok(serde_json::from_str(&client.get_string(&url)?)?);
+        }
+        let genesis = self.blockhash(client, base_url, 0)?;
+        let chain = if genesis == genesis_block(network.bitcoin()).block_hash() {
+            network.chain_name().to_owned()
+        } else {
+            format!("with genesis {}", genesis)
+        };
+        let blocks = self.tip(client, base_url)?;
+        Ok(ChainInfo {
+            chain,
+            blocks,
+            headers: blocks,
+            initialblockdownload: false,
+            pruneheight: None,
+        })
+    }
+}
+
+/// Electrum's `blockchain.headers.subscribe` result.
+#[derive(Deserialize)]
+struct HeaderNotification {
+    height: usize,
+}
+
+/// Electrum's `blockchain.block.headers` result: `count` concatenated headers (as hex), of up
+/// to `max` per call.
+#[derive(Deserialize)]
+struct Headers {
+    count: usize,
+    hex: String,
+    max: usize,
+}
+
+/// Resolves `count` hashes from `start`, as many as the server allows per call.
+pub fn electrum_blockhashes(
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+) -> Result<Vec<BlockHash>> {
+    let mut result = Vec::with_capacity(count);
+    // Until the server tells its limit (2016 for electrs and Fulcrum)
+    let mut batch = count;
+    while result.len() < count {
+        let height = start + result.len();
+        let n = batch.min(count - result.len());
+        let url = format!("{}/rpc/blockchain.block.headers/{}/{}", base_url, height, n);
+        let headers: Headers = serde_json::from_str(&client.get_string(&url)?)?;
+        batch = headers.max.max(1);
+        // Past the tip
+        if headers.count == 0 {
+            break;
+        }
+        let mut c = Cursor::new(Vec::from_hex(&headers.hex)?);
+        for _ in 0..headers.count {
+            result.push(Header::consensus_decode(&mut c)?.block_hash());
+        }
+    }
+    Ok(result)
+}
+
+/// Resolves `count` hashes from `start`, via batches of `batch` calls.
+pub fn rpc_blockhashes(
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+    batch: usize,
+) -> Result<Vec<BlockHash>> {
+    let mut result = Vec::with_capacity(count);
+    while result.len() < count {
+        let height = start + result.len();
+        let n = batch.min(count - result.len());
+        let url = format!("{}{}/hashes/{}/{}", rpc::SCHEME, base_url, height, n);
+        let replies: Vec<serde_json::Value> = serde_json::from_str(&client.get_string(&url)?)?;
+        for reply in replies {
+            // Past the tip
+            let Some(hash) = reply["result"].as_str() else {
+                return Ok(result);
+            };
+            result.push(hash.parse()?);
+        }
+    }
+    Ok(result)
+}
+
+/// Resolves `count` hashes from `start`, 10 at a time.
+pub fn esplora_blockhashes(
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+) -> Result<Vec<BlockHash>> {
+    let mut result = Vec::with_capacity(count);
+    let limit = start + count;
+    while start + result.len() < limit {
+        let height = start + result.len();
+        let top = (height + 9).min(limit - 1);
+        let url = format!("{}/blocks/{}", base_url, top);
+        let mut blocks: Vec<BlockSummary> = serde_json::from_str(&client.get_string(&url)?)?;
+        blocks.retain(|b| (height..=top).contains(&b.height));
+        blocks.sort_unstable_by_key(|b| b.height);
+        // Above the tip, Esplora lists the blocks below it instead
+        if blocks.first().is_none_or(|b| b.height != height) {
+            break;
+        }
+        for (b, expected) in blocks.iter().zip(height..) {
+            if b.height != expected {
+                return Err(format!("{}: missing block {}", url, expected).into());
+            }
+            result.push(b.id.parse()?);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use bitcoin::BlockHash;
+    use clap::Parser;
+    use serde_json::json;
+
+    use super::esplora_blockhashes;
+    use crate::{http::HttpClient, Args, Client};
+
+    fn hash(height: usize) -> BlockHash {
+        format!("{:064x}", height + 1).parse().unwrap()
+    }
+
+    /// Serves Esplora's `/blocks/:start_height` for a chain of `tip + 1` blocks, leaving out
+    /// the `missing` height.
+    fn serve(tip: usize, missing: Option<usize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = BufReader::new(&stream).lines().map(Result::unwrap);
+                let line = request.next().unwrap();
+                while !request.next().unwrap().is_empty() {}
+                let path = line.split(' ').nth(1).unwrap();
+                let top: usize = path.strip_prefix("/blocks/").unwrap().parse().unwrap();
+                let top = top.min(tip);
+                let heights = (top.saturating_sub(9)..=top).rev();
+                let heights = heights.filter(|&height| Some(height) != missing);
+                let blocks: Vec<_> = heights
+                    .map(|height| json!({ "id": hash(height).to_string(), "height": height }))
+                    .collect();
+                let body = serde_json::to_string(&blocks).unwrap();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    fn blockhashes(url: &str, start: usize, count: usize) -> crate::Result<Vec<BlockHash>> {
+        let args = Args::try_parse_from(["bench"]).unwrap();
+        let client = Client::new(&args, HttpClient::Ureq, None).unwrap();
+        esplora_blockhashes(&client, url, start, count)
+    }
+
+    #[test]
+    fn pages_blockhashes() {
+        let url = serve(100, None);
+        for (start, count) in [(0, 1), (0, 10), (3, 25), (91, 10)] {
+            let expected: Vec<_> = (start..start + count).map(hash).collect();
+            assert_eq!(blockhashes(&url, start, count).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn stops_at_tip() {
+        let url = serve(25, None);
+        let expected: Vec<_> = (12..=25).map(hash).collect();
+        assert_eq!(blockhashes(&url, 12, 30).unwrap(), expected);
+        assert!(blockhashes(&url, 26, 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_block() {
+        let url = serve(100, Some(15));
+        let err = blockhashes(&url, 10, 10).unwrap_err();
+        assert!(err.to_string().ends_with("missing block 15"), "{}", err);
+    }
+}
@@ -0,0 +1,303 @@
+//! Benchmark registry: each `--type` names the REST endpoint(s) it fetches per block,
+//! and decodes their responses.
+//!
+//! A new benchmark implements `Benchmark` (in its own module) and adds an `Entry` to `REGISTRY`.
+
+use std::io::BufReader;
+
+use bitcoin::io::{Cursor, FromStd};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+
+use crate::{
+    block::BitcoinBlock, block_decode, blockundo_decode, blockundo_slices_decode, bloom::Bloom,
+    check_consumed, fees::FeeRates, filter_query::FilterQuery, filters::BlockFilters,
+    headers::Headers, index::IndexWrite, roundtrip::Roundtrip, silent_payments::SilentPayments,
+    spenttxouts_decode, stripped::Stripped, utxo::Utxo, verify::Verify,
+    verify_scripts::VerifyScripts, wallet::Wallet, Args, Counters, Decoder, Result, Scratch,
+};
+
+/// Response being decoded while it's received (`--streaming`).
+pub type Stream<'a> = FromStd<BufReader<&'a mut dyn std::io::Read>>;
+
+pub trait Benchmark {
+    /// Decodes the responses of a single block (at `scratch.height`), one per endpoint.
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()>;
+
+    /// Decodes a single response while it's being received.
+    fn decode_stream(
+        &mut self,
+        _stream: &mut Stream,
+        _counters: &mut Counters,
+        _scratch: &mut Scratch,
+    ) -> Result<()> {
+        Err("this benchmark can't be decoded while streaming".into())
+    }
+
+    /// Called after each chunk, before its progress is saved (with `--state-db`).
+    fn checkpoint(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once all chunks were run against all endpoints.
+    fn summarize(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A registered benchmark, created anew for every run.
+pub struct Entry {
+    /// Used by `--type`, logs and sinks
+    pub name: &'static str,
+    /// Subcommand running only this benchmark
+    pub command: &'static str,
+    pub about: &'static str,
+    /// REST endpoints fetched per block (e.g. `blockundo`), in the order `decode` receives them
+    pub endpoints: &'static [&'static str],
+    pub create: fn(&Args) -> Result<Box<dyn Benchmark>>,
+}
+
+pub static REGISTRY: &[Entry] = &[
+    Entry {
+        name: "block",
+        command: "block",
+        about: "Fetches and parses blocks",
+        endpoints: &["block"],
+        create: Block::create,
+    },
+    Entry {
+        name: "block-roundtrip",
+        command: "roundtrip",
+        about: "Fetches blocks, and checks that encoding them again reproduces the response's \
+                bytes, timing the encoding",
+        endpoints: &["block"],
+        create: Roundtrip::create,
+    },
+    Entry {
+        name: "block-stripped",
+        command: "stripped",
+        about: "Fetches blocks, and encodes them without their witnesses, timing the encoding \
+                and reporting the bytes it saves",
+        endpoints: &["block"],
+        create: Stripped::create,
+    },
+    Entry {
+        name: "block-undo",
+        command: "undo",
+        about: "Fetches and decodes undo data",
+        endpoints: &["blockundo"],
+        create: BlockUndo::create,
+    },
+    Entry {
+        name: "spent-txouts",
+        command: "spent",
+        about: "Fetches and decodes spent outputs",
+        endpoints: &["spenttxouts"],
+        create: SpentTxouts::create,
+    },
+    Entry {
+        name: "verify",
+        command: "verify",
+        about: "Fetches both undo data and spent outputs, and checks that they match",
+        endpoints: &["blockundo", "spenttxouts"],
+        create: Verify::create,
+    },
+    Entry {
+        name: "fee-rates",
+        command: "fees",
+        about: "Fetches both the block and its undo data, and computes per-transaction fee rates",
+        endpoints: &["block", "blockundo"],
+        create: FeeRates::create,
+    },
+    Entry {
+        name: "headers",
+        command: "headers",
+        about: "Fetches each block's header, and checks its proof of work and difficulty changes",
+        endpoints: &["headers/1"],
+        create: Headers::create,
+    },
+    Entry {
+        name: "block-filters",
+        command: "filters",
+        about:
+            "Fetches the block, its spent outputs and its BIP158 filter, and compares the filter \
+                with one built locally",
+        endpoints: &["block", "spenttxouts", "blockfilter/basic"],
+        create: BlockFilters::create,
+    },
+    Entry {
+        name: "filter-query",
+        command: "filter-query",
+        about: "Fetches each block's BIP158 filter, and matches the --addresses and --descriptor \
+                scripts against it",
+        endpoints: &["blockfilter/basic"],
+        create: FilterQuery::create,
+    },
+    Entry {
+        name: "bloom",
+        command: "bloom",
+        about: "Fetches blocks, and matches their transactions against a BIP37 bloom filter of \
+                the --addresses and --descriptor scripts",
+        endpoints: &["block"],
+        create: Bloom::create,
+    },
+    Entry {
+        name: "silent-payments",
+        command: "silent-payments",
+        about: "Fetches both the block and its spent outputs, and scans it for BIP352 silent \
+                payments (computing each eligible transaction's tweak)",
+        endpoints: &["block", "spenttxouts"],
+        create: SilentPayments::create,
+    },
+    Entry {
+        name: "utxo",
+        command: "utxo",
+        about: "Fetches blocks, and applies them to an in-memory UTXO set",
+        endpoints: &["block"],
+        create: Utxo::create,
+    },
+    Entry {
+        name: "wallet",
+        command: "wallet",
+        about: "Fetches both the block and its spent outputs, and tracks the balances of the \
+                --addresses and --descriptor scripts",
+        endpoints: &["block", "spenttxouts"],
+        create: Wallet::create,
+    },
+    Entry {
+        name: "index-write",
+        command: "index",
+        about: "Writes electrs-style scripthash rows of each block to a temporary RocksDB \
+                (requires the `rocksdb` feature)",
+        endpoints: &["block"],
+        create: IndexWrite::create,
+    },
+    Entry {
+        name: "verify-scripts",
+        command: "scripts",
+        about: "Fetches both the block and its spent outputs, and verifies every input's scripts \
+                (requires the `bitcoinconsensus` feature)",
+        endpoints: &["block", "spenttxouts"],
+        create: VerifyScripts::create,
+    },
+];
+
+/// Parses `--type` values into registry entries.
+pub fn parser() -> impl TypedValueParser<Value = &'static Entry> {
+    let values = REGISTRY
+        .iter()
+        .map(|entry| PossibleValue::new(entry.name).help(entry.about));
+    PossibleValuesParser::new(values).map(|name| {
+        REGISTRY
+            .iter()
+            .find(|entry| entry.name == name)
+            .expect("parsed a registered name")
+    })
+}
+
+/// Decodes blocks with `bitcoin_slices`.
+struct Block;
+
+impl Block {
+    fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        match args.decoder {
+            Some(Decoder::Bitcoin) => BitcoinBlock::create(args),
+            None | Some(Decoder::Slices) if args.parallel.is_some() => {
+                Err("--parallel requires --decoder bitcoin".into())
+            }
+            None | Some(Decoder::Slices) => Ok(Box::new(Block)),
+        }
+    }
+}
+
+impl Benchmark for Block {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        block_decode(responses[0], counters, scratch)
+    }
+}
+
+struct BlockUndo(Decoder);
+
+impl BlockUndo {
+    fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(BlockUndo(
+            args.decoder.unwrap_or(Decoder::Bitcoin),
+        )))
+    }
+}
+
+impl Benchmark for BlockUndo {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        match self.0 {
+            Decoder::Bitcoin => {
+                let mut c = Cursor::new(responses[0]);
+                blockundo_decode(&mut c, counters, scratch)?;
+                let rest = responses[0].len() - c.position() as usize;
+                check_consumed(rest, counters, scratch.height, "undo data");
+                Ok(())
+            }
+            Decoder::Slices => blockundo_slices_decode(responses[0], counters, scratch),
+        }
+    }
+
+    fn decode_stream(
+        &mut self,
+        stream: &mut Stream,
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        match self.0 {
+            Decoder::Bitcoin => blockundo_decode(stream, counters, scratch),
+            Decoder::Slices => Err("the slices decoder needs the whole response".into()),
+        }
+    }
+}
+
+struct SpentTxouts;
+
+impl SpentTxouts {
+    fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        match args.decoder {
+            None | Some(Decoder::Bitcoin) => Ok(Box::new(SpentTxouts)),
+            Some(decoder) => Err(format!("spent-txouts has no {:?} decoder", decoder).into()),
+        }
+    }
+}
+
+impl Benchmark for SpentTxouts {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let mut c = Cursor::new(responses[0]);
+        spenttxouts_decode(&mut c, counters, scratch)?;
+        let rest = responses[0].len() - c.position() as usize;
+        check_consumed(rest, counters, scratch.height, "spent outputs");
+        Ok(())
+    }
+
+    fn decode_stream(
+        &mut self,
+        stream: &mut Stream,
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        spenttxouts_decode(stream, counters, scratch)
+    }
+}
@@ -0,0 +1,187 @@
+//! `block --decoder bitcoin`: owned `bitcoin::Block` decoding, followed by per-transaction
+//! processing (txid and script classification), optionally on a rayon pool (`--parallel`).
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    consensus::Decodable,
+    hashes::{sha256, Hash},
+    io::Cursor,
+    opcodes::all::OP_CHECKSIG,
+    Script, Transaction,
+};
+use rayon::prelude::*;
+
+use crate::{
+    benchmarks::Benchmark, check_consumed, check_merkle_root, check_witness_commitment,
+    script_type::ScriptFilter, witness_commitment, Args, Counters, Result, Scratch,
+    SPECIAL_SCRIPTS,
+};
+
+pub struct BitcoinBlock {
+    pool: Option<rayon::ThreadPool>,
+    txs: usize,
+    sequential: Duration,
+    parallel: Duration,
+}
+
+impl BitcoinBlock {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        let pool = match args.parallel {
+            Some(threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            ),
+            None => None,
+        };
+        Ok(Box::new(BitcoinBlock {
+            pool,
+            txs: 0,
+            sequential: Duration::ZERO,
+            parallel: Duration::ZERO,
+        }))
+    }
+}
+
+impl Benchmark for BitcoinBlock {
+    /// With `--parallel`, the decode time covers both the sequential and the parallel pass.
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let mut c = Cursor::new(responses[0]);
+        let block = bitcoin::Block::consensus_decode(&mut c)?;
+        let rest = responses[0].len() - c.position() as usize;
+        check_consumed(rest, counters, scratch.height, "block");
+        let scripthash = scratch.scripthash;
+        let filter = &scratch.script_filter;
+        if scratch.verify_merkle {
+            let t = Instant::now();
+            check_merkle_root(block.check_merkle_root(), counters, scratch.height);
+            let coinbase = block.txdata.first().map_or(&[][..], |tx| &tx.output[..]);
+            if coinbase
+                .iter()
+                .any(|txout| witness_commitment(txout.script_pubkey.as_bytes()).is_some())
+            {
+                let matches = block.check_witness_commitment();
+                check_witness_commitment(matches, counters, scratch.height);
+            }
+            counters.verify_time += t.elapsed();
+        }
+
+        if let Some(dump) = &mut scratch.dump_scripts {
+            for txout in block.txdata.iter().flat_map(|tx| &tx.output) {
+                if scratch.script_filter.selects(&txout.script_pubkey) {
+                    dump.write_script(scratch.height, "block", &txout.script_pubkey)?;
+                }
+            }
+        }
+
+        let t = Instant::now();
+        let outputs = block
+            .txdata
+            .iter()
+            .map(|tx| Outputs::of(tx, scripthash, filter))
+            .fold(Outputs::default(), Outputs::merge);
+        self.sequential += t.elapsed();
+
+        if let Some(pool) = &self.pool {
+            let t = Instant::now();
+            let parallel = pool.install(|| {
+                block
+                    .txdata
+                    .par_iter()
+                    .map(|tx| Outputs::of(tx, scripthash, filter))
+                    .reduce(Outputs::default, Outputs::merge)
+            });
+            self.parallel += t.elapsed();
+            if parallel != outputs {
+                return Err(format!(
+                    "block {}: parallel processing differs ({:?} != {:?})",
+                    scratch.height, parallel, outputs
+                )
+                .into());
+            }
+        }
+        self.txs += block.txdata.len();
+        counters.scripts += outputs.scripts;
+        counters.hashes += outputs.hashes;
+        for (total, n) in counters.count_by_type.iter_mut().zip(outputs.count_by_type) {
+            *total += n;
+        }
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        if let Some(pool) = &self.pool {
+            tracing::info!(
+                "block: {} transactions processed in {:.3}[s] sequentially, {:.3}[s] on {} threads ({:.2}x)",
+                self.txs,
+                self.sequential.as_secs_f64(),
+                self.parallel.as_secs_f64(),
+                pool.current_num_threads(),
+                self.sequential.as_secs_f64() / self.parallel.as_secs_f64().max(f64::MIN_POSITIVE),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Per-transaction results, summed over the block.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Outputs {
+    scripts: u64,
+    hashes: u64,
+    count_by_type: [u64; SPECIAL_SCRIPTS + 1],
+}
+
+impl Outputs {
+    /// Of the outputs selected by `filter` (`--filter-script-type`).
+    fn of(tx: &Transaction, scripthash: bool, filter: &ScriptFilter) -> Self {
+        std::hint::black_box(tx.compute_txid());
+        let mut outputs = Outputs::default();
+        for txout in &tx.output {
+            let script = txout.script_pubkey.as_script();
+            if !filter.selects(script) {
+                continue;
+            }
+            outputs.scripts += script.len() as u64;
+            outputs.count_by_type[script_kind(script)] += 1;
+            if scripthash {
+                std::hint::black_box(sha256::Hash::hash(script.as_bytes()));
+                outputs.hashes += 1;
+            }
+        }
+        outputs
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.scripts += other.scripts;
+        self.hashes += other.hashes;
+        for (total, n) in self.count_by_type.iter_mut().zip(other.count_by_type) {
+            *total += n;
+        }
+        self
+    }
+}
+
+/// The undo-data compression type of a script (see `decompress_script`), ignoring public key
+/// validity, or `SPECIAL_SCRIPTS` if it would be stored as is.
+fn script_kind(script: &Script) -> usize {
+    let b = script.as_bytes();
+    let checksig = b.last() == Some(&OP_CHECKSIG.to_u8());
+    if script.is_p2pkh() {
+        0
+    } else if script.is_p2sh() {
+        1
+    } else if b.len() == 35 && b[0] == 33 && matches!(b[1], 2 | 3) && checksig {
+        b[1] as usize
+    } else if b.len() == 67 && b[0] == 65 && b[1] == 4 && checksig {
+        4 | (b[65] & 1) as usize
+    } else {
+        SPECIAL_SCRIPTS
+    }
+}
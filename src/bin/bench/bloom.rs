@@ -0,0 +1,271 @@
+//! `Bloom` benchmark: matches each block's transactions against a BIP37 bloom filter of the
+//! `--addresses` and `--descriptor` scripts, as a node serving an SPV client would (for
+//! comparing with the BIP158 filters of `filter-query`).
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    consensus::{Decodable, Encodable},
+    io::Cursor,
+    script::Instruction,
+    Block, OutPoint, Script, ScriptBuf, Transaction,
+};
+use clap::ValueEnum;
+
+use crate::{
+    benchmarks::Benchmark, descriptors::Descriptors, wallet::watched_scripts, Args, Counters,
+    Result, Scratch,
+};
+
+/// BIP37's limits on `filterload`.
+const MAX_FILTER_BYTES: usize = 36_000;
+const MAX_HASH_FUNCS: u32 = 50;
+
+/// Which matched outputs are added to the filter (so that their spends match too).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BloomUpdate {
+    None,
+    All,
+    /// Only pay-to-pubkey and bare multisig outputs
+    P2pubkeyOnly,
+}
+
+/// BIP37's filter, as built by Bitcoin Core's `CBloomFilter`.
+struct BloomFilter {
+    data: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+}
+
+impl BloomFilter {
+    /// Sized for `elements` at a false-positive rate of `fp_rate`, within BIP37's limits.
+    fn new(elements: usize, fp_rate: f64, tweak: u32) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let elements = elements.max(1) as f64;
+        let bits = (-1.0 / (ln2 * ln2) * elements * fp_rate.ln()) as usize;
+        let bytes = (bits.min(MAX_FILTER_BYTES * 8) / 8).max(1);
+        let hash_funcs = ((bytes * 8) as f64 / elements * ln2) as u32;
+        BloomFilter {
+            data: vec![0; bytes],
+            hash_funcs: hash_funcs.clamp(1, MAX_HASH_FUNCS),
+            tweak,
+        }
+    }
+
+    fn bit(&self, n: u32, data: &[u8]) -> usize {
+        let seed = n.wrapping_mul(0xfba4c795).wrapping_add(self.tweak);
+        murmur3(seed, data) as usize % (self.data.len() * 8)
+    }
+
+    fn insert(&mut self, data: &[u8]) {
+        for n in 0..self.hash_funcs {
+            let bit = self.bit(n, data);
+            self.data[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    fn contains(&self, data: &[u8]) -> bool {
+        (0..self.hash_funcs).all(|n| {
+            let bit = self.bit(n, data);
+            self.data[bit / 8] & (1 << (bit % 8)) != 0
+        })
+    }
+
+    /// Of random elements, given the bits set so far.
+    fn fp_rate(&self) -> f64 {
+        let set: u32 = self.data.iter().map(|b| b.count_ones()).sum();
+        (set as f64 / (self.data.len() * 8) as f64).powi(self.hash_funcs as i32)
+    }
+}
+
+/// MurmurHash3 (x86, 32-bit), as used by BIP37.
+fn murmur3(seed: u32, data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+    let mut h = seed;
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let k = u32::from_le_bytes(block.try_into().expect("4 bytes"));
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let k = tail.iter().rev().fold(0u32, |k, &b| (k << 8) | b as u32);
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^ (h >> 16)
+}
+
+/// The script's (non-empty) data pushes, up to the first invalid opcode.
+pub fn pushes(script: &Script) -> impl Iterator<Item = &[u8]> {
+    script
+        .instructions()
+        .map_while(|i| i.ok())
+        .filter_map(|i| match i {
+            Instruction::PushBytes(data) if !data.is_empty() => Some(data.as_bytes()),
+            _ => None,
+        })
+}
+
+fn outpoint_bytes(outpoint: &OutPoint) -> [u8; 36] {
+    let mut bytes = [0; 36];
+    outpoint
+        .consensus_encode(&mut &mut bytes[..])
+        .expect("in-memory writer");
+    bytes
+}
+
+pub struct Bloom {
+    filter: BloomFilter,
+    update: BloomUpdate,
+    /// `--bloom-fp-rate`
+    target_fp_rate: f64,
+    /// Of the watched scripts' data pushes
+    elements: usize,
+    /// Added by matched outputs
+    outpoints: u64,
+    txs: u64,
+    matched_txs: u64,
+    blocks: u64,
+    /// Blocks with any matched transaction
+    matched_blocks: u64,
+    /// Hashing and matching the transactions (excluding decoding)
+    matching: Duration,
+}
+
+impl Bloom {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        if !(args.bloom_fp_rate > 0.0 && args.bloom_fp_rate < 1.0) {
+            return Err("--bloom-fp-rate must be between 0 and 1".into());
+        }
+        let mut scripts: Vec<ScriptBuf> = watched_scripts(args, "bloom")?
+            .into_iter()
+            .map(|(_, script)| script)
+            .collect();
+        let derived = Descriptors::parse(args)?.initial()?;
+        scripts.extend(derived.into_iter().map(|(_, _, script)| script));
+        // SPV wallets load their scripts' public keys (or their hashes)
+        let elements: Vec<&[u8]> = scripts.iter().flat_map(|s| pushes(s)).collect();
+        if elements.is_empty() {
+            return Err("bloom: the watched scripts have no data pushes to match".into());
+        }
+        let mut filter = BloomFilter::new(elements.len(), args.bloom_fp_rate, 0);
+        for element in &elements {
+            filter.insert(element);
+        }
+        tracing::info!(
+            "bloom: {} elements (of {} scripts) in a filter of {} bytes, {} hash functions",
+            elements.len(),
+            scripts.len(),
+            filter.data.len(),
+            filter.hash_funcs
+        );
+        Ok(Box::new(Bloom {
+            elements: elements.len(),
+            filter,
+            update: args.bloom_update,
+            target_fp_rate: args.bloom_fp_rate,
+            outpoints: 0,
+            txs: 0,
+            matched_txs: 0,
+            blocks: 0,
+            matched_blocks: 0,
+            matching: Duration::ZERO,
+        }))
+    }
+
+    /// Bitcoin Core's `CBloomFilter::IsRelevantAndUpdate`.
+    fn matches(&mut self, tx: &Transaction) -> bool {
+        let txid = tx.compute_txid();
+        let mut found = self.filter.contains(txid.as_ref());
+        for (vout, txout) in tx.output.iter().enumerate() {
+            let script = &txout.script_pubkey;
+            if !pushes(script).any(|data| self.filter.contains(data)) {
+                continue;
+            }
+            found = true;
+            let update = match self.update {
+                BloomUpdate::None => false,
+                BloomUpdate::All => true,
+                BloomUpdate::P2pubkeyOnly => script.is_p2pk() || script.is_multisig(),
+            };
+            if update {
+                self.filter
+                    .insert(&outpoint_bytes(&OutPoint::new(txid, vout as u32)));
+                self.outpoints += 1;
+            }
+        }
+        found
+            || tx.input.iter().any(|txin| {
+                self.filter.contains(&outpoint_bytes(&txin.previous_output))
+                    || pushes(&txin.script_sig).any(|data| self.filter.contains(data))
+            })
+    }
+}
+
+impl Benchmark for Bloom {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+
+        let t = Instant::now();
+        let mut matched = 0;
+        for tx in &block.txdata {
+            matched += self.matches(tx) as u64;
+        }
+        self.matching += t.elapsed();
+
+        let txs = block.txdata.len() as u64;
+        self.txs += txs;
+        self.matched_txs += matched;
+        self.blocks += 1;
+        counters.count += txs;
+        counters.txids += txs;
+        if matched > 0 {
+            self.matched_blocks += 1;
+            tracing::debug!("bloom: block {} matched {} txs", scratch.height, matched);
+        }
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let matching = self.matching.as_secs_f64().max(f64::MIN_POSITIVE);
+        tracing::info!(
+            "bloom: {} of {} blocks matched ({:.2}%), {} of {} txs; {} outpoints added to the \
+             filter; matched in {:.3}[s], {:.0}[blocks/s], {:.0}[txs/s]",
+            self.matched_blocks,
+            self.blocks,
+            100.0 * self.matched_blocks as f64 / self.blocks.max(1) as f64,
+            self.matched_txs,
+            self.txs,
+            self.outpoints,
+            matching,
+            self.blocks as f64 / matching,
+            self.txs as f64 / matching
+        );
+        let fp_rate = self.filter.fp_rate();
+        tracing::info!(
+            "bloom: {} elements, estimated false-positive rate {:.4}% per element queried",
+            self.elements as u64 + self.outpoints,
+            100.0 * fp_rate
+        );
+        // The outpoints added by `--bloom-update` degrade the filter, as they did for SPV wallets
+        if fp_rate > 10.0 * self.target_fp_rate {
+            tracing::warn!(
+                "bloom: the filter is saturated (sized for a false-positive rate of {}%)",
+                100.0 * self.target_fp_rate
+            );
+        }
+        Ok(())
+    }
+}
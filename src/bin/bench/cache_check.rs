@@ -0,0 +1,114 @@
+//! `--detect-cache` and `--refetch`: checks that distinct blocks get distinct responses (a broken
+//! cache between the client and the node may serve one for another's URL), and quantifies the
+//! node's own caching by fetching each response again right away.
+
+use std::{collections::HashMap, time::Duration};
+
+use bitcoin::hashes::{sha256, Hash};
+
+use crate::{adaptive::percentile, fetch_into, Client, Fetch, Result};
+
+/// Shorter responses legitimately repeat (e.g. the undo data of blocks without spends).
+const MIN_TRACKED_BYTES: usize = 16;
+
+pub struct CacheCheck {
+    detect: bool,
+    refetch: u32,
+    /// Of each (long enough) response, with the height it was first received for
+    digests: HashMap<sha256::Hash, usize>,
+    /// Long enough to be hashed
+    hashed: u64,
+    /// Responses identical to another block's
+    repeats: u64,
+    /// Refetched responses differing from the benchmarked one
+    changed: u64,
+    /// Of the benchmarked fetches, and of their refetches
+    first: Vec<Duration>,
+    again: Vec<Duration>,
+    raw: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl CacheCheck {
+    pub fn new(detect: bool, refetch: u32) -> Option<Self> {
+        (detect || refetch > 0).then(|| CacheCheck {
+            detect,
+            refetch,
+            digests: HashMap::new(),
+            hashed: 0,
+            repeats: 0,
+            changed: 0,
+            first: Vec::new(),
+            again: Vec::new(),
+            raw: Vec::new(),
+            data: Vec::new(),
+        })
+    }
+
+    /// Checks the response `data` of `url` (for the block at `height`), received by `fetch`.
+    pub fn check(
+        &mut self,
+        client: &Client,
+        url: &str,
+        height: usize,
+        data: &[u8],
+        fetch: &Fetch,
+    ) -> Result<()> {
+        if self.detect && data.len() >= MIN_TRACKED_BYTES {
+            self.hashed += 1;
+            let first = *self
+                .digests
+                .entry(sha256::Hash::hash(data))
+                .or_insert(height);
+            if first != height {
+                self.repeats += 1;
+                tracing::warn!(
+                    "{}: same response as for block {} (a broken cache?)",
+                    url,
+                    first
+                );
+            }
+        }
+        if self.refetch > 0 {
+            self.first.push(fetch.elapsed());
+        }
+        for _ in 0..self.refetch {
+            client.throttle();
+            let again = fetch_into(client, url, &mut self.raw, &mut self.data)?;
+            self.again.push(again.elapsed());
+            if self.data != data {
+                self.changed += 1;
+                tracing::warn!("{}: response changed when fetched again", url);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn summarize(&mut self) {
+        if self.detect {
+            tracing::info!(
+                "detect-cache: {} of {} responses repeated another block's",
+                self.repeats,
+                self.hashed
+            );
+        }
+        if self.first.is_empty() {
+            return;
+        }
+        self.first.sort_unstable();
+        self.again.sort_unstable();
+        let (first, again) = (percentile(&self.first, 50), percentile(&self.again, 50));
+        tracing::info!(
+            "refetch: {} responses fetched {} more times: median {:.2}[ms] first, {:.2}[ms] \
+             again ({:.2}x), p90 {:.2}[ms] and {:.2}[ms]; {} changed",
+            self.first.len(),
+            self.refetch,
+            first.as_secs_f64() * 1e3,
+            again.as_secs_f64() * 1e3,
+            first.as_secs_f64() / again.as_secs_f64().max(f64::MIN_POSITIVE),
+            percentile(&self.first, 90).as_secs_f64() * 1e3,
+            percentile(&self.again, 90).as_secs_f64() * 1e3,
+            self.changed
+        );
+    }
+}
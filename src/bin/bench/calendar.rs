@@ -0,0 +1,81 @@
+//! Block timestamps, for `--group-by day` and `--group-by month` (in UTC).
+
+use std::{cmp::min, collections::BTreeMap, io::Read};
+
+use bitcoin::{block::Header, consensus::Decodable, io::Cursor, BlockHash};
+
+use crate::{Client, Result};
+
+const SECONDS_PER_DAY: u32 = 86_400;
+
+/// Fetches the header timestamps of `blocks`, requesting up to `batch` consecutive headers at a
+/// time.
+pub fn block_times(
+    client: &Client,
+    base_url: &str,
+    blocks: &[(usize, BlockHash)],
+    batch: usize,
+) -> Result<BTreeMap<usize, u32>> {
+    let mut times = BTreeMap::new();
+    let mut rest = blocks;
+    while let Some(&(height, hash)) = rest.first() {
+        // Consecutive heights following the first block
+        let run = rest
+            .iter()
+            .zip(height..)
+            .take_while(|((h, _), expected)| h == expected)
+            .count();
+        let url = format!("{}/rest/headers/{}/{}.bin", base_url, min(batch, run), hash);
+        let mut data = Vec::new();
+        client.get(&url)?.read_to_end(&mut data)?;
+        let count = data.len() / Header::SIZE;
+        if count == 0 {
+            return Err(format!("{}: no headers", url).into());
+        }
+        let mut c = Cursor::new(data);
+        for h in height..height + count {
+            let header = Header::consensus_decode_from_finite_reader(&mut c)?;
+            times.insert(h, header.time);
+        }
+        rest = &rest[count.min(rest.len())..];
+    }
+    Ok(times)
+}
+
+/// Days since the unix epoch.
+pub fn day(time: u32) -> usize {
+    (time / SECONDS_PER_DAY) as usize
+}
+
+/// Months since year 0.
+pub fn month(time: u32) -> usize {
+    let (year, month, _) = civil(day(time));
+    year * 12 + month as usize - 1
+}
+
+/// `YYYY-MM-DD`
+pub fn format_day(time: u32) -> String {
+    let (year, month, day) = civil(day(time));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `YYYY-MM`
+pub fn format_month(time: u32) -> String {
+    let (year, month, _) = civil(day(time));
+    format!("{:04}-{:02}", year, month)
+}
+
+/// The proleptic Gregorian year, month and day of `days` since the unix epoch (Howard Hinnant's
+/// `civil_from_days`, for dates after the epoch).
+fn civil(days: usize) -> (usize, u32, u32) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + usize::from(month <= 2);
+    (year, month, day)
+}
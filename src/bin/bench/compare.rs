@@ -0,0 +1,165 @@
+//! The `compare` subcommand: aligns the chunks of two `--summary-out` files by height range, and
+//! prints the per-block time deltas of each benchmark with their 95% confidence intervals.
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::Result;
+
+/// Two-sided 95% quantile of the normal distribution
+const Z_95: f64 = 1.96;
+
+#[derive(Deserialize)]
+struct Report {
+    runs: Vec<Run>,
+}
+
+#[derive(Deserialize)]
+struct Run {
+    name: String,
+    endpoint: String,
+    iteration: u32,
+    /// Missing from summaries written before `compare` existed
+    #[serde(default)]
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    height: usize,
+    blocks: usize,
+    wall_time: f64,
+    fetch_time: f64,
+    decode_time: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    Wall,
+    Fetch,
+    Decode,
+}
+
+impl Chunk {
+    fn time(&self, phase: Phase) -> f64 {
+        match phase {
+            Phase::Wall => self.wall_time,
+            Phase::Fetch => self.fetch_time,
+            Phase::Decode => self.decode_time,
+        }
+    }
+}
+
+const PHASES: [(&str, Phase); 3] = [
+    ("wall", Phase::Wall),
+    ("fetch", Phase::Fetch),
+    ("decode", Phase::Decode),
+];
+
+fn load(path: &Path) -> Result<Report> {
+    let file = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_slice(&file).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Runs are paired by benchmark and iteration, and then by their order in the file (so that the
+/// endpoints of `--url` lists of the same length are paired).
+fn pairs<'a>(a: &'a Report, b: &'a Report) -> Vec<(&'a Run, &'a Run)> {
+    let mut unpaired: BTreeMap<(&str, u32), Vec<&Run>> = BTreeMap::new();
+    for run in b.runs.iter().rev() {
+        let key = (run.name.as_str(), run.iteration);
+        unpaired.entry(key).or_default().push(run);
+    }
+    a.runs
+        .iter()
+        .filter_map(|run| {
+            let key = (run.name.as_str(), run.iteration);
+            Some((run, unpaired.get_mut(&key)?.pop()?))
+        })
+        .collect()
+}
+
+/// Mean and 95% confidence half-width of `samples` (none for fewer than 2 samples).
+fn mean_ci(samples: &[f64]) -> (f64, Option<f64>) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    if samples.len() < 2 {
+        return (mean, None);
+    }
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, Some(Z_95 * (variance / n).sqrt()))
+}
+
+pub fn run(path_a: &Path, path_b: &Path) -> Result<()> {
+    let (a, b) = (load(path_a)?, load(path_b)?);
+    let pairs = pairs(&a, &b);
+    if pairs.is_empty() {
+        return Err(format!(
+            "{} and {} have no benchmark runs in common",
+            path_a.display(),
+            path_b.display()
+        )
+        .into());
+    }
+    for (run_a, run_b) in pairs {
+        let chunks_b: BTreeMap<_, _> = run_b
+            .chunks
+            .iter()
+            .map(|c| ((c.height, c.blocks), c))
+            .collect();
+        let aligned: Vec<(&Chunk, &Chunk)> = run_a
+            .chunks
+            .iter()
+            .filter_map(|c| Some((c, *chunks_b.get(&(c.height, c.blocks))?)))
+            .collect();
+        println!(
+            "{} #{}: {} -> {} ({} of {} chunks aligned)",
+            run_a.name,
+            run_a.iteration,
+            run_a.endpoint,
+            run_b.endpoint,
+            aligned.len(),
+            run_a.chunks.len().max(run_b.chunks.len())
+        );
+        if aligned.is_empty() {
+            continue;
+        }
+        println!(
+            "{:>8} {:>14} {:>14} {:>9} {:>9}  verdict",
+            "phase", "a[us/block]", "b[us/block]", "delta", "±95%"
+        );
+        let blocks: usize = aligned.iter().map(|(c, _)| c.blocks).sum();
+        for (name, phase) in PHASES {
+            let time = |c: &Chunk| c.time(phase);
+            let per_block = |total: f64| total * 1e6 / blocks as f64;
+            let total_a: f64 = aligned.iter().map(|(a, _)| time(a)).sum();
+            let total_b: f64 = aligned.iter().map(|(_, b)| time(b)).sum();
+            // Relative change of each chunk's time
+            let deltas: Vec<f64> = aligned
+                .iter()
+                .filter(|(a, _)| time(a) > 0.0)
+                .map(|(a, b)| time(b) / time(a) - 1.0)
+                .collect();
+            if deltas.is_empty() {
+                continue;
+            }
+            let (delta, ci) = mean_ci(&deltas);
+            let verdict = match ci {
+                Some(ci) if delta - ci > 0.0 => "regression",
+                Some(ci) if delta + ci < 0.0 => "improvement",
+                Some(_) => "-",
+                None => "(too few chunks)",
+            };
+            println!(
+                "{:>8} {:>14.1} {:>14.1} {:>+8.1}% {:>8}  {}",
+                name,
+                per_block(total_a),
+                per_block(total_b),
+                delta * 100.0,
+                ci.map_or("-".to_owned(), |ci| format!("{:.1}%", ci * 100.0)),
+                verdict
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! The `compressor` subcommand: times `decompress_script` on each compressed script type in
+//! isolation, over generated scripts or those of saved `blockundo` responses, to tell whether
+//! decompressing uncompressed public keys (types 4 and 5) dominates. Also times
+//! `decompress_amount`.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    secp256k1::{Secp256k1, SecretKey},
+    ScriptBuf,
+};
+
+use crate::{decompress_amount, decompress_script, undo, Result, SPECIAL_SCRIPTS};
+
+/// Generated scripts per type.
+const GENERATED: usize = 1000;
+
+const TYPES: [&str; SPECIAL_SCRIPTS + 1] = [
+    "P2PKH",
+    "P2SH",
+    "P2PK (even)",
+    "P2PK (odd)",
+    "P2PK uncompressed (even)",
+    "P2PK uncompressed (odd)",
+    "raw",
+];
+
+/// Set by the `compressor` subcommand.
+pub struct Compressor {
+    /// Saved `blockundo` responses, instead of generated scripts
+    pub undo: Vec<PathBuf>,
+    /// Decompressions timed per script type
+    pub rounds: usize,
+}
+
+/// Compressed scripts of each type, and compressed amounts (as stored in undo data).
+struct Samples {
+    scripts: Vec<Vec<Vec<u8>>>,
+    amounts: Vec<u64>,
+}
+
+/// Bitcoin Core's `CompressAmount`.
+fn compress_amount(mut n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    let mut e = 0;
+    while n.is_multiple_of(10) && e < 9 {
+        n /= 10;
+        e += 1;
+    }
+    if e < 9 {
+        let d = n % 10;
+        n /= 10;
+        1 + (n * 9 + d - 1) * 10 + e
+    } else {
+        1 + (n - 1) * 10 + 9
+    }
+}
+
+/// Amounts with a few significant digits, as payments usually have.
+fn generate_amounts() -> Vec<u64> {
+    (0..GENERATED)
+        .map(|_| {
+            let digits = rand::random_range(1..10_000u64);
+            let zeros = rand::random_range(0..=8);
+            compress_amount(digits * 10u64.pow(zeros))
+        })
+        .collect()
+}
+
+/// Compressed scripts of each type (as stored in undo data), as Bitcoin Core's
+/// `ScriptCompression` would produce them.
+fn generate() -> Result<Vec<Vec<Vec<u8>>>> {
+    let mut scripts: Vec<Vec<Vec<u8>>> = vec![Vec::new(); SPECIAL_SCRIPTS + 1];
+    for (kind, scripts) in scripts[..4].iter_mut().enumerate() {
+        let size = if kind < 2 { 20 } else { 32 };
+        *scripts = (0..GENERATED)
+            .map(|_| (0..size).map(|_| rand::random()).collect())
+            .collect();
+    }
+    // Only the x coordinate is stored, so it must be on the curve
+    let secp = Secp256k1::signing_only();
+    while scripts[4].len() < GENERATED || scripts[5].len() < GENERATED {
+        let key = SecretKey::from_slice(&rand::random::<[u8; 32]>())?.public_key(&secp);
+        let key = key.serialize();
+        let kind = key[0] as usize + 2;
+        if scripts[kind].len() < GENERATED {
+            scripts[kind].push(key[1..].to_vec());
+        }
+    }
+    // Today's raw scripts are mostly P2WPKH and P2TR
+    scripts[SPECIAL_SCRIPTS] = (0..GENERATED)
+        .map(|i| {
+            let (version, size) = if i % 2 == 0 { (0x00, 20) } else { (0x51, 32) };
+            let mut script = vec![version, size as u8];
+            script.extend((0..size).map(|_| rand::random::<u8>()));
+            script
+        })
+        .collect();
+    Ok(scripts)
+}
+
+fn load(paths: &[PathBuf]) -> Result<Samples> {
+    let mut scripts = vec![Vec::new(); SPECIAL_SCRIPTS + 1];
+    let mut amounts = Vec::new();
+    for path in paths {
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        undo::visit(&data, |coin| {
+            scripts[coin.script.kind].push(coin.script.bytes.to_vec());
+            amounts.push(compress_amount(coin.amount));
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(Samples { scripts, amounts })
+}
+
+/// Times `decompress_amount` over `rounds` amounts, returning its nanoseconds per amount.
+fn amounts(amounts: &[u64], rounds: usize) -> f64 {
+    let t = Instant::now();
+    for &x in amounts.iter().cycle().take(rounds) {
+        std::hint::black_box(decompress_amount(std::hint::black_box(x)));
+    }
+    t.elapsed().as_secs_f64() * 1e9 / rounds as f64
+}
+
+/// Decompresses `rounds` scripts of `kind`, cycling through `scripts`.
+fn time(kind: usize, scripts: &[Vec<u8>], rounds: usize) -> Result<Duration> {
+    let mut script = ScriptBuf::new();
+    let t = Instant::now();
+    for bytes in scripts.iter().cycle().take(rounds) {
+        if kind < SPECIAL_SCRIPTS {
+            decompress_script(kind as u8, bytes, &mut script)?;
+        } else {
+            // As `script_decode` does
+            let mut buf = std::mem::take(&mut script).into_bytes();
+            buf.clear();
+            buf.extend_from_slice(bytes);
+            script = ScriptBuf::from_bytes(buf);
+        }
+        std::hint::black_box(&script);
+    }
+    Ok(t.elapsed())
+}
+
+pub fn run(compressor: &Compressor) -> Result<()> {
+    let loaded = !compressor.undo.is_empty();
+    let samples = if loaded {
+        load(&compressor.undo)?
+    } else {
+        Samples {
+            scripts: generate()?,
+            amounts: generate_amounts(),
+        }
+    };
+    let scripts = &samples.scripts;
+    let mut rows = Vec::with_capacity(scripts.len());
+    for (kind, scripts) in scripts.iter().enumerate() {
+        if scripts.is_empty() {
+            continue;
+        }
+        let elapsed = time(kind, scripts, compressor.rounds)?;
+        let ns = elapsed.as_secs_f64() * 1e9 / compressor.rounds as f64;
+        rows.push((kind, scripts.len(), ns));
+    }
+    // Of the loaded scripts' total decompression time
+    let total: f64 = rows.iter().map(|&(_, n, ns)| n as f64 * ns).sum();
+    println!(
+        "{} scripts, {} decompressions per type",
+        if loaded { "loaded" } else { "generated" },
+        compressor.rounds
+    );
+    println!(
+        "{:>4} {:<26} {:>10} {:>10} {:>8}",
+        "type", "script", "scripts", "[ns/op]", "share"
+    );
+    for (kind, n, ns) in rows {
+        let share = if loaded {
+            format!("{:.1}%", 100.0 * n as f64 * ns / total)
+        } else {
+            "-".to_owned()
+        };
+        println!(
+            "{:>4} {:<26} {:>10} {:>10.1} {:>8}",
+            kind, TYPES[kind], n, ns, share
+        );
+    }
+    if !samples.amounts.is_empty() {
+        let ns = amounts(&samples.amounts, compressor.rounds);
+        println!("amounts: {:.1}[ns/op]", ns);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_amount, decompress_amount};
+
+    /// `decompress_amount` as it was before its power-of-ten table (Bitcoin Core's loop).
+    fn decompress_amount_loop(mut x: u64) -> u64 {
+        if x == 0 {
+            return 0;
+        }
+        x -= 1;
+        let mut e = x % 10;
+        x /= 10;
+        let mut n = if e < 9 {
+            let d = (x % 9) + 1;
+            x /= 9;
+            x * 10 + d
+        } else {
+            x + 1
+        };
+        while e != 0 {
+            n = n.wrapping_mul(10);
+            e -= 1;
+        }
+        n
+    }
+
+    #[test]
+    fn decompress_matches_loop() {
+        let near_max = (u64::MAX - 1000)..=u64::MAX;
+        for x in (0..1_000_000).chain(near_max) {
+            assert_eq!(decompress_amount(x), decompress_amount_loop(x), "{}", x);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        assert_eq!(compress_amount(0), 0);
+        assert_eq!(decompress_amount(0), 0);
+        // Mantissas of 1 to 9 digits, with up to 10 trailing zeros
+        for digits in 1..=9 {
+            let mantissas = [
+                10u64.pow(digits - 1),
+                10u64.pow(digits) - 1,
+                123456789 % 10u64.pow(digits),
+            ];
+            for m in mantissas.into_iter().filter(|&m| m > 0) {
+                for zeros in 0..=10 {
+                    let n = m * 10u64.pow(zeros);
+                    assert_eq!(decompress_amount(compress_amount(n)), n, "{}", n);
+                }
+            }
+        }
+        // Near `u64::MAX`, as far as the compressed amount still fits (`u64::MAX` itself doesn't)
+        for n in [
+            u64::MAX / 10,
+            u64::MAX - 5,
+            18 * 10u64.pow(18),
+            10u64.pow(19),
+        ] {
+            assert_eq!(decompress_amount(compress_amount(n)), n, "{}", n);
+            assert_eq!(decompress_amount_loop(compress_amount(n)), n, "{}", n);
+        }
+    }
+}
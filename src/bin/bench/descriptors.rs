@@ -0,0 +1,88 @@
+//! `--descriptor`: output descriptors (e.g. `wpkh(xpub.../0/*)`) watched by the wallet
+//! benchmarks, deriving their scripts up to `--gap-limit` past the last one used.
+
+use std::str::FromStr;
+
+use bitcoin::{
+    secp256k1::{Secp256k1, VerifyOnly},
+    ScriptBuf,
+};
+use miniscript::{Descriptor, DescriptorPublicKey};
+
+use crate::{Args, Result};
+
+pub struct Descriptors {
+    secp: Secp256k1<VerifyOnly>,
+    /// Single-path descriptors (multipath ones, e.g. `.../<0;1>/*`, are split), each with the
+    /// number of its scripts derived so far
+    descriptors: Vec<(Descriptor<DescriptorPublicKey>, u32)>,
+    gap: u32,
+}
+
+impl Descriptors {
+    pub fn parse(args: &Args) -> Result<Self> {
+        let mut descriptors = Vec::new();
+        for s in &args.descriptors {
+            let desc = Descriptor::<DescriptorPublicKey>::from_str(s)
+                .and_then(Descriptor::into_single_descriptors)
+                .map_err(|e| format!("--descriptor {}: {}", s, e))?;
+            descriptors.extend(desc.into_iter().map(|d| (d, 0)));
+        }
+        Ok(Descriptors {
+            secp: Secp256k1::verification_only(),
+            descriptors,
+            gap: args.gap_limit,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.descriptors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.descriptors.is_empty()
+    }
+
+    /// Derives the first `--gap-limit` scripts of each descriptor, returning each along with its
+    /// descriptor's position and derivation index.
+    pub fn initial(&mut self) -> Result<Vec<(usize, u32, ScriptBuf)>> {
+        let mut scripts = Vec::new();
+        for d in 0..self.descriptors.len() {
+            scripts.extend(self.derive(d, self.gap.max(1))?);
+        }
+        Ok(scripts)
+    }
+
+    /// Marks `index` of descriptor `d` as used, deriving the scripts up to `--gap-limit` past it.
+    pub fn used(&mut self, d: usize, index: u32) -> Result<Vec<(usize, u32, ScriptBuf)>> {
+        self.derive(d, index.saturating_add(self.gap).saturating_add(1))
+    }
+
+    /// Derives the scripts of descriptor `d` below `end`, which weren't derived before (a
+    /// descriptor without a wildcard has only one).
+    fn derive(&mut self, d: usize, end: u32) -> Result<Vec<(usize, u32, ScriptBuf)>> {
+        let (desc, derived) = &mut self.descriptors[d];
+        let end = if desc.has_wildcard() { end } else { 1 };
+        let mut scripts = Vec::with_capacity(end.saturating_sub(*derived) as usize);
+        for i in *derived..end {
+            let script = desc.derived_descriptor(&self.secp, i)?.script_pubkey();
+            scripts.push((d, i, script));
+        }
+        *derived = (*derived).max(end);
+        Ok(scripts)
+    }
+
+    pub fn label(&self, d: usize, index: u32) -> String {
+        let desc = &self.descriptors[d].0;
+        if desc.has_wildcard() {
+            format!("{:#} at {}", desc, index)
+        } else {
+            format!("{:#}", desc)
+        }
+    }
+
+    /// Scripts derived so far.
+    pub fn derived(&self) -> u64 {
+        self.descriptors.iter().map(|(_, n)| *n as u64).sum()
+    }
+}
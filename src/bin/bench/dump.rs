@@ -0,0 +1,101 @@
+//! `--dump-undo` and `--dump-scripts`: one JSON line per decoded spent coin or scriptPubKey, for
+//! diffing the decoders' output with the node's (e.g. `getblock <hash> 3`'s `prevout`s) when
+//! investigating a discrepancy.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use bitcoin::Script;
+use serde::Serialize;
+
+use crate::{script_type::ScriptType, Result};
+
+pub struct Dump {
+    writer: BufWriter<File>,
+    lines: u64,
+}
+
+/// A spent coin, as decoded from undo data.
+pub struct UndoCoin {
+    /// Of the spending block
+    pub height: usize,
+    /// Of the spending transaction within the block (1 for the first after the coinbase)
+    pub tx: usize,
+    pub vin: usize,
+    /// Of the block that created the coin
+    pub coin_height: u32,
+    pub coinbase: bool,
+    pub value: u64,
+}
+
+#[derive(Serialize)]
+struct CoinLine<'a> {
+    height: usize,
+    tx: usize,
+    vin: usize,
+    coin_height: u32,
+    coinbase: bool,
+    value: u64,
+    script_hex: &'a str,
+    script_asm: &'a str,
+}
+
+#[derive(Serialize)]
+struct ScriptLine<'a> {
+    height: usize,
+    endpoint: &'a str,
+    #[serde(rename = "type")]
+    script_type: &'a str,
+    script_hex: &'a str,
+    script_asm: &'a str,
+}
+
+impl Dump {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Dump {
+            writer: BufWriter::new(file),
+            lines: 0,
+        })
+    }
+
+    pub fn write_coin(&mut self, coin: &UndoCoin, script: &Script) -> Result<()> {
+        self.write(&CoinLine {
+            height: coin.height,
+            tx: coin.tx,
+            vin: coin.vin,
+            coin_height: coin.coin_height,
+            coinbase: coin.coinbase,
+            value: coin.value,
+            script_hex: &script.to_hex_string(),
+            script_asm: &script.to_asm_string(),
+        })
+    }
+
+    /// A scriptPubKey decoded from the `endpoint`'s response of the block at `height`.
+    pub fn write_script(&mut self, height: usize, endpoint: &str, script: &Script) -> Result<()> {
+        self.write(&ScriptLine {
+            height,
+            endpoint,
+            script_type: ScriptType::of(script).name(),
+            script_hex: &script.to_hex_string(),
+            script_asm: &script.to_asm_string(),
+        })
+    }
+
+    fn write(&mut self, line: &impl Serialize) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        self.writer.write_all(b"\n")?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Returns the number of lines written.
+    pub fn finish(mut self) -> Result<u64> {
+        self.writer.flush()?;
+        Ok(self.lines)
+    }
+}
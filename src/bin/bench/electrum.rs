@@ -0,0 +1,389 @@
+//! Electrum protocol transport (`--backend electrum`): newline-delimited JSON-RPC over TCP,
+//! serving the benchmarks' requests (see `backend::BlockUrl`) via the protocol's methods:
+//!
+//! - `tcp://host:port/header/<height>`: `blockchain.block.header`, as the raw header
+//! - `tcp://host:port/block/<height>`: the header, and each transaction (found by
+//!   `blockchain.transaction.id_from_pos`) via `blockchain.transaction.get`, as a raw block
+//! - `tcp://host:port/rpc/<method>/<params>...`: any method (with integer parameters), as its
+//!   JSON result
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Cursor, Write},
+    net::TcpStream,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    block::Header,
+    consensus::{deserialize, encode::VarInt, Encodable},
+    merkle_tree, Txid,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    http::{IoError, Response, Transport},
+    rpc::hex,
+    Result,
+};
+
+pub const SCHEME: &str = "tcp://";
+
+/// Calls per JSON-RPC batch, when fetching a block's transactions.
+const BATCH: usize = 100;
+
+/// Electrum servers report a missing transaction position as an error, ending the block.
+type CallResult = std::result::Result<Value, Value>;
+
+/// Whether `error` reports a transaction position past the block's end, as ElectrumX's and
+/// Fulcrum's "no tx at position" and electrs' "invalid tx_pos" do (other errors, e.g. rate
+/// limits, don't end the block).
+fn is_past_end(error: &Value) -> bool {
+    let message = error["message"]
+        .as_str()
+        .or(error.as_str())
+        .unwrap_or_default();
+    let message = message.to_lowercase();
+    message.contains("position") || message.contains("tx_pos")
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    line: String,
+}
+
+impl Connection {
+    /// Connects to `addr`, and negotiates the protocol version (which Fulcrum requires first).
+    fn open(addr: &str) -> Result<(Self, Duration)> {
+        let t = Instant::now();
+        let stream = TcpStream::connect(addr).map_err(|e| IoError::new(addr, e))?;
+        let connect = t.elapsed();
+        stream.set_nodelay(true)?;
+        let mut conn = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            line: String::new(),
+        };
+        conn.call(addr, "server.version", json!(["bench", "1.4"]))?;
+        Ok((conn, connect))
+    }
+
+    /// Sends `calls` as a single batch (unless there's one), returning their results in order.
+    fn batch(&mut self, addr: &str, calls: &[(&str, Value)]) -> Result<Vec<CallResult>> {
+        let requests: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+            })
+            .collect();
+        let mut request = match &requests[..] {
+            [single] => serde_json::to_vec(single)?,
+            _ => serde_json::to_vec(&requests)?,
+        };
+        request.push(b'\n');
+        self.writer
+            .write_all(&request)
+            .map_err(|e| IoError::new(addr, e))?;
+
+        self.line.clear();
+        let n = self
+            .reader
+            .read_line(&mut self.line)
+            .map_err(|e| IoError::new(addr, e))?;
+        if n == 0 {
+            let eof = io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed");
+            return Err(IoError::new(addr, eof).into());
+        }
+        let replies = match serde_json::from_str(&self.line)? {
+            Value::Array(replies) => replies,
+            reply => vec![reply],
+        };
+        let mut results = vec![Err(Value::Null); calls.len()];
+        for mut reply in replies {
+            let Some(id) = reply["id"].as_u64().filter(|&id| id < calls.len() as u64) else {
+                return Err(format!("{}: unexpected reply {}", addr, reply).into());
+            };
+            results[id as usize] = match reply["error"].take() {
+                Value::Null => Ok(reply["result"].take()),
+                error => Err(error),
+            };
+        }
+        Ok(results)
+    }
+
+    fn call(&mut self, addr: &str, method: &str, params: Value) -> Result<Value> {
+        let result = self
+            .batch(addr, &[(method, params)])?
+            .pop()
+            .expect("one call");
+        result.map_err(|e| format!("{}: {}: {}", addr, method, e).into())
+    }
+
+    fn header(&mut self, addr: &str, height: u64) -> Result<Vec<u8>> {
+        let header = self.call(addr, "blockchain.block.header", json!([height]))?;
+        hex(&header)
+    }
+
+    /// Assembles the block at `height` from its header and transactions.
+    fn block(&mut self, addr: &str, height: u64) -> Result<Vec<u8>> {
+        let mut block = self.header(addr, height)?;
+        let mut txids = Vec::new();
+        'positions: loop {
+            let calls: Vec<_> = (txids.len()..txids.len() + BATCH)
+                .map(|pos| ("blockchain.transaction.id_from_pos", json!([height, pos])))
+                .collect();
+            for result in self.batch(addr, &calls)? {
+                match result {
+                    Ok(txid) => {
+                        let txid = txid.as_str().and_then(|txid| txid.parse::<Txid>().ok());
+                        let txid = txid.ok_or_else(|| {
+                            format!(
+                                "{}: block {}: invalid txid at {}",
+                                addr,
+                                height,
+                                txids.len()
+                            )
+                        })?;
+                        txids.push(txid);
+                    }
+                    Err(e) if !txids.is_empty() && is_past_end(&e) => break 'positions,
+                    Err(e) => return Err(format!("{}: block {}: {}", addr, height, e).into()),
+                }
+            }
+        }
+        // Missing (or extra) transactions would still parse as a block
+        let header: Header = deserialize(&block)?;
+        let root = merkle_tree::calculate_root(txids.iter().map(|txid| txid.to_raw_hash()));
+        if root.map(Into::into) != Some(header.merkle_root) {
+            return Err(format!(
+                "{}: block {}: {} txids don't match the header's merkle root",
+                addr,
+                height,
+                txids.len()
+            )
+            .into());
+        }
+        VarInt(txids.len() as u64).consensus_encode(&mut block)?;
+        for chunk in txids.chunks(BATCH) {
+            let calls: Vec<_> = chunk
+                .iter()
+                .map(|txid| ("blockchain.transaction.get", json!([txid.to_string()])))
+                .collect();
+            for result in self.batch(addr, &calls)? {
+                let tx = result.map_err(|e| format!("{}: block {}: {}", addr, height, e))?;
+                block.extend(hex(&tx)?);
+            }
+        }
+        Ok(block)
+    }
+}
+
+/// Keeps the connections (per server) between requests, opening more for concurrent ones.
+#[derive(Default)]
+pub struct Electrum {
+    idle: Mutex<HashMap<String, Vec<Connection>>>,
+}
+
+impl Electrum {
+    fn request(&self, conn: &mut Connection, addr: &str, path: &str) -> Result<Vec<u8>> {
+        let mut parts = path.split('/');
+        let kind = parts.next().unwrap_or_default();
+        let mut height = || -> Result<u64> {
+            let height = parts.next().unwrap_or_default();
+            Ok(height
+                .parse()
+                .map_err(|_| format!("invalid height {:?}", height))?)
+        };
+        match kind {
+            "header" => conn.header(addr, height()?),
+            "block" => conn.block(addr, height()?),
+            "rpc" => {
+                let method = parts.next().unwrap_or_default();
+                let params = parts
+                    .map(|p| p.parse::<u64>().map(Value::from))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(serde_json::to_vec(&conn.call(
+                    addr,
+                    method,
+                    params.into(),
+                )?)?)
+            }
+            _ => Err(format!("unsupported Electrum request {:?}", path).into()),
+        }
+    }
+}
+
+impl Transport for Electrum {
+    /// Ignores the headers (e.g. `--auth`, `--accept-encoding`), which Electrum doesn't support.
+    fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response> {
+        let rest = url
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("{}: not an Electrum URL (expected {}...)", url, SCHEME))?;
+        let (addr, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let idle = self.idle.lock().unwrap().get_mut(addr).and_then(Vec::pop);
+        let (mut conn, connect) = match idle {
+            Some(conn) => (conn, None),
+            None => Connection::open(addr).map(|(conn, t)| (conn, Some(t)))?,
+        };
+        let result = self.request(&mut conn, addr, path);
+        // After IO errors, the connection may be out of sync with the server
+        if !matches!(&result, Err(e) if e.is::<IoError>()) {
+            let mut idle = self.idle.lock().unwrap();
+            idle.entry(addr.to_owned()).or_default().push(conn);
+        }
+        let body = result?;
+        Ok(Response {
+            body: Box::new(Cursor::new(body)),
+            content_encoding: None,
+            content_length: None,
+            status: 200,
+            connect,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use bitcoin::{
+        absolute::LockTime,
+        block,
+        consensus::{encode::serialize_hex, serialize},
+        hashes::Hash,
+        transaction::Version,
+        Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Transaction, TxIn,
+        TxMerkleNode, TxOut,
+    };
+    use serde_json::{json, Value};
+
+    use super::{Electrum, Transport, BATCH};
+    use crate::http::IoError;
+
+    /// A block of `n` transactions, with a valid merkle root.
+    fn block(n: usize) -> Block {
+        let txdata = (0..n).map(|i| Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(i as u32),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(i as u64),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        });
+        let mut block = Block {
+            header: block::Header {
+                version: block::Version::TWO,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0),
+                nonce: 0,
+            },
+            txdata: txdata.collect(),
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block
+    }
+
+    /// The reply to a `request` for `block` (at any height), listing only its first `listed`
+    /// transactions, and replying `past_end` for the positions after them.
+    fn reply(block: &Block, listed: usize, past_end: &str, request: &Value) -> Value {
+        let params = &request["params"];
+        let result = match request["method"].as_str().unwrap() {
+            "server.version" => Ok(json!(["mock", "1.4"])),
+            "blockchain.block.header" => Ok(json!(serialize_hex(&block.header))),
+            "blockchain.transaction.id_from_pos" => {
+                let pos = params[1].as_u64().unwrap() as usize;
+                match block.txdata.get(pos).filter(|_| pos < listed) {
+                    Some(tx) => Ok(json!(tx.compute_txid().to_string())),
+                    None => Err(json!({ "code": 1, "message": past_end })),
+                }
+            }
+            "blockchain.transaction.get" => {
+                let txid = params[0].as_str().unwrap();
+                let mut txs = block.txdata.iter();
+                let tx = txs.find(|tx| tx.compute_txid().to_string() == txid);
+                Ok(json!(serialize_hex(tx.unwrap())))
+            }
+            method => panic!("unexpected {}", method),
+        };
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": request["id"], "error": error }),
+        }
+    }
+
+    /// Serves `block` to each connection in turn, as `reply` does.
+    fn serve(block: Block, listed: usize, past_end: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                for line in BufReader::new(stream).lines() {
+                    let reply = |request| reply(&block, listed, past_end, request);
+                    let reply = match serde_json::from_str(&line.unwrap()).unwrap() {
+                        Value::Array(requests) => requests.iter().map(reply).collect(),
+                        request => reply(&request),
+                    };
+                    let mut reply = serde_json::to_vec(&reply).unwrap();
+                    reply.push(b'\n');
+                    writer.write_all(&reply).unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    fn get(addr: &str, path: &str) -> crate::Result<Vec<u8>> {
+        let response = Electrum::default().get(&format!("tcp://{}/{}", addr, path), &[])?;
+        let mut body = vec![];
+        response.body.take(1 << 20).read_to_end(&mut body)?;
+        Ok(body)
+    }
+
+    #[test]
+    fn pages_transactions() {
+        // Ending within the second batch, and at its end
+        for n in [1, BATCH + 50, 2 * BATCH] {
+            let block = block(n);
+            let addr = serve(block.clone(), n, "No tx at position");
+            assert_eq!(get(&addr, "block/7").unwrap(), serialize(&block), "{}", n);
+        }
+        let block = block(3);
+        let addr = serve(block.clone(), 3, "invalid tx_pos");
+        assert_eq!(get(&addr, "header/7").unwrap(), serialize(&block.header));
+        assert_eq!(get(&addr, "block/7").unwrap(), serialize(&block));
+    }
+
+    #[test]
+    fn other_errors_fail() {
+        let addr = serve(block(BATCH + 1), BATCH + 1, "rate limited");
+        let err = get(&addr, "block/7").unwrap_err();
+        assert!(err.to_string().contains("rate limited"), "{}", err);
+        assert!(!err.is::<IoError>());
+    }
+
+    #[test]
+    fn checks_merkle_root() {
+        let addr = serve(block(BATCH + 1), BATCH, "No tx at position");
+        let err = get(&addr, "block/7").unwrap_err();
+        let expected = format!(
+            "block 7: {} txids don't match the header's merkle root",
+            BATCH
+        );
+        assert!(err.to_string().ends_with(&expected), "{}", err);
+    }
+}
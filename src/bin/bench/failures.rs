@@ -0,0 +1,105 @@
+//! `--on-error`: what to do when a benchmarked request fails, and counts of failures by cause.
+
+use std::{collections::BTreeMap, error::Error, fmt, io, sync::Mutex};
+
+use clap::ValueEnum;
+
+use crate::http::StatusError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OnError {
+    /// Stop the run
+    Abort,
+    /// Leave the block out of all results
+    Skip,
+    /// Send the request again (up to `--retries` times), then stop the run
+    Retry,
+}
+
+/// Why a request failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Failure {
+    Status(u16),
+    /// A 404 for a block the node has pruned
+    Pruned,
+    Io(io::ErrorKind),
+}
+
+impl Failure {
+    /// Finds an HTTP status or IO error in `error`'s source chain.
+    pub fn of(error: &(dyn Error + 'static)) -> Option<Self> {
+        let mut cause = Some(error);
+        while let Some(e) = cause {
+            if let Some(e) = e.downcast_ref::<RequestError>() {
+                return Some(e.failure);
+            }
+            if let Some(e) = e.downcast_ref::<StatusError>() {
+                if e.is_pruned() {
+                    return Some(Failure::Pruned);
+                }
+                return Some(Failure::Status(e.status));
+            }
+            if let Some(e) = e.downcast_ref::<io::Error>() {
+                return Some(Failure::Io(e.kind()));
+            }
+            cause = e.source();
+        }
+        None
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Status(status) => write!(f, "HTTP {}", status),
+            Failure::Pruned => f.write_str("pruned"),
+            Failure::Io(kind) => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+/// A request that failed despite `--on-error`, distinguished from decoding errors (which always
+/// stop the run). Can be sent across threads, unlike the error it wraps.
+pub struct RequestError {
+    pub failure: Failure,
+    message: String,
+}
+
+impl RequestError {
+    pub fn new(failure: Failure, error: &dyn Error) -> Self {
+        RequestError {
+            failure,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Like the other (string) errors returned from `main`.
+impl fmt::Debug for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.message, f)
+    }
+}
+
+impl Error for RequestError {}
+
+/// Failed attempts (including retried ones) of one client's benchmarked requests.
+#[derive(Default)]
+pub struct Failures(Mutex<BTreeMap<Failure, u64>>);
+
+impl Failures {
+    pub fn add(&self, failure: Failure) {
+        *self.0.lock().unwrap().entry(failure).or_default() += 1;
+    }
+
+    /// Returns the counts since the previous call.
+    pub fn take(&self) -> BTreeMap<Failure, u64> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
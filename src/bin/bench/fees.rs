@@ -0,0 +1,132 @@
+//! `FeeRates` benchmark: per-transaction fee rates, from a block and its undo data.
+
+use std::ops::ControlFlow;
+
+use bitcoin_slices::{bsl, Visit};
+
+use crate::{benchmarks::Benchmark, check_consumed, undo, Args, Counters, Result, Scratch};
+
+pub struct FeeRates;
+
+impl FeeRates {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(FeeRates))
+    }
+}
+
+impl Benchmark for FeeRates {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        decode(responses[0], responses[1], counters, scratch)
+    }
+}
+
+/// Matches each transaction of the block with its spent value, in order.
+struct FeeVisitor<'a> {
+    counters: &'a mut Counters,
+    tx_inputs: &'a [(u64, usize)],
+    tx: usize,
+    inputs: usize,
+    outputs: u64,
+    error: Option<String>,
+}
+
+impl FeeVisitor<'_> {
+    /// Returns the fee of the current (non-coinbase) transaction.
+    fn fee(&self) -> std::result::Result<u64, String> {
+        let &(spent, inputs) = self
+            .tx_inputs
+            .get(self.tx - 1)
+            .ok_or_else(|| format!("tx {}: missing from undo data", self.tx))?;
+        if inputs != self.inputs {
+            return Err(format!(
+                "tx {}: {} inputs != {} undo coins",
+                self.tx, self.inputs, inputs
+            ));
+        }
+        spent
+            .checked_sub(self.outputs)
+            .ok_or_else(|| format!("tx {}: outputs exceed spent value", self.tx))
+    }
+}
+
+impl bitcoin_slices::Visitor for FeeVisitor<'_> {
+    fn visit_tx_ins(&mut self, total_inputs: usize) {
+        // Called twice for segwit transactions (the first time for the marker)
+        self.inputs = total_inputs;
+    }
+
+    fn visit_tx_out(&mut self, _vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+        self.outputs += tx_out.value();
+        ControlFlow::Continue(())
+    }
+
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<()> {
+        if self.tx > 0 {
+            match self.fee() {
+                Ok(fee) => self
+                    .counters
+                    .fee_rates
+                    .add_fee(fee, tx.weight().div_ceil(4)),
+                Err(e) => {
+                    self.error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+        self.tx += 1;
+        self.outputs = 0;
+        ControlFlow::Continue(())
+    }
+}
+
+/// Sums the spent coins of each transaction from `undo`, then visits `block` to compute the fees.
+fn decode(block: &[u8], undo: &[u8], counters: &mut Counters, scratch: &mut Scratch) -> Result<()> {
+    let mut tx_inputs = std::mem::take(&mut scratch.tx_inputs);
+    tx_inputs.clear();
+    // Sized up front, as transactions without inputs have no coins to visit
+    let tx_count = undo::tx_count(undo)?;
+    tx_inputs.resize(tx_count, (0, 0));
+    let rest = undo::visit_txs(undo, |tx, coin| {
+        counters.count += 1;
+        counters.count_by_type[coin.script.kind] += 1;
+        counters.spent += coin.amount as u128;
+        counters.add_spent_coin(scratch, coin.height, coin.amount);
+        let (spent, inputs) = &mut tx_inputs[tx];
+        *spent += coin.amount;
+        *inputs += 1;
+    })?;
+    check_consumed(rest, counters, scratch.height, "undo data");
+
+    let mut visitor = FeeVisitor {
+        counters,
+        tx_inputs: &tx_inputs,
+        tx: 0,
+        inputs: 0,
+        outputs: 0,
+        error: None,
+    };
+    let result = bsl::Block::visit(block, &mut visitor);
+    let (tx, error) = (visitor.tx, visitor.error.take());
+    scratch.tx_inputs = tx_inputs;
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    let rest = result
+        .map_err(|e| format!("invalid block: {:?}", e))?
+        .remaining()
+        .len();
+    check_consumed(rest, counters, scratch.height, "block");
+    if tx != tx_count + 1 {
+        return Err(format!(
+            "{} transactions != {} in undo data (+ coinbase)",
+            tx, tx_count
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! `FilterQuery` benchmark: matches the `--addresses` (and the first `--gap-limit` scripts of each
+//! `--descriptor`) against each block's BIP158 basic filter, as a light client scanning for its
+//! wallet would (only fetching the blocks that match).
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{bip158::BlockFilter, ScriptBuf};
+
+use crate::{
+    benchmarks::Benchmark, descriptors::Descriptors, filters::parse_filter,
+    wallet::watched_scripts, Args, Counters, Result, Scratch,
+};
+
+pub struct FilterQuery {
+    scripts: Vec<ScriptBuf>,
+    filters: u64,
+    /// Of the filters' content
+    bytes: u64,
+    /// Filters matching any of the scripts
+    matched: u64,
+    /// Decoding and querying the filters (excluding parsing the responses)
+    querying: Duration,
+}
+
+impl FilterQuery {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        let mut scripts: Vec<ScriptBuf> = watched_scripts(args, "filter-query")?
+            .into_iter()
+            .map(|(_, script)| script)
+            .collect();
+        let derived = Descriptors::parse(args)?.initial()?;
+        scripts.extend(derived.into_iter().map(|(_, _, script)| script));
+        tracing::info!("filter-query: matching {} scripts", scripts.len());
+        Ok(Box::new(FilterQuery {
+            scripts,
+            filters: 0,
+            bytes: 0,
+            matched: 0,
+            querying: Duration::ZERO,
+        }))
+    }
+}
+
+impl Benchmark for FilterQuery {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let (hash, content) = parse_filter(responses[0])?;
+        let filter = BlockFilter { content };
+
+        let t = Instant::now();
+        let queries = self.scripts.iter().map(|script| script.as_bytes());
+        let matched = filter.match_any(&hash, queries)?;
+        self.querying += t.elapsed();
+
+        self.filters += 1;
+        self.bytes += filter.content.len() as u64;
+        counters.count += self.scripts.len() as u64;
+        if matched {
+            self.matched += 1;
+            tracing::debug!("filter-query: block {} matched", scratch.height);
+        }
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let querying = self.querying.as_secs_f64();
+        tracing::info!(
+            "filter-query: {} of {} filters matched ({:.2}%); {} filter bytes queried in {:.3}[s], \
+             {:.0}[filters/s]",
+            self.matched,
+            self.filters,
+            100.0 * self.matched as f64 / self.filters.max(1) as f64,
+            self.bytes,
+            querying,
+            self.filters as f64 / querying.max(f64::MIN_POSITIVE)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! `BlockFilters` benchmark: builds each block's BIP158 basic filter from the block and its spent
+//! outputs, and compares it with the node's `blockfilter/basic` response.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    bip158::BlockFilter,
+    consensus::{encode::VarInt, Decodable},
+    io::Cursor,
+    Block, BlockHash, OutPoint, Script, TxOut,
+};
+
+use crate::{benchmarks::Benchmark, spenttxouts_parse, Args, Counters, Result, Scratch};
+
+/// Logged per run, so that a broken endpoint doesn't flood the output.
+const MAX_REPORTED: u64 = 10;
+
+/// As serialized by Bitcoin Core's REST interface.
+const BASIC_FILTER_TYPE: u8 = 0;
+
+#[derive(Default)]
+pub struct BlockFilters {
+    spent: Vec<Vec<TxOut>>,
+    filters: u64,
+    /// Of the locally built filters
+    bytes: u64,
+    mismatches: u64,
+    /// Building the filters (excluding decoding)
+    building: Duration,
+}
+
+impl BlockFilters {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::<BlockFilters>::default())
+    }
+}
+
+/// Returns the filter's block hash and content.
+pub fn parse_filter(data: &[u8]) -> Result<(BlockHash, Vec<u8>)> {
+    let mut c = Cursor::new(data);
+    let filter_type = u8::consensus_decode(&mut c)?;
+    if filter_type != BASIC_FILTER_TYPE {
+        return Err(format!("unexpected filter type {}", filter_type).into());
+    }
+    let hash = BlockHash::consensus_decode(&mut c)?;
+    let len = VarInt::consensus_decode(&mut c)?.0 as usize;
+    let start = c.position() as usize;
+    let content = data
+        .get(start..start + len)
+        .ok_or_else(|| format!("truncated filter ({} of {} bytes)", data.len() - start, len))?;
+    Ok((hash, content.to_vec()))
+}
+
+impl Benchmark for BlockFilters {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let height = scratch.height;
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+        spenttxouts_parse(responses[1], &mut self.spent)?;
+        let (hash, expected) = parse_filter(responses[2])?;
+        if hash != block.block_hash() {
+            return Err(format!("block {}: filter of {}", height, hash).into());
+        }
+
+        let t = Instant::now();
+        let inputs = block.txdata.iter().skip(1).flat_map(|tx| &tx.input);
+        let coins: HashMap<OutPoint, &Script> = inputs
+            .zip(self.spent.iter().flatten())
+            .map(|(txin, txout)| (txin.previous_output, txout.script_pubkey.as_script()))
+            .collect();
+        let filter = BlockFilter::new_script_filter(&block, |outpoint| {
+            coins
+                .get(outpoint)
+                .copied()
+                .ok_or(bitcoin::bip158::Error::UtxoMissing(*outpoint))
+        })?;
+        self.building += t.elapsed();
+
+        self.filters += 1;
+        self.bytes += filter.content.len() as u64;
+        counters.count += coins.len() as u64;
+        if filter.content != expected {
+            self.mismatches += 1;
+            counters.mismatches += 1;
+            if self.mismatches <= MAX_REPORTED {
+                tracing::warn!(
+                    "filters: block {}: built {} bytes, but the node's filter has {} bytes{}",
+                    height,
+                    filter.content.len(),
+                    expected.len(),
+                    if filter.content.len() == expected.len() {
+                        " (with different content)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let building = self.building.as_secs_f64();
+        tracing::info!(
+            "filters: {} filters ({} bytes) built in {:.3}[s], {:.0}[filters/s]; {} mismatching \
+             the node's",
+            self.filters,
+            self.bytes,
+            building,
+            self.filters as f64 / building.max(f64::MIN_POSITIVE),
+            self.mismatches
+        );
+        Ok(())
+    }
+}
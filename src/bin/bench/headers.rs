@@ -0,0 +1,246 @@
+//! `Headers` benchmark: fetches each block's header, checks its proof of work and difficulty
+//! transitions, and accumulates the range's chainwork and timestamp statistics.
+
+use std::{collections::VecDeque, fmt};
+
+use bitcoin::{
+    block::Header, consensus::Decodable, io::Cursor, params::Params, pow::Work, CompactTarget,
+};
+
+use crate::{benchmarks::Benchmark, Args, Counters, Result, Scratch, DIFFICULTY_PERIOD};
+
+/// Logged per run, so that a broken endpoint doesn't flood the output.
+const MAX_REPORTED: u64 = 10;
+
+/// Blocks whose timestamps' median is the median-time-past (MTP) of the next block.
+const MTP_BLOCKS: usize = 11;
+
+/// A timestamp this far (in seconds) before its predecessor's is reported.
+const LARGE_NEGATIVE_DRIFT: i64 = -3600;
+
+/// Upper bounds (exclusive, in seconds) and labels of the drift buckets, the last one being
+/// unbounded.
+const DRIFT_BUCKETS: [(i64, &str); 8] = [
+    (LARGE_NEGATIVE_DRIFT, "<-1h"),
+    (0, "<0"),
+    (60, "<1m"),
+    (600, "<10m"),
+    (1800, "<30m"),
+    (3600, "<1h"),
+    (7200, "<2h"),
+    (i64::MAX, ">=2h"),
+];
+
+/// Histogram of timestamp differences.
+#[derive(Default)]
+struct Drifts([u64; DRIFT_BUCKETS.len()]);
+
+impl Drifts {
+    fn add(&mut self, drift: i64) {
+        let bucket = DRIFT_BUCKETS
+            .iter()
+            .position(|(bound, _)| drift < *bound)
+            .unwrap_or(DRIFT_BUCKETS.len() - 1);
+        self.0[bucket] += 1;
+    }
+}
+
+impl fmt::Debug for Drifts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let labels = DRIFT_BUCKETS.iter().map(|(_, label)| label);
+        f.debug_map().entries(labels.zip(&self.0)).finish()
+    }
+}
+
+pub struct Headers {
+    params: &'static Params,
+    headers: u64,
+    /// Of the headers so far, or `None` before the first one
+    chainwork: Option<Work>,
+    /// The previous header and its height, to check difficulty transitions of consecutive blocks
+    prev: Option<(usize, Header)>,
+    /// The timestamp of the current difficulty period's first block
+    period_start: Option<(usize, u32)>,
+    /// Hashes above their own target
+    bad_pow: u64,
+    /// `nBits` differing from the expected (retargeted or unchanged) value
+    bad_bits: u64,
+    /// Timestamps of the latest consecutive headers, up to `MTP_BLOCKS`
+    times: VecDeque<u32>,
+    /// Timestamp minus the previous block's
+    since_prev: Drifts,
+    /// Timestamp minus MTP
+    since_mtp: Drifts,
+    /// Timestamps not after their MTP (invalid by consensus)
+    bad_time: u64,
+    /// Timestamps at least an hour before their predecessor's
+    negative_drifts: u64,
+}
+
+impl Headers {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(Headers {
+            params: args.network.bitcoin().into(),
+            headers: 0,
+            chainwork: None,
+            prev: None,
+            period_start: None,
+            bad_pow: 0,
+            bad_bits: 0,
+            times: VecDeque::with_capacity(MTP_BLOCKS),
+            since_prev: Drifts::default(),
+            since_mtp: Drifts::default(),
+            bad_time: 0,
+            negative_drifts: 0,
+        }))
+    }
+
+    /// Records the timestamp differences of the header at `height`, given the previous ones.
+    fn add_time(&mut self, height: usize, header: &Header) {
+        let consecutive = self.prev.is_some_and(|(h, _)| h + 1 == height);
+        if !consecutive {
+            self.times.clear();
+        }
+        let time = i64::from(header.time);
+        if let Some(&prev) = self.times.back() {
+            let drift = time - i64::from(prev);
+            self.since_prev.add(drift);
+            if drift < LARGE_NEGATIVE_DRIFT {
+                self.negative_drifts += 1;
+                if self.negative_drifts <= MAX_REPORTED {
+                    tracing::warn!(
+                        "headers: {} at height {} is {}[s] before its predecessor",
+                        header.block_hash(),
+                        height,
+                        -drift
+                    );
+                }
+            }
+        }
+        // The MTP of the first blocks is over all their predecessors (and genesis has none)
+        if height > 0 && (self.times.len() == MTP_BLOCKS || self.times.len() == height) {
+            let mut sorted: Vec<u32> = self.times.iter().copied().collect();
+            sorted.sort_unstable();
+            let mtp = i64::from(sorted[sorted.len() / 2]);
+            self.since_mtp.add(time - mtp);
+            if time <= mtp {
+                self.bad_time += 1;
+                if self.bad_time <= MAX_REPORTED {
+                    tracing::warn!(
+                        "headers: {} at height {} isn't after its median-time-past",
+                        header.block_hash(),
+                        height
+                    );
+                }
+            }
+        }
+        if self.times.len() == MTP_BLOCKS {
+            self.times.pop_front();
+        }
+        self.times.push_back(header.time);
+    }
+
+    /// The `nBits` the header at `height` must have, if the preceding headers were seen.
+    fn expected_bits(&self, height: usize) -> Option<CompactTarget> {
+        let (prev_height, prev) = self.prev?;
+        if prev_height + 1 != height {
+            return None;
+        }
+        if !height.is_multiple_of(DIFFICULTY_PERIOD) {
+            // Testnet allows minimum-difficulty blocks after 20 minutes without one
+            return (!self.params.allow_min_difficulty_blocks).then_some(prev.bits);
+        }
+        let (start_height, start_time) = self.period_start?;
+        if start_height + DIFFICULTY_PERIOD != height || self.params.allow_min_difficulty_blocks {
+            return None;
+        }
+        let timespan = u64::from(prev.time.saturating_sub(start_time));
+        Some(CompactTarget::from_next_work_required(
+            prev.bits,
+            timespan,
+            self.params,
+        ))
+    }
+}
+
+impl Benchmark for Headers {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        _counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let height = scratch.height;
+        let header = Header::consensus_decode(&mut Cursor::new(responses[0]))?;
+        self.headers += 1;
+        let work = header.work();
+        self.chainwork = Some(self.chainwork.map_or(work, |total| total + work));
+
+        if header.validate_pow(header.target()).is_err() {
+            self.bad_pow += 1;
+            if self.bad_pow <= MAX_REPORTED {
+                tracing::warn!(
+                    "headers: {} at height {} doesn't meet its target {:#x}",
+                    header.block_hash(),
+                    height,
+                    header.bits.to_consensus()
+                );
+            }
+        }
+        if let Some(expected) = self.expected_bits(height) {
+            if header.bits != expected {
+                self.bad_bits += 1;
+                if self.bad_bits <= MAX_REPORTED {
+                    tracing::warn!(
+                        "headers: {} at height {} has nBits {:#x}, expected {:#x}",
+                        header.block_hash(),
+                        height,
+                        header.bits.to_consensus(),
+                        expected.to_consensus()
+                    );
+                }
+            }
+        }
+
+        self.add_time(height, &header);
+        if height.is_multiple_of(DIFFICULTY_PERIOD) {
+            self.period_start = Some((height, header.time));
+        }
+        self.prev = Some((height, header));
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let chainwork = self.chainwork.map_or(0.0, Work::log2);
+        if self.bad_pow + self.bad_bits > 0 {
+            tracing::warn!(
+                "headers: {} headers with chainwork 2^{:.3}; {} not meeting their target, {} \
+                 with unexpected nBits",
+                self.headers,
+                chainwork,
+                self.bad_pow,
+                self.bad_bits
+            );
+        } else {
+            tracing::info!(
+                "headers: {} headers with chainwork 2^{:.3}, all valid",
+                self.headers,
+                chainwork
+            );
+        }
+        tracing::info!(
+            "headers: time since the previous block {:?}, since the median-time-past {:?}",
+            self.since_prev,
+            self.since_mtp
+        );
+        if self.bad_time + self.negative_drifts > 0 {
+            tracing::warn!(
+                "headers: {} timestamps not after their median-time-past, {} over an hour before \
+                 their predecessor's",
+                self.bad_time,
+                self.negative_drifts
+            );
+        }
+        Ok(())
+    }
+}
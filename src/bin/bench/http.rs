@@ -0,0 +1,495 @@
+//! HTTP client backends, selected with `--http-client`.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+
+use crate::Result;
+
+/// A successful response, with its body not yet read.
+pub struct Response {
+    pub body: Box<dyn Read + Send>,
+    pub content_encoding: Option<String>,
+    /// To check the body against (it's the encoded body's, with a `content_encoding`)
+    pub content_length: Option<u64>,
+    pub status: u16,
+    /// Only known for transports opening a connection per request (pooled ones hide it)
+    pub connect: Option<Duration>,
+}
+
+/// A non-2xx response, kept apart from other errors so `--on-error` can count it by status.
+pub struct StatusError {
+    pub url: String,
+    pub status: u16,
+    /// Start of the response body, where bitcoind explains the error
+    pub message: String,
+}
+
+/// Of the body kept in `StatusError::message`.
+const MAX_ERROR_BODY: u64 = 256;
+
+impl StatusError {
+    pub fn new(url: &str, status: u16, body: impl Read) -> Self {
+        let mut message = String::new();
+        // Best effort: the status is what matters.
+        let _ = body.take(MAX_ERROR_BODY).read_to_string(&mut message);
+        StatusError {
+            url: url.to_owned(),
+            status,
+            message: message.trim().to_owned(),
+        }
+    }
+
+    /// Whether bitcoind refused to serve a block (or its undo data) because it was pruned.
+    pub fn is_pruned(&self) -> bool {
+        self.status == 404 && self.message.contains("pruned")
+    }
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: HTTP {}", self.url, self.status)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Like the other (string) errors returned from `main`.
+impl fmt::Debug for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// An IO error with the URL (or socket) it occurred on, keeping its kind for `--on-error`.
+pub struct IoError {
+    context: String,
+    source: io::Error,
+}
+
+impl IoError {
+    pub fn new(context: &str, source: io::Error) -> Self {
+        IoError {
+            context: context.to_owned(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.source)
+    }
+}
+
+impl fmt::Debug for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+pub trait Transport: Send + Sync {
+    /// Sends a GET request, failing on non-2xx responses (with a `StatusError`).
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HttpClient {
+    Ureq,
+    /// Requires the `reqwest` feature
+    Reqwest,
+    /// Requires the `hyper` feature; bodies are collected before being returned
+    Hyper,
+}
+
+impl HttpClient {
+    pub fn name(self) -> &'static str {
+        match self {
+            HttpClient::Ureq => "ureq",
+            HttpClient::Reqwest => "reqwest",
+            HttpClient::Hyper => "hyper",
+        }
+    }
+
+    pub fn transport(self) -> Result<Box<dyn Transport>> {
+        match self {
+            HttpClient::Ureq => Ok(Box::new(Ureq(
+                ureq::Agent::config_builder()
+                    .http_status_as_error(false)
+                    .build()
+                    .into(),
+            ))),
+            #[cfg(feature = "reqwest")]
+            HttpClient::Reqwest => Ok(Box::new(Reqwest(reqwest::blocking::Client::new()))),
+            #[cfg(feature = "hyper")]
+            HttpClient::Hyper => Ok(Box::new(hyper_client::Hyper::new()?)),
+            #[allow(unreachable_patterns)]
+            _ => Err(format!("built without the `{}` feature", self.name()).into()),
+        }
+    }
+}
+
+struct Ureq(ureq::Agent);
+
+impl Transport for Ureq {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        let mut request = self.0.get(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request.call().map_err(|e| -> Box<dyn std::error::Error> {
+            match e {
+                ureq::Error::Io(e) => Box::new(IoError::new(url, e)),
+                ureq::Error::Timeout(_) => Box::new(IoError::new(
+                    url,
+                    io::Error::new(io::ErrorKind::TimedOut, e.to_string()),
+                )),
+                e => format!("{}: {}", url, e).into(),
+            }
+        })?;
+        let status = response.status().as_u16();
+        if !response.status().is_success() {
+            let body = response.into_body().into_reader();
+            return Err(StatusError::new(url, status, body).into());
+        }
+        let content_encoding = header(response.headers().get("Content-Encoding"))?;
+        let content_length = header(response.headers().get("Content-Length"))?;
+        Ok(Response {
+            status,
+            body: Box::new(response.into_body().into_reader()),
+            content_encoding,
+            content_length: content_length.and_then(|n| n.parse().ok()),
+            connect: None,
+        })
+    }
+}
+
+#[cfg(feature = "reqwest")]
+struct Reqwest(reqwest::blocking::Client);
+
+#[cfg(feature = "reqwest")]
+impl Transport for Reqwest {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        let mut request = self.0.get(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request.send().map_err(|e| -> Box<dyn std::error::Error> {
+            // Classified like the other clients' I/O errors (for `--on-error` and the failures)
+            let kind = if e.is_timeout() {
+                io::ErrorKind::TimedOut
+            } else if e.is_connect() {
+                io::ErrorKind::ConnectionRefused
+            } else if e.is_request() {
+                io::ErrorKind::Other
+            } else {
+                return format!("{}: {}", url, e).into();
+            };
+            Box::new(IoError::new(url, io::Error::new(kind, e.to_string())))
+        })?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(StatusError::new(url, status, response).into());
+        }
+        let content_encoding = header(response.headers().get("Content-Encoding"))?;
+        Ok(Response {
+            status: response.status().as_u16(),
+            content_length: response.content_length(),
+            body: Box::new(response),
+            content_encoding,
+            connect: None,
+        })
+    }
+}
+
+#[cfg(feature = "hyper")]
+mod hyper_client {
+    use std::{
+        error::Error,
+        io::{self, Cursor},
+    };
+
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use hyper_util::{
+        client::legacy::{connect::HttpConnector, Client},
+        rt::TokioExecutor,
+    };
+
+    use super::{header, IoError, Response, StatusError, Transport};
+    use crate::Result;
+
+    /// Wraps a request error, keeping the kind of the IO error that caused it (if any).
+    fn io_error(url: &str, e: impl Error + Send + Sync + 'static) -> IoError {
+        let mut source = e.source();
+        let mut kind = io::ErrorKind::Other;
+        while let Some(s) = source {
+            if let Some(e) = s.downcast_ref::<io::Error>() {
+                kind = e.kind();
+                break;
+            }
+            source = s.source();
+        }
+        IoError::new(url, io::Error::new(kind, e))
+    }
+
+    /// Drives an async hyper client from a private single-threaded runtime.
+    pub struct Hyper {
+        runtime: tokio::runtime::Runtime,
+        client: Client<HttpConnector, Empty<Bytes>>,
+    }
+
+    impl Hyper {
+        pub fn new() -> Result<Self> {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            let client = Client::builder(TokioExecutor::new()).build_http();
+            Ok(Hyper { runtime, client })
+        }
+    }
+
+    impl Transport for Hyper {
+        fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+            let mut request = hyper::Request::get(url);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let request = request.body(Empty::new())?;
+            self.runtime.block_on(async {
+                let response = self
+                    .client
+                    .request(request)
+                    .await
+                    .map_err(|e| io_error(url, e))?;
+                let content_encoding = header(response.headers().get("Content-Encoding"))?;
+                let content_length = header(response.headers().get("Content-Length"))?;
+                let (success, status) =
+                    (response.status().is_success(), response.status().as_u16());
+                let body = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .map_err(|e| io_error(url, e))?
+                    .to_bytes();
+                if !success {
+                    return Err(StatusError::new(url, status, Cursor::new(body)).into());
+                }
+                Ok(Response {
+                    body: Box::new(Cursor::new(body)),
+                    content_encoding,
+                    content_length: content_length.and_then(|n| n.parse().ok()),
+                    status,
+                    connect: None,
+                })
+            })
+        }
+    }
+}
+
+/// Prefix of `--url`s served over a unix domain socket, e.g. `unix:///run/bitcoind/rest.sock`.
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// Minimal HTTP/1.1 over a unix domain socket, using a new connection per request
+/// (so the body can be streamed straight from the socket).
+#[cfg(unix)]
+pub struct Unix;
+
+#[cfg(unix)]
+impl Transport for Unix {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        use std::os::unix::net::UnixStream;
+
+        let rest = url
+            .strip_prefix(UNIX_SCHEME)
+            .ok_or_else(|| format!("{}: not a unix socket URL", url))?;
+        // The socket path is everything before the REST API path.
+        let split = rest
+            .find("/rest/")
+            .ok_or_else(|| format!("{}: missing /rest/ path", url))?;
+        let (socket, path) = rest.split_at(split);
+
+        let t = Instant::now();
+        let stream = UnixStream::connect(socket).map_err(|e| IoError::new(socket, e))?;
+        let connect = t.elapsed();
+        get_over(stream, url, "localhost", path, headers, connect)
+    }
+}
+
+/// Sends a GET request over a new connection, closed after the response, whose body is then
+/// streamed straight from `stream`.
+fn get_over<S: Read + Write + Send + 'static>(
+    mut stream: S,
+    url: &str,
+    host: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    connect: Duration,
+) -> Result<Response> {
+    let mut request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        path, host
+    );
+    for (name, value) in headers {
+        request += &format!("{}: {}\r\n", name, value);
+    }
+    request += "\r\n";
+    stream.write_all(request.as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line.split_whitespace().nth(1).unwrap_or_default();
+    let status: u16 = status
+        .parse()
+        .map_err(|_| format!("{}: invalid status line {:?}", url, line.trim_end()))?;
+    let mut content_length = None;
+    let mut content_encoding = None;
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(format!("{}: invalid header {:?}", url, header).into());
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = Some(value.parse::<u64>()?);
+        } else if name.eq_ignore_ascii_case("Content-Encoding") {
+            content_encoding = Some(value.to_owned());
+        } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+            return Err(format!("{}: unsupported Transfer-Encoding {:?}", url, value).into());
+        }
+    }
+    // Without Content-Length, the body ends when the server closes the connection.
+    let body = reader.take(content_length.unwrap_or(u64::MAX));
+    if !(200..300).contains(&status) {
+        return Err(StatusError::new(url, status, body).into());
+    }
+    Ok(Response {
+        body: Box::new(body),
+        content_encoding,
+        content_length,
+        status,
+        connect: Some(connect),
+    })
+}
+
+/// Prefixes of `--proxy` URLs (the proxy always resolves the host names, as Tor must).
+const SOCKS5_SCHEMES: [&str; 2] = ["socks5://", "socks5h://"];
+
+/// HTTP/1.1 through a SOCKS5 proxy (`--proxy`), e.g. Tor's for `.onion` URLs, using a new
+/// connection per request. Its connect time covers the proxy's handshake (and, with Tor, the
+/// circuit to the node).
+pub struct Socks5 {
+    /// `host:port`
+    proxy: String,
+}
+
+impl Socks5 {
+    pub fn parse(url: &str) -> Result<Self> {
+        let proxy = SOCKS5_SCHEMES
+            .iter()
+            .find_map(|scheme| url.strip_prefix(scheme))
+            .ok_or_else(|| format!("{}: expected a socks5:// proxy URL", url))?;
+        Ok(Socks5 {
+            proxy: proxy.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// Connects to `host:port` via the proxy (RFC 1928, without authentication).
+    fn connect(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let mut stream =
+            TcpStream::connect(&self.proxy).map_err(|e| IoError::new(&self.proxy, e))?;
+        stream.set_nodelay(true)?;
+        stream.write_all(&[5, 1, 0])?;
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply)?;
+        if reply != [5, 0] {
+            return Err(format!("{}: SOCKS5 proxy requires authentication", self.proxy).into());
+        }
+        let len = u8::try_from(host.len()).map_err(|_| format!("{}: host too long", host))?;
+        let mut request = vec![5, 1, 0, 3, len];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request)?;
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            let reason = match reply[1] {
+                1 => "general failure",
+                2 => "not allowed by ruleset",
+                3 => "network unreachable",
+                4 => "host unreachable",
+                5 => "connection refused",
+                6 => "TTL expired",
+                _ => "unsupported request",
+            };
+            return Err(format!(
+                "{}: SOCKS5 proxy failed to connect to {}:{} ({})",
+                self.proxy, host, port, reason
+            )
+            .into());
+        }
+        // The proxy's bound address and port, unused
+        let address = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => {
+                let mut len = [0];
+                stream.read_exact(&mut len)?;
+                len[0] as u64
+            }
+            _ => return Err(format!("{}: invalid SOCKS5 reply", self.proxy).into()),
+        };
+        io::copy(&mut (&mut stream).take(address + 2), &mut io::sink())?;
+        Ok(stream)
+    }
+}
+
+impl Transport for Socks5 {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("{}: only http:// URLs can be proxied", url))?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| format!("{}: bad port", url))?,
+            ),
+            None => (authority, 80),
+        };
+        let t = Instant::now();
+        let stream = self.connect(host, port)?;
+        let connect = t.elapsed();
+        let path = if path.is_empty() { "/" } else { path };
+        get_over(stream, url, authority, path, headers, connect)
+    }
+}
+
+/// All backends share the `http` crate's header types.
+fn header(value: Option<&ureq::http::HeaderValue>) -> Result<Option<String>> {
+    Ok(match value {
+        Some(value) => Some(value.to_str()?.to_owned()),
+        None => None,
+    })
+}
@@ -0,0 +1,191 @@
+//! `IndexWrite` benchmark: electrs-style scripthash rows, written to a temporary RocksDB.
+
+use crate::{benchmarks::Benchmark, Args, Counters, Result, Scratch};
+
+use imp::Index;
+
+pub struct IndexWrite(Index);
+
+impl IndexWrite {
+    /// Opens a new database for every run.
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(IndexWrite(Index::open()?)))
+    }
+}
+
+impl Benchmark for IndexWrite {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        self.0.write_block(scratch.height, responses[0], counters)
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        self.0.finish()
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+mod imp {
+    use std::{
+        ops::ControlFlow,
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin_slices::{bsl, Visit};
+    use rocksdb::{statistics::Ticker, Options, WriteBatch, WriteOptions, DB};
+
+    use crate::{Counters, Result};
+
+    /// Blocks per `WriteBatch`, as in electrs' bulk indexing.
+    const BATCH_BLOCKS: usize = 100;
+
+    /// Funding rows only: spending rows would need the spent outputs' scripts as well.
+    struct RowVisitor<'a> {
+        counters: &'a mut Counters,
+        batch: &'a mut WriteBatch,
+        height: u32,
+    }
+
+    impl bitcoin_slices::Visitor for RowVisitor<'_> {
+        fn visit_tx_out(&mut self, _vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+            let script = tx_out.script_pubkey();
+            self.counters.scripts += script.len() as u64;
+            // 8-byte scripthash prefix, followed by the big-endian height (like electrs' `ScriptHashRow`)
+            let mut key = [0u8; 12];
+            key[..8].copy_from_slice(&sha256::Hash::hash(script).as_byte_array()[..8]);
+            key[8..].copy_from_slice(&self.height.to_be_bytes());
+            self.batch.put(key, b"");
+            ControlFlow::Continue(())
+        }
+    }
+
+    /// Removed on drop, after the database is closed.
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            if let Err(e) = std::fs::remove_dir_all(&self.0) {
+                tracing::warn!("failed to remove {}: {}", self.0.display(), e);
+            }
+        }
+    }
+
+    pub struct Index {
+        db: DB,
+        _dir: TempDir, // dropped after `db`
+        opts: Options,
+        write_opts: WriteOptions,
+        batch: WriteBatch,
+        batch_blocks: usize,
+        blocks: u64,
+        rows: u64,
+        started: Instant,
+    }
+
+    impl Index {
+        /// Opens an empty database in a temporary directory.
+        pub fn open() -> Result<Self> {
+            let dir = std::env::temp_dir().join(format!("bench-index-{}", std::process::id()));
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            opts.enable_statistics();
+            let db = DB::open(&opts, &dir)?;
+            // electrs skips the WAL during initial sync, too
+            let mut write_opts = WriteOptions::default();
+            write_opts.disable_wal(true);
+            Ok(Index {
+                db,
+                _dir: TempDir(dir),
+                opts,
+                write_opts,
+                batch: WriteBatch::default(),
+                batch_blocks: 0,
+                blocks: 0,
+                rows: 0,
+                started: Instant::now(),
+            })
+        }
+
+        pub fn write_block(
+            &mut self,
+            height: usize,
+            data: &[u8],
+            counters: &mut Counters,
+        ) -> Result<()> {
+            let rows = self.batch.len();
+            let mut visit = RowVisitor {
+                counters,
+                batch: &mut self.batch,
+                height: height as u32,
+            };
+            bsl::Block::visit(data, &mut visit).map_err(|e| format!("invalid block: {:?}", e))?;
+            self.rows += (self.batch.len() - rows) as u64;
+            self.blocks += 1;
+            self.batch_blocks += 1;
+            if self.batch_blocks == BATCH_BLOCKS {
+                self.write_batch()?;
+            }
+            Ok(())
+        }
+
+        fn write_batch(&mut self) -> Result<()> {
+            let batch = std::mem::take(&mut self.batch);
+            self.db.write_opt(batch, &self.write_opts)?;
+            self.batch_blocks = 0;
+            Ok(())
+        }
+
+        /// Writes the last batch and flushes the memtable, then logs the end-to-end results.
+        pub fn finish(&mut self) -> Result<()> {
+            self.write_batch()?;
+            self.db.flush()?;
+            let elapsed = self.started.elapsed().max(Duration::from_nanos(1));
+            let written = self.opts.get_ticker_count(Ticker::BytesWritten);
+            let flushed = self.opts.get_ticker_count(Ticker::FlushWriteBytes);
+            let compacted = self.opts.get_ticker_count(Ticker::CompactWriteBytes);
+            tracing::info!(
+                "IndexWrite: {} blocks, {} rows in {:.3}[s], {:.1}[blocks/s], {} bytes written, {:.2}x write amplification",
+                self.blocks,
+                self.rows,
+                elapsed.as_secs_f64(),
+                self.blocks as f64 / elapsed.as_secs_f64(),
+                written,
+                (flushed + compacted) as f64 / written.max(1) as f64,
+            );
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "rocksdb"))]
+mod imp {
+    use crate::{Counters, Result};
+
+    /// Stands in for the real index, so that `IndexWrite` fails with a clear error.
+    pub struct Index(std::convert::Infallible);
+
+    impl Index {
+        pub fn open() -> Result<Self> {
+            Err("built without the `rocksdb` feature".into())
+        }
+
+        pub fn write_block(
+            &mut self,
+            _height: usize,
+            _data: &[u8],
+            _counters: &mut Counters,
+        ) -> Result<()> {
+            match self.0 {}
+        }
+
+        pub fn finish(&mut self) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
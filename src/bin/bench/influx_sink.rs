@@ -0,0 +1,94 @@
+//! `--influx`: per-chunk measurements in InfluxDB line protocol, written to a URL (e.g. InfluxDB's
+//! or VictoriaMetrics' `/write` endpoint) or to stdout (`-`).
+
+use std::{
+    io::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{BlockRecord, Result};
+
+const MEASUREMENT: &str = "bench_chunk";
+
+enum Target {
+    Stdout,
+    Url(String),
+}
+
+pub struct InfluxSink {
+    target: Target,
+    /// `,node_version=...,run=...`, shared by all lines
+    tags: String,
+}
+
+/// Escapes a tag value (commas, spaces and equal signs).
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl InfluxSink {
+    pub fn new(target: &str, node_version: Option<&str>) -> Result<Self> {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        // Distinguishes runs starting within the same second
+        let run = format!("{}-{}", started.as_secs(), std::process::id());
+        let mut tags = String::new();
+        if let Some(version) = node_version {
+            tags += &format!(",node_version={}", escape(version));
+        }
+        tags += &format!(",run={}", run);
+        let target = match target {
+            "-" => Target::Stdout,
+            url => Target::Url(url.to_owned()),
+        };
+        Ok(InfluxSink { target, tags })
+    }
+
+    pub fn write_chunk(
+        &mut self,
+        bench: &str,
+        url: &str,
+        duration: Duration,
+        records: &[BlockRecord],
+    ) -> Result<()> {
+        let Some(first) = records.first() else {
+            return Ok(());
+        };
+        let sum = |f: fn(&BlockRecord) -> u64| -> u64 { records.iter().map(f).sum() };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let line = format!(
+            "{},bench={},url={}{} height={}i,blocks={}i,duration_us={}i,received={}i,bytes={}i,\
+             fetch_us={}i,decode_us={}i,coins={}i,scripts={}i {}\n",
+            MEASUREMENT,
+            escape(bench),
+            escape(url),
+            self.tags,
+            first.height,
+            records.len(),
+            duration.as_micros(),
+            sum(|r| r.received as u64),
+            sum(|r| r.bytes as u64),
+            sum(|r| r.fetch.as_micros() as u64),
+            sum(|r| r.decode.as_micros() as u64),
+            sum(|r| r.stats.count),
+            sum(|r| r.stats.scripts),
+            timestamp
+        );
+        match &self.target {
+            Target::Stdout => std::io::stdout().lock().write_all(line.as_bytes())?,
+            Target::Url(target) => {
+                ureq::post(target)
+                    .content_type("text/plain; charset=utf-8")
+                    .send(&line)
+                    .map_err(|e| format!("{}: {}", target, e))?;
+            }
+        }
+        Ok(())
+    }
+}
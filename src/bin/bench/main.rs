@@ -0,0 +1,3495 @@
+use std::{
+    cmp::min,
+    collections::BTreeMap,
+    io::{self, BufReader, Read},
+    ops::{ControlFlow, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+use base64::prelude::*;
+use bitcoin::{
+    block::Header,
+    blockdata::opcodes::all::*,
+    consensus::encode::{Decodable, ReadExt, VarInt},
+    hashes::{sha256, sha256d, Hash, HashEngine},
+    io::{Cursor, FromStd},
+    key::PublicKey,
+    merkle_tree,
+    script::PushBytes,
+    Amount, BlockHash, Script, ScriptBuf, TxOut, Txid, WitnessMerkleNode, Wtxid,
+};
+use bitcoin_slices::{bsl, Visit};
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::Deserialize;
+use tracing::field;
+use tracing_subscriber::{
+    fmt::{format::FmtSpan, writer::BoxMakeWriter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+mod adaptive;
+mod alloc;
+mod backend;
+mod benchmarks;
+mod block;
+mod bloom;
+mod cache_check;
+mod calendar;
+mod compare;
+mod compressor;
+mod descriptors;
+mod dump;
+mod electrum;
+mod failures;
+mod fees;
+mod filter_query;
+mod filters;
+mod headers;
+mod http;
+mod index;
+mod influx_sink;
+mod mempool;
+mod otlp;
+mod paired;
+mod parquet_sink;
+mod plot;
+mod prefetch;
+mod profile;
+mod ratelimit;
+mod reducers;
+mod roundtrip;
+mod rpc;
+mod script_type;
+mod selftest;
+mod silent_payments;
+mod sqlite_sink;
+mod state;
+mod statsd_sink;
+mod status;
+mod stripped;
+mod summary;
+mod sweep;
+mod trace;
+mod tui;
+mod undo;
+mod utxo;
+mod verify;
+mod verify_scripts;
+mod wallet;
+
+use alloc::Allocs;
+use backend::{Backend, BlockUrl};
+use benchmarks::{Benchmark, Entry};
+use bloom::BloomUpdate;
+use cache_check::CacheCheck;
+use compressor::Compressor;
+use dump::{Dump, UndoCoin};
+use electrum::Electrum;
+use failures::{Failure, Failures, OnError, RequestError};
+use http::{HttpClient, Transport};
+use influx_sink::InfluxSink;
+use mempool::MempoolPolls;
+use otlp::Otlp;
+use paired::Pairs;
+use parquet_sink::ParquetSink;
+use prefetch::Prefetcher;
+use profile::{PerfControl, Responses};
+use ratelimit::RateLimiter;
+use reducers::{BlockFields, BlockView, Buckets, Histogram, Reducers, StatsKind};
+use rpc::Rpc;
+use script_type::{ScriptFilter, ScriptType};
+use sqlite_sink::SqliteSink;
+use state::StateDb;
+use statsd_sink::StatsdSink;
+use status::{Progress, StatusTrigger};
+use summary::RunSummary;
+use sweep::Sweep;
+use trace::RequestLog;
+use tui::Dashboard;
+
+fn varint_decode<D: bitcoin::io::Read>(
+    d: &mut D,
+) -> std::result::Result<usize, bitcoin::consensus::encode::Error> {
+    let mut n = 0usize;
+    // TODO: add checks
+    loop {
+        let b = u8::consensus_decode(d)?;
+        n = (n << 7) | (b & 0x7F) as usize;
+        if b & 0x80 != 0 {
+            n += 1;
+        } else {
+            return Ok(n);
+        }
+    }
+}
+
+fn decode_bytes<'a, D: bitcoin::io::Read>(
+    d: &mut D,
+    len: usize,
+    buf: &'a mut Vec<u8>,
+) -> std::result::Result<&'a [u8], bitcoin::consensus::encode::Error> {
+    buf.resize(len, 0);
+    d.read_slice(buf)?;
+    Ok(buf)
+}
+
+/// Buffers reused across blocks, so that decoding doesn't allocate per script.
+#[derive(Default)]
+struct Scratch {
+    bytes: Vec<u8>,
+    script: ScriptBuf,
+    /// Also hash every script (`--scripthash`)
+    scripthash: bool,
+    /// Size uncompressed P2PK scripts without decompressing their keys
+    /// (`--skip-pubkey-decompression`)
+    skip_pubkeys: bool,
+    /// Size undo data's scripts without decompressing them, unless hashed (`--lazy-scripts`)
+    lazy_scripts: bool,
+    /// Spent coins decoded from undo data (`--dump-undo`)
+    dump_undo: Option<Dump>,
+    /// Decoded scriptPubKeys (`--dump-scripts`)
+    dump_scripts: Option<Dump>,
+    /// Only the selected scripts are counted and dumped (`--filter-script-type`)
+    script_filter: ScriptFilter,
+    /// Also compute every txid (`--txid`)
+    txid: bool,
+    /// Also check each block's merkle root and witness commitment (`--verify-merkle`)
+    verify_merkle: bool,
+    /// Of the block being verified
+    txids: Vec<Txid>,
+    wtxids: Vec<Wtxid>,
+    /// Also sum coin-days destroyed (`--coin-days`)
+    coin_days: bool,
+    /// Height of the block being decoded, for spent-coin ages
+    height: usize,
+    /// Spent value and input count of each non-coinbase transaction (`FeeRates`)
+    tx_inputs: Vec<(u64, usize)>,
+    /// Also collect the block's fields some `--stats` read (`reducers::BlockFields`)
+    fields: bool,
+}
+
+impl Scratch {
+    fn new(args: &Args) -> Self {
+        Scratch {
+            scripthash: args.scripthash,
+            skip_pubkeys: args.skip_pubkey_decompression,
+            lazy_scripts: args.lazy_scripts,
+            script_filter: ScriptFilter(args.filter_script_type.clone()),
+            txid: args.txid,
+            verify_merkle: args.verify_merkle,
+            coin_days: args.coin_days,
+            fields: reducers::reads_fields(&args.stats),
+            ..Default::default()
+        }
+    }
+
+    /// Whether decoders need the actual scriptPubKeys, rather than their sizes.
+    fn needs_scripts(&self) -> bool {
+        self.scripthash
+            || self.dump_undo.is_some()
+            || self.dump_scripts.is_some()
+            || self.script_filter.is_active()
+    }
+}
+
+/// Electrum-style scripthash (SHA-256 of the scriptPubKey), kept opaque to the optimizer.
+fn hash_script(script: &[u8], counters: &mut Counters) {
+    std::hint::black_box(sha256::Hash::hash(script));
+    counters.hashes += 1;
+}
+
+/// Empties `script`, keeping its allocation.
+fn clear_script(script: &mut ScriptBuf) {
+    let mut bytes = std::mem::take(script).into_bytes();
+    bytes.clear();
+    *script = ScriptBuf::from_bytes(bytes);
+}
+
+const SPECIAL_SCRIPTS: usize = 6;
+
+fn decompress_script(script_type: u8, bytes: &[u8], script: &mut ScriptBuf) -> Result<()> {
+    clear_script(script);
+    let mut key = [0u8; 33];
+    match script_type {
+        0 => {
+            script.push_opcode(OP_DUP);
+            script.push_opcode(OP_HASH160);
+            script.push_slice(<&PushBytes>::try_from(bytes)?);
+            script.push_opcode(OP_EQUALVERIFY);
+            script.push_opcode(OP_CHECKSIG);
+        }
+        1 => {
+            script.push_opcode(OP_HASH160);
+            script.push_slice(<&PushBytes>::try_from(bytes)?);
+            script.push_opcode(OP_EQUAL);
+        }
+        2..=5 if bytes.len() != 32 => {
+            return Err(format!("invalid public key size {}", bytes.len()).into());
+        }
+        2 | 3 => {
+            key[0] = script_type;
+            key[1..].copy_from_slice(bytes);
+            script.push_slice(key);
+            script.push_opcode(OP_CHECKSIG);
+        }
+        4 | 5 => {
+            key[0] = script_type - 2;
+            key[1..].copy_from_slice(bytes);
+            let pubkey = PublicKey::from_slice(&key)
+                .map_err(|e| format!("invalid public key of script type {}: {}", script_type, e))?;
+            script.push_slice(pubkey.inner.serialize_uncompressed());
+            script.push_opcode(OP_CHECKSIG);
+        }
+        _ => return Err(format!("invalid special script type {}", script_type).into()),
+    }
+    if !(script.is_p2pk() || script.is_p2pkh() || script.is_p2sh()) {
+        return Err(format!("invalid decompressed script {}", script.to_hex_string()).into());
+    }
+    Ok(())
+}
+
+/// Like `decompress_script`, also timing the decompression of public keys (types 4 and 5), or
+/// skipping it: `skip` leaves a placeholder key of the same size, with only its x coordinate.
+fn decompress_timed(
+    script_type: u8,
+    bytes: &[u8],
+    script: &mut ScriptBuf,
+    skip: bool,
+    counters: &mut Counters,
+) -> Result<()> {
+    if !matches!(script_type, 4 | 5) {
+        return decompress_script(script_type, bytes, script);
+    }
+    if skip {
+        clear_script(script);
+        let mut key = [0u8; 65];
+        key[0] = 0x04;
+        key[1..33].copy_from_slice(bytes);
+        script.push_slice(key);
+        script.push_opcode(OP_CHECKSIG);
+        return Ok(());
+    }
+    let t = Instant::now();
+    decompress_script(script_type, bytes, script)?;
+    counters.pubkey_time += t.elapsed();
+    counters.pubkeys += 1;
+    Ok(())
+}
+
+/// Powers of ten, by the exponent of a compressed amount.
+const POW10: [u64; 10] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+];
+
+fn decompress_amount(mut x: u64) -> u64 {
+    // x = 0  OR  x = 1+10*(9*n + d - 1) + e  OR  x = 1+10*(n - 1) + 9
+    if x == 0 {
+        return 0;
+    }
+    x -= 1;
+    // x = 10*(9*n + d - 1) + e
+    let e = (x % 10) as usize;
+    x /= 10;
+
+    let n = if e < 9 {
+        // x = 9*n + d - 1
+        let d = (x % 9) + 1;
+        x /= 9;
+        // x = n
+        x * 10 + d
+    } else {
+        x + 1
+    };
+    // Wrapping as Bitcoin Core's `uint64_t` does, on amounts no valid coin has
+    n.wrapping_mul(POW10[e])
+}
+
+/// Counters filled by the decoders for one block, which the `--stats` reducers add up.
+#[derive(Debug, Default)]
+struct Counters {
+    count: u64,
+    count_by_type: [u64; 7],
+    spent: u128,  // total satoshis spent
+    scripts: u64, // total decompressed script size
+    hashes: u64,  // scripthashes computed with `--scripthash`
+    txids: u64,   // computed with `--txid` or `--verify-merkle`
+    txid_time: Duration,
+    pubkeys: u64, // uncompressed P2PK keys decompressed (script types 4 and 5)
+    pubkey_time: Duration,
+    merkle_roots: u64, // checked with `--verify-merkle`
+    bad_merkle_roots: u64,
+    commitments: u64, // witness commitments checked with `--verify-merkle`
+    bad_commitments: u64,
+    verify_time: Duration, // computing the wtxids and rebuilding the trees (txids excluded)
+    mismatches: u64,       // coins that differ between undo data and spent outputs (`Verify`)
+    trailing: u64,         // responses not fully consumed by their decoder
+    short_reads: u64,      // response bodies shorter than their Content-Length
+    coin_days: CoinDays,   // with `--coin-days`
+    fee_rates: FeeRates,   // per-transaction fee rates (`FeeRates`)
+    fields: Option<BlockFields>, // collected by the `block` decoder for some `--stats`
+}
+
+impl Counters {
+    /// Adds the coin-days destroyed by spending a coin created at `coin_height`, with
+    /// `--coin-days`.
+    fn add_spent_coin(&mut self, scratch: &Scratch, coin_height: u32, amount: u64) {
+        if scratch.coin_days {
+            let age = scratch.height.saturating_sub(coin_height as usize);
+            self.coin_days.0 += amount as u128 * age as u128;
+        }
+    }
+}
+
+/// Coin-days destroyed (value × age), summed in satoshi-blocks to stay exact.
+#[derive(Default)]
+struct CoinDays(u128);
+
+/// One coin held for one day.
+const SAT_BLOCKS_PER_COIN_DAY: u128 = 100_000_000 * 144;
+
+impl std::fmt::Debug for CoinDays {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.1}", self.0 as f64 / SAT_BLOCKS_PER_COIN_DAY as f64)
+    }
+}
+
+/// Fee rates in sat/vB.
+struct FeeRateBuckets;
+
+impl Buckets<8> for FeeRateBuckets {
+    const BUCKETS: [(u64, &'static str); 8] = [
+        (1, "<1"),
+        (2, "<2"),
+        (5, "<5"),
+        (10, "<10"),
+        (20, "<20"),
+        (50, "<50"),
+        (100, "<100"),
+        (u64::MAX, ">=100"),
+    ];
+}
+
+/// Histogram of transaction fee rates, in sat/vB.
+type FeeRates = Histogram<FeeRateBuckets, 8>;
+
+impl FeeRates {
+    /// Compares `fee` against `bound * vsize`, to avoid rounding the rate.
+    fn add_fee(&mut self, fee: u64, vsize: u64) {
+        self.add_below(|bound| (fee as u128) < bound as u128 * vsize as u128);
+    }
+}
+
+fn script_decode<D: bitcoin::io::Read>(
+    d: &mut D,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<usize> {
+    let len = varint_decode(d)?;
+    if len < SPECIAL_SCRIPTS {
+        let script_type = len as u8;
+        let size = match script_type {
+            0 | 1 => 20,
+            2..=5 => 32,
+            _ => unreachable!(),
+        };
+        let compressed = decode_bytes(d, size, &mut scratch.bytes)?;
+        let skip = scratch.skip_pubkeys;
+        decompress_timed(script_type, compressed, &mut scratch.script, skip, counters)?;
+        Ok(len)
+    } else {
+        let len = len - SPECIAL_SCRIPTS;
+        let mut bytes = std::mem::take(&mut scratch.script).into_bytes();
+        decode_bytes(d, len, &mut bytes)?;
+        scratch.script = ScriptBuf::from_bytes(bytes);
+        Ok(SPECIAL_SCRIPTS)
+    }
+}
+
+/// Like `script_decode`, but only returns the decompressed script's length.
+fn script_len_decode<D: bitcoin::io::Read>(
+    d: &mut D,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<usize> {
+    let len = varint_decode(d)?;
+    counters.count += 1;
+    let (kind, size) = match len {
+        0 | 1 => (len, 20),
+        2..=5 => (len, 32),
+        _ => (SPECIAL_SCRIPTS, len - SPECIAL_SCRIPTS),
+    };
+    counters.count_by_type[kind] += 1;
+    let bytes = decode_bytes(d, size, &mut scratch.bytes)?;
+    Ok(undo::CompressedScript { kind, bytes }.script_len())
+}
+
+fn blockundo_decode<D: bitcoin::io::Read>(
+    d: &mut D,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<()> {
+    let tx_count = VarInt::consensus_decode(d)?.0 as usize;
+    for tx in 0..tx_count {
+        let txin_count = VarInt::consensus_decode(d)?.0 as usize;
+        for vin in 0..txin_count {
+            let height_coinbase = varint_decode(d)?;
+            if height_coinbase >> 1 > 0 {
+                let version = varint_decode(d)?; // unused today
+                if version != 0 {
+                    return Err(format!(
+                        "tx {} input {}: unexpected coin version {}",
+                        tx, vin, version
+                    )
+                    .into());
+                }
+            }
+            let coin_height = (height_coinbase >> 1) as u32;
+            let coinbase = height_coinbase & 1 == 1;
+            let amount = decompress_amount(varint_decode(d)? as u64);
+            if scratch.lazy_scripts && !scratch.needs_scripts() {
+                counters.scripts += script_len_decode(d, counters, scratch)? as u64;
+                counters.spent += amount as u128;
+                counters.add_spent_coin(scratch, coin_height, amount);
+                continue;
+            }
+            let kind = script_decode(d, counters, scratch)?;
+            if !scratch.script_filter.selects(&scratch.script) {
+                continue;
+            }
+            counters.count += 1;
+            counters.count_by_type[kind] += 1;
+            counters.spent += amount as u128;
+            counters.add_spent_coin(scratch, coin_height, amount);
+            counters.scripts += scratch.script.len() as u64;
+            if scratch.scripthash {
+                hash_script(scratch.script.as_bytes(), counters);
+            }
+            if let Some(dump) = &mut scratch.dump_scripts {
+                dump.write_script(scratch.height, "blockundo", &scratch.script)?;
+            }
+            if let Some(dump) = &mut scratch.dump_undo {
+                let coin = UndoCoin {
+                    height: scratch.height,
+                    tx: tx + 1,
+                    vin,
+                    coin_height,
+                    coinbase,
+                    value: amount,
+                };
+                dump.write_coin(&coin, &scratch.script)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn blockundo_slices_decode(
+    data: &[u8],
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<()> {
+    let mut result = Ok(());
+    // Of the spending transaction (the coinbase excluded), and the input
+    let mut position = (usize::MAX, 0);
+    let rest = undo::visit_txs(data, |tx, coin| {
+        position = if position.0 == tx {
+            (tx, position.1 + 1)
+        } else {
+            (tx, 0)
+        };
+        if result.is_err() {
+            return;
+        }
+        if scratch.needs_scripts() {
+            match slices_coin_script(&coin, position, counters, scratch) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    result = Err(e);
+                    return;
+                }
+            }
+        }
+        counters.count += 1;
+        counters.count_by_type[coin.script.kind] += 1;
+        counters.spent += coin.amount as u128;
+        counters.scripts += coin.script.script_len() as u64;
+        counters.add_spent_coin(scratch, coin.height, coin.amount);
+    })?;
+    check_consumed(rest, counters, scratch.height, "undo data");
+    result
+}
+
+/// Filters (`--filter-script-type`), hashes (`--scripthash`) and dumps (`--dump-undo`,
+/// `--dump-scripts`) the coin's script, which all need the actual scriptPubKey. Returns whether
+/// the script is selected.
+fn slices_coin_script(
+    coin: &undo::Coin,
+    (tx, vin): (usize, usize),
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<bool> {
+    let script = if coin.script.kind < SPECIAL_SCRIPTS {
+        let kind = coin.script.kind as u8;
+        decompress_timed(
+            kind,
+            coin.script.bytes,
+            &mut scratch.script,
+            false,
+            counters,
+        )?;
+        scratch.script.as_script()
+    } else {
+        Script::from_bytes(coin.script.bytes)
+    };
+    if !scratch.script_filter.selects(script) {
+        return Ok(false);
+    }
+    if scratch.scripthash {
+        hash_script(script.as_bytes(), counters);
+    }
+    if let Some(dump) = &mut scratch.dump_scripts {
+        dump.write_script(scratch.height, "blockundo", script)?;
+    }
+    if let Some(dump) = &mut scratch.dump_undo {
+        let undo_coin = UndoCoin {
+            height: scratch.height,
+            tx: tx + 1,
+            vin,
+            coin_height: coin.height,
+            coinbase: coin.coinbase,
+            value: coin.amount,
+        };
+        dump.write_coin(&undo_coin, script)?;
+    }
+    Ok(true)
+}
+
+struct BlockVisitor<'a> {
+    counters: &'a mut Counters,
+    scripthash: bool,
+    txid: bool,
+    /// Collected with `--verify-merkle`
+    txids: Option<&'a mut Vec<Txid>>,
+    wtxids: Option<&'a mut Vec<Wtxid>>,
+    merkle_root: [u8; 32],
+    /// Of the transaction being visited
+    tx_index: usize,
+    /// The coinbase's witness commitment and reserved value (its first witness element)
+    commitment: Option<[u8; 32]>,
+    reserved: Option<[u8; 32]>,
+    /// Of the outputs to count (`--filter-script-type`)
+    script_filter: &'a ScriptFilter,
+    /// With `--dump-scripts`, and the first error writing it
+    dump_scripts: Option<&'a mut Dump>,
+    dumped: Result<()>,
+    height: usize,
+}
+
+impl bitcoin_slices::Visitor for BlockVisitor<'_> {
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<()> {
+        self.merkle_root.copy_from_slice(header.merkle_root());
+        if let Some(fields) = &mut self.counters.fields {
+            let _ = fields.visit_block_header(header);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn visit_tx_ins(&mut self, total_inputs: usize) {
+        if let Some(fields) = &mut self.counters.fields {
+            fields.visit_tx_ins(total_inputs);
+        }
+    }
+
+    fn visit_tx_in(&mut self, vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<()> {
+        if let Some(fields) = &mut self.counters.fields {
+            let _ = fields.visit_tx_in(vin, tx_in);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn visit_witness(&mut self, vin: usize) -> ControlFlow<()> {
+        if let Some(fields) = &mut self.counters.fields {
+            let _ = fields.visit_witness(vin);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn visit_witness_total_element(&mut self, witness_total: usize) {
+        if let Some(fields) = &mut self.counters.fields {
+            fields.visit_witness_total_element(witness_total);
+        }
+    }
+
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<()> {
+        if let Some(fields) = &mut self.counters.fields {
+            let _ = fields.visit_transaction(tx);
+        }
+        if self.txid || self.txids.is_some() {
+            let t = Instant::now();
+            // Double SHA-256 of the serialization without witnesses
+            let (version, inputs_outputs, locktime) = tx.txid_preimage();
+            let mut engine = sha256d::Hash::engine();
+            engine.input(version);
+            engine.input(inputs_outputs);
+            engine.input(locktime);
+            let txid = std::hint::black_box(Txid::from_engine(engine));
+            self.counters.txid_time += t.elapsed();
+            self.counters.txids += 1;
+            if let Some(txids) = &mut self.txids {
+                txids.push(txid);
+            }
+        }
+        if let Some(wtxids) = &mut self.wtxids {
+            let t = Instant::now();
+            // The coinbase's is all zeros, since its witness commits to the others
+            let wtxid = if self.tx_index == 0 {
+                Wtxid::all_zeros()
+            } else {
+                Wtxid::hash(tx.as_ref())
+            };
+            wtxids.push(std::hint::black_box(wtxid));
+            self.counters.verify_time += t.elapsed();
+        }
+        self.tx_index += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn visit_tx_out(&mut self, _vout: usize, tx_out: &bsl::TxOut) -> ControlFlow<()> {
+        let script = tx_out.script_pubkey();
+        if self.tx_index == 0 && self.wtxids.is_some() {
+            // The last matching output is the commitment
+            self.commitment = witness_commitment(script).or(self.commitment);
+        }
+        if !self.script_filter.selects(Script::from_bytes(script)) {
+            return ControlFlow::Continue(());
+        }
+        self.counters.scripts += script.len() as u64;
+        if self.scripthash {
+            hash_script(script, self.counters);
+        }
+        if let Some(dump) = &mut self.dump_scripts {
+            if self.dumped.is_ok() {
+                let script = Script::from_bytes(script);
+                self.dumped = dump.write_script(self.height, "block", script);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn visit_witness_element(&mut self, witness_i: usize, witness_element: &[u8]) {
+        if self.tx_index == 0 && witness_i == 0 {
+            self.reserved = witness_element.try_into().ok();
+        }
+        if let Some(fields) = &mut self.counters.fields {
+            fields.visit_witness_element(witness_i, witness_element);
+        }
+    }
+}
+
+fn block_decode(data: &[u8], counters: &mut Counters, scratch: &mut Scratch) -> Result<()> {
+    scratch.txids.clear();
+    scratch.wtxids.clear();
+    counters.fields = scratch.fields.then(BlockFields::default);
+    let mut visit = BlockVisitor {
+        counters,
+        scripthash: scratch.scripthash,
+        txid: scratch.txid,
+        txids: scratch.verify_merkle.then_some(&mut scratch.txids),
+        wtxids: scratch.verify_merkle.then_some(&mut scratch.wtxids),
+        merkle_root: [0; 32],
+        tx_index: 0,
+        commitment: None,
+        reserved: None,
+        script_filter: &scratch.script_filter,
+        dump_scripts: scratch.dump_scripts.as_mut(),
+        dumped: Ok(()),
+        height: scratch.height,
+    };
+    let rest = bsl::Block::visit(data, &mut visit)
+        .map_err(|e| format!("block {}: invalid block: {:?}", scratch.height, e))?
+        .remaining()
+        .len();
+    visit.dumped?;
+    let (expected, commitment, reserved) = (visit.merkle_root, visit.commitment, visit.reserved);
+    check_consumed(rest, counters, scratch.height, "block");
+    if scratch.verify_merkle {
+        let t = Instant::now();
+        let root = merkle_tree::calculate_root_inline(&mut scratch.txids);
+        check_merkle_root(
+            root.map(|r| r.to_byte_array()) == Some(expected),
+            counters,
+            scratch.height,
+        );
+        // Blocks without a commitment can't have witnesses, or the visit would have failed
+        if let Some(commitment) = commitment {
+            let matches = reserved.is_some_and(|reserved| {
+                let root = merkle_tree::calculate_root_inline(&mut scratch.wtxids)
+                    .expect("the coinbase's wtxid");
+                let root = WitnessMerkleNode::from_byte_array(root.to_byte_array());
+                bitcoin::Block::compute_witness_commitment(&root, &reserved).to_byte_array()
+                    == commitment
+            });
+            check_witness_commitment(matches, counters, scratch.height);
+        }
+        counters.verify_time += t.elapsed();
+    }
+    Ok(())
+}
+
+/// `OP_RETURN` pushing 36 bytes, starting with the witness commitment's header (BIP 141).
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// The witness commitment in a coinbase output's script, if any.
+fn witness_commitment(script: &[u8]) -> Option<[u8; 32]> {
+    if script.len() < 38 || !script.starts_with(&WITNESS_COMMITMENT_PREFIX) {
+        return None;
+    }
+    script[6..38].try_into().ok()
+}
+
+/// Counts a merkle root check of the block at `height`, reporting a mismatch.
+fn check_merkle_root(matches: bool, counters: &mut Counters, height: usize) {
+    counters.merkle_roots += 1;
+    if !matches {
+        counters.bad_merkle_roots += 1;
+        tracing::warn!("block {}: merkle root mismatch", height);
+    }
+}
+
+/// Counts a witness commitment check of the block at `height`, reporting a mismatch.
+fn check_witness_commitment(matches: bool, counters: &mut Counters, height: usize) {
+    counters.commitments += 1;
+    if !matches {
+        counters.bad_commitments += 1;
+        tracing::warn!("block {}: witness commitment mismatch", height);
+    }
+}
+
+fn spenttxouts_decode<D: bitcoin::io::Read>(
+    d: &mut D,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<()> {
+    let tx_count = VarInt::consensus_decode(d)?.0;
+    for _ in 0..tx_count {
+        let txin_count = VarInt::consensus_decode(d)?.0;
+        for _ in 0..txin_count {
+            // Same as `TxOut::consensus_decode`, but into a reused script buffer
+            let value = Amount::consensus_decode(d)?;
+            let len = VarInt::consensus_decode(d)?.0 as usize;
+            let script = decode_bytes(d, len, &mut scratch.bytes)?;
+            if !scratch.script_filter.selects(Script::from_bytes(script)) {
+                continue;
+            }
+            counters.count += 1;
+            counters.spent += value.to_sat() as u128;
+            counters.scripts += script.len() as u64;
+            if scratch.scripthash {
+                hash_script(script, counters);
+            }
+            if let Some(dump) = &mut scratch.dump_scripts {
+                dump.write_script(scratch.height, "spenttxouts", Script::from_bytes(script))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the outputs spent by each non-coinbase transaction, reusing `spent`'s vectors.
+fn spenttxouts_parse(data: &[u8], spent: &mut Vec<Vec<TxOut>>) -> Result<()> {
+    let mut c = Cursor::new(data);
+    let tx_count = VarInt::consensus_decode(&mut c)?.0 as usize;
+    spent.resize_with(tx_count, Vec::new);
+    for outputs in spent {
+        let txin_count = VarInt::consensus_decode(&mut c)?.0;
+        outputs.clear();
+        for _ in 0..txin_count {
+            outputs.push(TxOut::consensus_decode(&mut c)?);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes directly from a (network) reader, without buffering the whole response.
+fn decode_stream(
+    bench: &mut dyn Benchmark,
+    reader: &mut dyn std::io::Read,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<()> {
+    let mut d = FromStd::new(BufReader::with_capacity(STREAM_BUFFER_SIZE, reader));
+    bench.decode_stream(&mut d, counters, scratch)?;
+    // Consume the rest of the response, so the connection can be reused
+    let rest = std::io::copy(d.inner_mut(), &mut std::io::sink())?;
+    check_consumed(rest as usize, counters, scratch.height, "response");
+    Ok(())
+}
+
+/// Counts a response whose decoder left `rest` bytes of it, which a valid one wouldn't have.
+fn check_consumed(rest: usize, counters: &mut Counters, height: usize, endpoint: &str) {
+    if rest > 0 {
+        counters.trailing += 1;
+        tracing::warn!(
+            "block {}: {} trailing bytes after decoding the {}",
+            height,
+            rest,
+            endpoint
+        );
+    }
+}
+
+/// Counts a response whose body is shorter than its Content-Length said (as a server or proxy
+/// closing the connection early would send).
+fn check_length(fetch: &Fetch, counters: &mut Counters, url: &str) {
+    let Some(expected) = fetch.content_length else {
+        return;
+    };
+    if fetch.received as u64 != expected {
+        counters.short_reads += 1;
+        tracing::warn!(
+            "{}: received {} bytes, but Content-Length is {}",
+            url,
+            fetch.received,
+            expected
+        );
+    }
+}
+
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> Self {
+        CountingReader { inner, count: 0 }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Response compression requested via `Accept-Encoding`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn from_header(value: &str) -> Result<Option<Encoding>> {
+        match value.trim() {
+            "" | "identity" => Ok(None),
+            "gzip" | "x-gzip" => Ok(Some(Encoding::Gzip)),
+            "deflate" => Ok(Some(Encoding::Deflate)),
+            other => Err(format!("unsupported Content-Encoding {:?}", other).into()),
+        }
+    }
+
+    fn decoder<'r>(self, reader: impl std::io::Read + 'r) -> Box<dyn std::io::Read + 'r> {
+        match self {
+            Encoding::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Encoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(reader)),
+        }
+    }
+}
+
+/// Reads a response body into `data`, decompressing it via `raw` if needed.
+/// Returns the number of bytes received and the time spent decompressing.
+fn read_body(
+    body: Body,
+    encoding: Option<Encoding>,
+    raw: &mut Vec<u8>,
+    data: &mut Vec<u8>,
+) -> Result<(usize, Duration)> {
+    data.clear();
+    let Some(encoding) = encoding else {
+        body.take(u64::MAX).read_to_end(data)?;
+        return Ok((data.len(), Duration::ZERO));
+    };
+    raw.clear();
+    body.take(u64::MAX).read_to_end(raw)?;
+    let t = Instant::now();
+    encoding.decoder(&raw[..]).read_to_end(data)?;
+    Ok((raw.len(), t.elapsed()))
+}
+
+type Body = Box<dyn std::io::Read + Send>;
+
+/// A benchmarked response, with its body not yet read.
+struct Encoded {
+    body: Body,
+    encoding: Option<Encoding>,
+    status: u16,
+    connect: Option<Duration>,
+    content_length: Option<u64>,
+}
+
+/// Timings of a single benchmarked request.
+#[derive(Clone, Copy, Debug, Default)]
+struct Fetch {
+    /// Bytes on the wire
+    received: usize,
+    /// Expected on the wire, if the server said
+    content_length: Option<u64>,
+    status: u16,
+    /// See `http::Response::connect`
+    connect: Option<Duration>,
+    /// Until the response headers were received
+    ttfb: Duration,
+    /// Reading the body after its headers (`None` when streaming, as it overlaps decoding)
+    transfer: Option<Duration>,
+    inflate: Duration,
+}
+
+impl Fetch {
+    /// Until the whole body was received, excluding decompression.
+    fn elapsed(&self) -> Duration {
+        self.ttfb + self.transfer.unwrap_or_default()
+    }
+}
+
+/// Requests `url`, reading (and decompressing) its body into `data`.
+fn fetch_into(client: &Client, url: &str, raw: &mut Vec<u8>, data: &mut Vec<u8>) -> Result<Fetch> {
+    client.with_retries(url, || {
+        let t = Instant::now();
+        let response = client.get_encoded(url)?;
+        let ttfb = t.elapsed();
+        let (received, inflate) = read_body(response.body, response.encoding, raw, data)?;
+        Ok(Fetch {
+            received,
+            content_length: response.content_length,
+            status: response.status,
+            connect: response.connect,
+            ttfb,
+            transfer: Some(t.elapsed() - ttfb - inflate),
+            inflate,
+        })
+    })
+}
+
+/// Thin wrapper around an HTTP backend, applying per-run request settings.
+struct Client {
+    transport: Box<dyn Transport>,
+    /// For `--backend electrum` and `rpc` URLs
+    electrum: Electrum,
+    rpc: Rpc,
+    kind: HttpClient,
+    /// Instead of `transport`, for http:// URLs (`--proxy`)
+    proxy: Option<http::Socks5>,
+    auth: Option<String>,
+    /// `--header`s, and `--user-agent`
+    headers: Vec<(String, String)>,
+    accept_encoding: Option<Encoding>,
+    limiter: Option<Arc<RateLimiter>>,
+    on_error: OnError,
+    retries: u32,
+    failures: Failures,
+}
+
+/// Before the first `--on-error retry` attempt, doubling for every further one.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+impl Client {
+    fn new(args: &Args, kind: HttpClient, limiter: Option<Arc<RateLimiter>>) -> Result<Self> {
+        let auth = args
+            .auth
+            .as_ref()
+            .map(|creds| format!("Basic {}", BASE64_STANDARD.encode(creds)));
+        Ok(Client {
+            transport: kind.transport()?,
+            electrum: Electrum::default(),
+            rpc: Rpc::default(),
+            kind,
+            proxy: args.proxy.as_deref().map(http::Socks5::parse).transpose()?,
+            auth,
+            headers: args
+                .headers
+                .iter()
+                .cloned()
+                .chain(
+                    args.user_agent
+                        .iter()
+                        .map(|ua| ("User-Agent".to_owned(), ua.clone())),
+                )
+                .collect(),
+            accept_encoding: args.accept_encoding,
+            limiter,
+            on_error: args.on_error,
+            retries: args.retries,
+            failures: Failures::default(),
+        })
+    }
+
+    /// Waits until `--max-rps` allows another request.
+    fn throttle(&self) {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire();
+        }
+    }
+
+    fn get(&self, url: &str) -> Result<Body> {
+        self.throttle();
+        Ok(self.request(url, None)?.body)
+    }
+
+    fn get_string(&self, url: &str) -> Result<String> {
+        let mut result = String::new();
+        self.get(url)?.read_to_string(&mut result)?;
+        Ok(result)
+    }
+
+    /// Requests the benchmarked payload, allowing it to be compressed with `--accept-encoding`.
+    /// Callers `throttle` first, so that waiting for `--max-rps` isn't timed as fetch latency.
+    fn get_encoded(&self, url: &str) -> Result<Encoded> {
+        let response = self.request(url, self.accept_encoding)?;
+        let encoding = match response.content_encoding {
+            Some(value) => Encoding::from_header(&value)?,
+            None => None,
+        };
+        Ok(Encoded {
+            body: response.body,
+            encoding,
+            status: response.status,
+            connect: response.connect,
+            content_length: response.content_length,
+        })
+    }
+
+    /// Calls `request` (again, with `--on-error retry`), counting its HTTP and IO failures.
+    /// These are returned as `RequestError`s, so that `--on-error skip` can tell them apart.
+    fn with_retries<T>(&self, url: &str, mut request: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            let error = match request() {
+                Ok(result) => return Ok(result),
+                Err(e) => e,
+            };
+            let Some(failure) = Failure::of(error.as_ref()) else {
+                return Err(error);
+            };
+            self.failures.add(failure);
+            if self.on_error != OnError::Retry || attempt == self.retries {
+                return Err(Box::new(RequestError::new(failure, error.as_ref())));
+            }
+            let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+            attempt += 1;
+            tracing::warn!(
+                "retrying {} ({}/{}) in {:?}: {}",
+                url,
+                attempt,
+                self.retries,
+                backoff,
+                failure
+            );
+            std::thread::sleep(backoff);
+            self.throttle();
+        }
+    }
+
+    fn request(&self, url: &str, accept_encoding: Option<Encoding>) -> Result<http::Response> {
+        let mut headers = Vec::with_capacity(2 + self.headers.len());
+        headers.extend(self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str())));
+        if let Some(auth) = &self.auth {
+            headers.push(("Authorization", auth.as_str()));
+        }
+        if let Some(encoding) = accept_encoding {
+            headers.push(("Accept-Encoding", encoding.name()));
+        }
+        #[cfg(unix)]
+        if url.starts_with(http::UNIX_SCHEME) {
+            return http::Unix.get(url, &headers);
+        }
+        if url.starts_with(electrum::SCHEME) {
+            return self.electrum.get(url, &headers);
+        }
+        if url.starts_with(rpc::SCHEME) {
+            return self.rpc.get(url, &headers);
+        }
+        if let Some(proxy) = &self.proxy {
+            return proxy.get(url, &headers);
+        }
+        self.transport.get(url, &headers)
+    }
+}
+
+/// Resolves `count` hashes from `start`, requesting `batch` headers at a time.
+fn fetch_blockhashes(
+    backend: Backend,
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+    batch: usize,
+) -> Result<Vec<BlockHash>> {
+    match backend {
+        Backend::Rest => (),
+        Backend::Rpc => return backend::rpc_blockhashes(client, base_url, start, count, batch),
+        Backend::Esplora => return backend::esplora_blockhashes(client, base_url, start, count),
+        Backend::Electrum => return backend::electrum_blockhashes(client, base_url, start, count),
+    }
+    let mut result = Vec::with_capacity(count);
+    let mut height = start;
+    let limit = start + count;
+    while height < limit {
+        let hash = backend.blockhash(client, base_url, height)?;
+        let url = format!(
+            "{}/rest/headers/{}/{}.bin",
+            base_url,
+            min(batch, limit - height),
+            hash
+        );
+        let mut data = Vec::new();
+        client.get(&url)?.read_to_end(&mut data)?;
+        let count = data.len() / Header::SIZE;
+        let mut c = Cursor::new(data);
+        for _ in 0..count {
+            let h = Header::consensus_decode_from_finite_reader(&mut c)?;
+            result.push(h.block_hash());
+            height += 1;
+        }
+    }
+    Ok(result)
+}
+
+/// Picks `n` distinct heights from `first` (above genesis, which has no undo data) up to the tip,
+/// in increasing order, and resolves their hashes.
+fn sample_blocks(
+    backend: Backend,
+    client: &Client,
+    base_url: &str,
+    n: usize,
+    first: usize,
+    rng: &mut StdRng,
+) -> Result<Vec<(usize, BlockHash)>> {
+    let tip = backend.tip(client, base_url)?;
+    let available = (tip + 1).saturating_sub(first);
+    if n > available {
+        return Err(format!("can't sample {} blocks from heights {}..={}", n, first, tip).into());
+    }
+    let mut heights: Vec<usize> = rand::seq::index::sample(rng, available, n)
+        .into_iter()
+        .map(|i| i + first)
+        .collect();
+    heights.sort_unstable();
+    heights
+        .into_iter()
+        .map(|height| Ok((height, backend.blockhash(client, base_url, height)?)))
+        .collect()
+}
+
+/// Loads the hashes of `count` blocks starting at `start` from `dir`, or fetches and stores them.
+fn cached_blockhashes(
+    dir: &Path,
+    args: &Args,
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+) -> Result<Vec<BlockHash>> {
+    let path = dir.join(format!(
+        "{}-{}-{}.hashes",
+        args.network.chain_name(),
+        start,
+        count
+    ));
+    match std::fs::read(&path) {
+        Ok(data) if data.len() == count * 32 => {
+            tracing::info!("loaded {} block hashes from {}", count, path.display());
+            return Ok(data
+                .chunks_exact(32)
+                .map(|h| BlockHash::from_byte_array(h.try_into().unwrap()))
+                .collect());
+        }
+        Ok(_) => tracing::warn!("ignoring truncated {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+    }
+    let batch = args.header_batch as usize;
+    let hashes = fetch_blockhashes(args.backend(0), client, base_url, start, count, batch)?;
+    // Cache only complete ranges, since a shorter result means the chain tip was reached.
+    if hashes.len() == count {
+        std::fs::create_dir_all(dir)?;
+        let data: Vec<u8> = hashes.iter().flat_map(|h| h.to_byte_array()).collect();
+        std::fs::write(&path, data)?;
+    }
+    Ok(hashes)
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Decoder {
+    /// `bitcoin::consensus` decoding into owned types
+    Bitcoin,
+    /// Zero-copy parsing of the response bytes
+    Slices,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    fn default_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 8332,
+            Network::Testnet => 18332,
+            Network::Signet => 38332,
+            Network::Regtest => 18443,
+        }
+    }
+
+    /// electrs' default
+    fn electrum_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 50001,
+            Network::Testnet => 60001,
+            Network::Signet => 60601,
+            Network::Regtest => 60401,
+        }
+    }
+
+    /// As reported by `getblockchaininfo`
+    fn chain_name(self) -> &'static str {
+        match self {
+            Network::Mainnet => "main",
+            Network::Testnet => "test",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    fn bitcoin(self) -> bitcoin::Network {
+        match self {
+            Network::Mainnet => bitcoin::Network::Bitcoin,
+            Network::Testnet => bitcoin::Network::Testnet,
+            Network::Signet => bitcoin::Network::Signet,
+            Network::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+
+    /// Blocks between block subsidy halvings
+    fn halving_interval(self) -> usize {
+        match self {
+            Network::Regtest => 150,
+            _ => 210_000,
+        }
+    }
+}
+
+/// Blocks between difficulty adjustments (on every network)
+const DIFFICULTY_PERIOD: usize = 2016;
+
+/// How `--group-by` splits the blocks into chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Grouping {
+    /// `--chunk-size` blocks
+    Chunk,
+    /// 2016-block difficulty adjustment periods
+    Period,
+    /// Block subsidy halving epochs
+    Epoch,
+    /// Days of block time
+    Day,
+    /// Months of block time
+    Month,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChainInfo {
+    chain: String,
+    blocks: usize,
+    #[serde(default)]
+    headers: usize,
+    #[serde(default)]
+    initialblockdownload: bool,
+    /// Lowest height with block (and undo) data, only reported by pruned nodes
+    pruneheight: Option<usize>,
+}
+
+fn fetch_chaininfo(client: &Client, base_url: &str) -> Result<ChainInfo> {
+    let url = format!("{}/rest/chaininfo.json", base_url);
+    Ok(serde_json::from_str(&client.get_string(&url)?)?)
+}
+
+/// First `--wait-for-node` delay, doubling up to `NODE_BACKOFF_MAX`.
+const NODE_BACKOFF: Duration = Duration::from_secs(1);
+const NODE_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Probes the node until it's reachable, past its RPC warmup and out of initial block download.
+/// Fails with a diagnostic instead of waiting, unless `wait` (`--wait-for-node`) is given.
+fn wait_for_node(client: &Client, base_url: &str, wait: Option<Duration>) -> Result<ChainInfo> {
+    let started = Instant::now();
+    let mut backoff = NODE_BACKOFF;
+    loop {
+        let reason = match fetch_chaininfo(client, base_url) {
+            Ok(info) if !info.initialblockdownload => return Ok(info),
+            Ok(info) => format!(
+                "is in initial block download (at height {} of {})",
+                info.blocks, info.headers
+            ),
+            Err(e) => match Failure::of(e.as_ref()) {
+                Some(Failure::Status(503)) => "is warming up (HTTP 503)".to_owned(),
+                Some(Failure::Io(io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound)) => {
+                    format!("isn't accepting connections ({})", e)
+                }
+                Some(Failure::Status(404)) => {
+                    return Err(format!(
+                        "{} has no REST interface (is bitcoind running with -rest?)",
+                        base_url
+                    )
+                    .into())
+                }
+                _ => return Err(e),
+            },
+        };
+        let Some(wait) = wait else {
+            return Err(format!("{} {}; see --wait-for-node", base_url, reason).into());
+        };
+        if started.elapsed() + backoff > wait {
+            return Err(format!(
+                "{} {}, still after {:.0?} (--wait-for-node)",
+                base_url,
+                reason,
+                started.elapsed()
+            )
+            .into());
+        }
+        tracing::info!("{} {}, retrying in {:?}", base_url, reason, backoff);
+        std::thread::sleep(backoff);
+        backoff = (backoff * 2).min(NODE_BACKOFF_MAX);
+    }
+}
+
+/// Checks that the node is ready and on the expected chain.
+fn check_node(client: &Client, base_url: &str, backend: Backend, args: &Args) -> Result<ChainInfo> {
+    let network = args.network;
+    let info = match backend {
+        Backend::Rest => wait_for_node(client, base_url, args.wait_for_node)?,
+        _ => backend.chaininfo(client, base_url, network)?,
+    };
+    if info.chain != network.chain_name() {
+        return Err(format!(
+            "{} serves chain {:?}, expected {:?}",
+            base_url,
+            info.chain,
+            network.chain_name()
+        )
+        .into());
+    }
+    match info.pruneheight {
+        Some(prune_height) => tracing::info!(
+            "{} is on {} at height {}, pruned below {}",
+            base_url,
+            info.chain,
+            info.blocks,
+            prune_height
+        ),
+        None => tracing::info!(
+            "{} is on {} at height {}",
+            base_url,
+            info.chain,
+            info.blocks
+        ),
+    }
+    Ok(info)
+}
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+/// Bitcoin address indexer
+struct Args {
+    /// TOML file with `flag = value` defaults (flags can also be set via `BENCH_<FLAG>` variables)
+    #[arg(long, env = "BENCH_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// First height of the range (required unless `--sample`)
+    #[arg(value_enum, long = "start")]
+    start: Option<usize>,
+
+    /// Number of blocks in the range (required unless `--sample` or `--duration`)
+    #[arg(value_enum, long = "count")]
+    count: Option<usize>,
+
+    /// Instead of `--count`, fetch blocks from `--start` towards the tip for this long
+    /// (e.g. `60s`), per benchmark and endpoint
+    #[arg(long, value_parser = humantime::parse_duration, conflicts_with_all = ["count", "sample"])]
+    duration: Option<Duration>,
+
+    /// Instead of a range, benchmark this many random heights between genesis (or the highest
+    /// prune height of the nodes) and the tip (seeded by `--seed`)
+    #[arg(long, conflicts_with_all = ["start", "count"])]
+    sample: Option<usize>,
+
+    /// Start the range at the highest prune height of the nodes (with a warning) if it starts
+    /// below it, instead of failing
+    #[arg(long, conflicts_with = "sample")]
+    clamp_pruned: bool,
+
+    /// Exclude these heights and inclusive height ranges from the run
+    /// (e.g. `91722,91880,500000-500100`)
+    #[arg(long, value_delimiter = ',', value_parser = parse_heights)]
+    skip: Vec<RangeInclusive<usize>>,
+
+    /// Benchmark(s) to run, one after another (instead of a subcommand)
+    #[arg(long = "type", value_delimiter = ',', value_parser = benchmarks::parser())]
+    benches: Vec<&'static Entry>,
+
+    /// Decoder implementation (by default, blocks use `slices` and undo data uses `bitcoin`)
+    #[arg(value_enum, long)]
+    decoder: Option<Decoder>,
+
+    /// Also process each block's transactions on this many rayon threads (0 for all cores) with
+    /// `block --decoder bitcoin`, reporting the speedup over a sequential pass
+    #[arg(long)]
+    parallel: Option<usize>,
+
+    /// Decode responses while they are being received, instead of after reading them fully
+    #[arg(long)]
+    streaming: bool,
+
+    /// Also compute the Electrum-style scripthash of every decoded script
+    #[arg(long)]
+    scripthash: bool,
+
+    /// Don't decompress the public keys of uncompressed P2PK scripts in undo data (types 4 and
+    /// 5), only sizing them, when the script bytes aren't needed
+    #[arg(long, conflicts_with = "scripthash")]
+    skip_pubkey_decompression: bool,
+
+    /// Only record the type and size of undo data's scripts, without decompressing them (unless
+    /// `--scripthash` needs their bytes)
+    #[arg(long)]
+    lazy_scripts: bool,
+
+    /// Also compute the txid of every transaction, timing the hashing (the `bitcoin` block
+    /// decoder always computes them)
+    #[arg(long)]
+    txid: bool,
+
+    /// Also rebuild each block's merkle tree from its txids, and compare the root with the header's
+    /// (and similarly check the coinbase's witness commitment, from the wtxids)
+    #[arg(long)]
+    verify_merkle: bool,
+
+    /// Addresses (or `addr()`/`raw()` descriptors) watched by the `wallet` and `filter-query`
+    /// benchmarks, one per line
+    #[arg(long)]
+    addresses: Option<PathBuf>,
+
+    /// Output descriptors (e.g. `wpkh(xpub.../0/*)`) watched by the `wallet` and `filter-query`
+    /// benchmarks, besides the `--addresses`
+    #[arg(long = "descriptor")]
+    descriptors: Vec<String>,
+
+    /// Scripts derived from each `--descriptor` past the last one used
+    #[arg(long, default_value_t = 20)]
+    gap_limit: u32,
+
+    /// False-positive rate the `bloom` benchmark sizes its filter for (within BIP37's limits)
+    #[arg(long, default_value_t = 0.0001)]
+    bloom_fp_rate: f64,
+
+    /// Matched outputs the `bloom` benchmark adds to its filter, as BIP37's `nFlags`
+    #[arg(value_enum, long, default_value = "all")]
+    bloom_update: BloomUpdate,
+
+    /// Also sum coin-days destroyed (value × age) of the spent coins in undo data
+    #[arg(long)]
+    coin_days: bool,
+
+    /// Per-chunk statistics to log
+    #[arg(
+        value_enum,
+        long,
+        value_delimiter = ',',
+        default_value = "counts,scripts,values,sizes,fees"
+    )]
+    stats: Vec<StatsKind>,
+
+    /// Download up to this many responses ahead of the decoder, on a separate thread
+    #[arg(long, default_value_t = 0, conflicts_with = "streaming")]
+    prefetch: usize,
+
+    /// Hash every response, warning about (and counting) those identical to another block's, as
+    /// a broken cache between the client and the node would serve
+    #[arg(long, conflicts_with_all = ["streaming", "prefetch"])]
+    detect_cache: bool,
+
+    /// Fetch every response this many more times right away (without decoding them), comparing
+    /// their latency with the benchmarked fetch's to quantify the node's caching
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["streaming", "prefetch"])]
+    refetch: u32,
+
+    /// Stop prefetching while queued responses exceed this size (e.g. `256MB`)
+    #[arg(long, value_parser = parse_size, requires = "prefetch")]
+    prefetch_memory: Option<usize>,
+
+    /// Bound the responses held in memory (prefetch queue, `--profile-decode` cache), e.g. `1GB`
+    #[arg(long, value_parser = parse_size)]
+    max_memory: Option<usize>,
+
+    /// Ask for compressed responses (e.g. from a proxy in front of the node)
+    #[arg(long)]
+    accept_encoding: Option<Encoding>,
+
+    /// HTTP client backend(s); each one is benchmarked against every `--url`
+    #[arg(long = "http-client", value_delimiter = ',', default_value = "ureq")]
+    http_clients: Vec<HttpClient>,
+
+    /// REST endpoint(s) to benchmark (may be repeated for a comparison run);
+    /// `unix:///path/to/socket` connects via a unix domain socket, and `tcp://host:port` to an
+    /// Electrum server (with `--backend electrum`)
+    #[arg(long = "url", value_delimiter = ',')]
+    urls: Vec<String>,
+
+    /// API served at each `--url` (or one for all of them)
+    #[arg(
+        value_enum,
+        long = "backend",
+        value_delimiter = ',',
+        default_value = "rest"
+    )]
+    backends: Vec<Backend>,
+
+    /// HTTP basic auth credentials (`user:password`), for nodes behind a proxy
+    #[arg(long)]
+    auth: Option<String>,
+
+    /// Send http:// requests through this SOCKS5 proxy (e.g. `socks5://127.0.0.1:9050` for Tor's,
+    /// to reach `.onion` URLs), over a new connection each, whose setup is reported as connect time
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Also send this header (`Name: value`) with every request, e.g. for an authenticating
+    /// proxy or a cache in front of the node (repeatable)
+    #[arg(long = "header", value_name = "HEADER", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Send this User-Agent header, instead of the HTTP client's own
+    #[arg(long)]
+    user_agent: Option<String>,
+
+    /// Send at most this many requests per second (e.g. to a production node), across all clients
+    #[arg(long, value_parser = ratelimit::parse_rps)]
+    max_rps: Option<f64>,
+
+    /// When a benchmarked request fails (with an HTTP error status or an IO error)
+    #[arg(value_enum, long, default_value = "abort")]
+    on_error: OnError,
+
+    /// Attempts after the first one with `--on-error retry`, backing off exponentially
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Wait up to this long (e.g. `10m`) for the node to finish warming up or syncing,
+    /// instead of failing
+    #[arg(long, value_parser = humantime::parse_duration)]
+    wait_for_node: Option<Duration>,
+
+    /// Selects the default REST port, and is checked against the node's chain
+    #[arg(value_enum, long, default_value = "mainnet")]
+    network: Network,
+
+    /// Fetch the range's blocks in random order, defeating the node's sequential read-ahead
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Seed for `--shuffle` and `--sample` (random by default, and logged to allow repeating a run)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Run the whole range this many times (e.g. against a warm cache), reporting run-to-run spread
+    #[arg(
+        long,
+        visible_alias = "passes",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    iterations: u32,
+
+    /// Compare the first and second pass over the range chunk by chunk (with `--passes 2`),
+    /// quantifying the node's page-cache effect
+    #[arg(long)]
+    compare_passes: bool,
+
+    /// Alternate between endpoints after each chunk, instead of running them one after another
+    #[arg(long)]
+    interleave: bool,
+
+    /// Compare two endpoints (e.g. `--backend rest,rpc`) block by block: fetch each chunk from
+    /// both in turn (alternating which goes first), and report their per-block differences
+    #[arg(long, conflicts_with = "interleave")]
+    paired: bool,
+
+    /// `RUST_LOG=debug` additionally emits per-block fetch/decode spans
+    #[arg(value_enum, long, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Also log a status snapshot at this interval (e.g. `10m`), besides on `SIGUSR1`
+    #[arg(long, value_parser = humantime::parse_duration)]
+    status_interval: Option<Duration>,
+
+    /// Show a live dashboard instead of log lines
+    #[arg(long)]
+    tui: bool,
+
+    /// Fetch the range once, then only loop the decoder over it for this long (e.g. `30s`)
+    #[arg(long, value_parser = humantime::parse_duration)]
+    profile_decode: Option<Duration>,
+
+    /// Instead of benchmarking, fetch the range with an adaptive number of requests in flight,
+    /// reporting the one with the highest throughput whose p99 latency is under this (e.g. `20ms`)
+    #[arg(long, value_parser = humantime::parse_duration)]
+    adaptive_concurrency: Option<Duration>,
+
+    /// Set by the `sweep` subcommand
+    #[arg(skip)]
+    sweep: Option<Sweep>,
+
+    /// Set by the `mempool` subcommand
+    #[arg(skip)]
+    mempool: Option<MempoolPolls>,
+
+    /// Blocks per chunk, the granularity of log lines, `--stats` reducers and sinks
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    chunk_size: u64,
+
+    /// Split the blocks into chunks of `--chunk-size`, along difficulty periods or halving epochs,
+    /// or by the day or month of their timestamps
+    #[arg(long, value_enum, default_value = "chunk")]
+    group_by: Grouping,
+
+    /// Headers per `/rest/headers` request when resolving the range's hashes (bitcoind allows
+    /// up to 2000)
+    #[arg(long, default_value_t = 2000, value_parser = clap::value_parser!(u64).range(1..))]
+    header_batch: u64,
+
+    /// Upper bound of `--adaptive-concurrency`
+    #[arg(long, default_value_t = 64, requires = "adaptive_concurrency")]
+    max_concurrency: usize,
+
+    /// perf control FIFO (see `perf record --delay=-1 --control fifo:CTL,ACK`),
+    /// so that only the decode loop is recorded
+    #[arg(long, requires = "profile_decode")]
+    perf_ctl: Option<PathBuf>,
+
+    /// perf acknowledgement FIFO, matching `--perf-ctl`
+    #[arg(long, requires = "perf_ctl")]
+    perf_ack: Option<PathBuf>,
+
+    /// Render per-block latency and response size versus height (`.svg` or `.png`)
+    #[arg(long)]
+    plot: Option<PathBuf>,
+
+    /// Write one JSON line per benchmarked request (URL, status, size, and phase durations)
+    #[arg(long)]
+    trace_requests: Option<PathBuf>,
+
+    /// Write one JSON line per spent coin decoded from undo data (its height, coinbase flag,
+    /// value and script), e.g. of a single block (`--start <height> --count 1`)
+    #[arg(long, conflicts_with = "skip_pubkey_decompression")]
+    dump_undo: Option<PathBuf>,
+
+    /// Write one JSON line per scriptPubKey decoded by the block, block-undo and spent-txouts
+    /// benchmarks (its type, hex and asm), e.g. for checking `decompress_script` on known blocks
+    #[arg(long, conflicts_with = "skip_pubkey_decompression")]
+    dump_scripts: Option<PathBuf>,
+
+    /// Only count (and dump) the scriptPubKeys of these types, in the block, block-undo and
+    /// spent-txouts benchmarks (e.g. `p2tr,p2wsh`)
+    #[arg(value_enum, long, value_delimiter = ',')]
+    filter_script_type: Vec<ScriptType>,
+
+    /// Blocks to list in the summary per benchmark and endpoint, slowest first (0 for none)
+    #[arg(long, default_value_t = 5)]
+    slowest: usize,
+
+    /// Write the end-of-run summary (totals, phase times and counters per benchmark) as JSON
+    #[arg(long)]
+    summary_out: Option<PathBuf>,
+
+    /// Write per-block times as `github-action-benchmark` JSON (`customSmallerIsBetter`)
+    #[arg(long)]
+    github_benchmark: Option<PathBuf>,
+
+    /// Write per-block records to an Apache Parquet file (requires the `parquet` feature)
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+
+    /// Append this run's per-chunk results to an SQLite database (requires the `sqlite` feature)
+    #[arg(long)]
+    sqlite: Option<PathBuf>,
+
+    /// Save each benchmark's progress (and the `utxo` benchmark's coins) to a database in this
+    /// directory after every chunk, and resume the `--start` range after it (requires the `sled`
+    /// feature)
+    #[arg(long, conflicts_with_all = ["sample", "shuffle"])]
+    state_db: Option<PathBuf>,
+
+    /// Send per-chunk results in InfluxDB line protocol to this URL (e.g.
+    /// `http://localhost:8086/write?db=bench`), or print them to stdout (`-`)
+    #[arg(long)]
+    influx: Option<String>,
+
+    /// Node version to tag `--influx` measurements with (the REST interface doesn't report it)
+    #[arg(long, requires = "influx")]
+    node_version: Option<String>,
+
+    /// Send per-request latency timers and byte counters to this statsd server (`host:port`)
+    #[arg(long)]
+    statsd: Option<String>,
+
+    /// Prefix of the `--statsd` metric names
+    #[arg(long, default_value = "bench", requires = "statsd")]
+    statsd_prefix: String,
+
+    /// Export per-block spans and per-request metrics to this OTLP/HTTP collector (e.g.
+    /// `http://localhost:4318`; requires the `otlp` feature)
+    #[arg(long)]
+    otlp: Option<String>,
+
+    /// Directory caching resolved block hashes of a range, so repeated runs skip resolving them
+    #[arg(long)]
+    hash_cache: Option<PathBuf>,
+}
+
+impl Args {
+    /// Of the `url`th `--url`.
+    fn backend(&self, url: usize) -> Backend {
+        self.backends[url.min(self.backends.len() - 1)]
+    }
+
+    /// The tighter of `--prefetch-memory` and `--max-memory`.
+    fn prefetch_cap(&self) -> Option<usize> {
+        match (self.prefetch_memory, self.max_memory) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+fn init_logging(format: LogFormat, quiet: bool, otlp: Option<&Otlp>) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let writer = if quiet {
+        BoxMakeWriter::new(std::io::sink)
+    } else {
+        BoxMakeWriter::new(std::io::stderr)
+    };
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_span_events(FmtSpan::CLOSE);
+    let layer = match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(otlp.map(Otlp::layer))
+        .with(layer.with_filter(filter))
+        .init();
+}
+
+/// Parses a byte size such as `4096`, `64kB`, `256MB` or `1GiB`.
+fn parse_size(s: &str) -> std::result::Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(format!("unknown size unit {:?}", unit)),
+    };
+    let n: usize = digits.parse().map_err(|e| format!("{}: {}", s, e))?;
+    n.checked_mul(multiplier)
+        .ok_or_else(|| format!("{}: too large", s))
+}
+
+/// Parses a request header, such as `Cache-Control: no-cache`.
+fn parse_header(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{}: expected `Name: value`", s))?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("{}: invalid header name", s));
+    }
+    Ok((name.to_owned(), value.trim().to_owned()))
+}
+
+/// Parses a height (`91722`) or an inclusive range of heights (`500000-500100`).
+fn parse_heights(s: &str) -> std::result::Result<RangeInclusive<usize>, String> {
+    let parse = |h: &str| {
+        h.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("{}: {}", s, e))
+    };
+    let (first, last) = match s.split_once('-') {
+        Some((first, last)) => (parse(first)?, parse(last)?),
+        None => (parse(s)?, parse(s)?),
+    };
+    if first > last {
+        return Err(format!("{}: empty range", s));
+    }
+    Ok(first..=last)
+}
+
+/// The `--config` path has to be known before the full command line is parsed.
+fn config_path() -> Option<PathBuf> {
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("BENCH_CONFIG").map(PathBuf::from)
+}
+
+fn toml_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// What `main` runs, as selected by the subcommand.
+enum Invocation {
+    Bench(Box<Args>),
+    /// `selftest --bitcoind <path>`
+    SelfTest(PathBuf),
+    /// `compare <a> <b>`
+    Compare(PathBuf, PathBuf),
+    /// `compressor [--undo <file>...]`
+    Compressor(Compressor),
+}
+
+/// Precedence is: command line, then `BENCH_*` environment, then the config file.
+fn parse_args() -> Result<Invocation> {
+    // Global, so that flags may also follow the subcommand (clap doesn't allow required globals,
+    // but `from_arg_matches` still reports missing values)
+    let mut cmd = Args::command().mut_args(|arg| match arg.get_long() {
+        Some(long) => {
+            let var = format!("BENCH_{}", long.replace('-', "_").to_uppercase());
+            arg.env(var).global(true).required(false)
+        }
+        None => arg,
+    });
+    if let Some(path) = config_path() {
+        let table: toml::Table = std::fs::read_to_string(&path)?.parse()?;
+        for (key, value) in table {
+            let id = match cmd.get_arguments().find(|arg| arg.get_long() == Some(&key)) {
+                Some(arg) => arg.get_id().clone(),
+                None => return Err(format!("{}: unknown setting {:?}", path.display(), key).into()),
+            };
+            let values: Vec<String> = match &value {
+                toml::Value::Array(items) => items.iter().map(toml_to_string).collect(),
+                v => vec![toml_to_string(v)],
+            };
+            cmd = cmd.mut_arg(id, |arg| arg.default_values(values).required(false));
+        }
+    }
+    // Subcommands run a single benchmark, as a shorthand for `--type`
+    cmd = cmd.subcommands(
+        benchmarks::REGISTRY
+            .iter()
+            .map(|entry| clap::Command::new(entry.command).about(entry.about)),
+    );
+    cmd = cmd.subcommand(
+        clap::Command::new("selftest")
+            .about("Runs every benchmark against a temporary regtest node, and checks its stats")
+            .arg(
+                clap::Arg::new("bitcoind")
+                    .long("bitcoind")
+                    .help("bitcoind executable (with `/rest/blockundo` and `/rest/spenttxouts`)")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true),
+            ),
+    );
+    cmd = cmd.subcommand(
+        clap::Command::new("compare")
+            .about("Compares two --summary-out files chunk by chunk, reporting regressions")
+            .args(["a", "b"].map(|name| {
+                clap::Arg::new(name)
+                    .help(format!("Summary of run {}", name.to_uppercase()))
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true)
+            })),
+    );
+    cmd = cmd.subcommand(
+        clap::Command::new("compressor")
+            .about("Times the decompression of each compressed script type, without a node")
+            .arg(
+                clap::Arg::new("undo")
+                    .long("undo")
+                    .help("Saved `/rest/blockundo` responses, whose scripts to decompress instead of generated ones")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .num_args(1..),
+            )
+            .arg(
+                clap::Arg::new("rounds")
+                    .long("rounds")
+                    .help("Decompressions timed per script type")
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .default_value("1000000"),
+            ),
+    );
+    cmd = cmd.subcommand(
+        clap::Command::new("sweep")
+            .about("Repeats the range (selected by --type) at each level of a setting, and compares them")
+            .args(sweep::LEVELS.iter().map(|&(name, help)| {
+                clap::Arg::new(name)
+                    .long(name)
+                    .help(help)
+                    .value_delimiter(',')
+                    .value_parser(clap::value_parser!(u64).range(1..))
+            }))
+            .group(
+                clap::ArgGroup::new("levels")
+                    .args(sweep::LEVELS.iter().map(|&(name, _)| name))
+                    .multiple(true)
+                    .required(true),
+            ),
+    );
+    cmd = cmd.subcommand(
+        clap::Command::new("mempool")
+            .about(
+                "Polls the node's mempool contents at an interval, and diffs successive snapshots",
+            )
+            .arg(
+                clap::Arg::new("interval")
+                    .long("interval")
+                    .help("Between polls (e.g. `1s`)")
+                    .value_parser(humantime::parse_duration)
+                    .default_value("1s"),
+            )
+            .arg(
+                clap::Arg::new("polls")
+                    .long("polls")
+                    .help("Snapshots to fetch")
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .default_value("10"),
+            ),
+    );
+    let matches = cmd.get_matches_mut();
+    if let Some(("selftest", m)) = matches.subcommand() {
+        let bitcoind = m.get_one::<PathBuf>("bitcoind").expect("required");
+        return Ok(Invocation::SelfTest(bitcoind.clone()));
+    }
+    if let Some(("compare", m)) = matches.subcommand() {
+        let path = |name| m.get_one::<PathBuf>(name).expect("required").clone();
+        return Ok(Invocation::Compare(path("a"), path("b")));
+    }
+    if let Some(("compressor", m)) = matches.subcommand() {
+        return Ok(Invocation::Compressor(Compressor {
+            undo: m
+                .get_many::<PathBuf>("undo")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+            rounds: *m.get_one::<u64>("rounds").expect("defaulted") as usize,
+        }));
+    }
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut cmd).exit());
+    if let Some(("mempool", m)) = matches.subcommand() {
+        if args.backend(0) != Backend::Rest {
+            return Err("mempool requires --backend rest".into());
+        }
+        args.mempool = Some(MempoolPolls {
+            interval: *m.get_one::<Duration>("interval").expect("defaulted"),
+            polls: *m.get_one::<u64>("polls").expect("defaulted") as usize,
+        });
+        return Ok(Invocation::Bench(Box::new(args)));
+    }
+    let range = args.start.is_some() && (args.count.is_some() || args.duration.is_some());
+    if args.sample.is_none() && !range {
+        cmd.error(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            "--start and --count (or --duration) are required, unless --sample is given",
+        )
+        .exit();
+    }
+    if let Some(("sweep", m)) = matches.subcommand() {
+        let levels = |name| -> Vec<usize> {
+            m.get_many::<u64>(name)
+                .map(|values| values.map(|&n| n as usize).collect())
+                .unwrap_or_default()
+        };
+        args.sweep = Some(Sweep {
+            jobs: levels("jobs"),
+            chunk_sizes: levels("chunk-sizes"),
+            header_batches: levels("header-batches"),
+        });
+    } else if let Some(command) = matches.subcommand_name() {
+        if matches.value_source("benches") == Some(ValueSource::CommandLine) {
+            return Err("--type can't be combined with a subcommand".into());
+        }
+        let entry = benchmarks::REGISTRY.iter().find(|e| e.command == command);
+        args.benches = vec![entry.expect("registered subcommand")];
+    }
+    if args.compare_passes && args.iterations < 2 {
+        return Err("--compare-passes requires --passes 2 (or more)".into());
+    }
+    if args.backends.len() > 1 && args.backends.len() != args.urls.len() {
+        return Err("--backend takes one value for all the --urls, or one per --url".into());
+    }
+    if args.backend(0) != Backend::Rest {
+        // Both fetch batches of `/rest/headers` from the first `--url`
+        if matches!(args.group_by, Grouping::Day | Grouping::Month) {
+            return Err("--group-by day and month require --backend rest".into());
+        }
+        if args
+            .sweep
+            .as_ref()
+            .is_some_and(|s| !s.header_batches.is_empty())
+        {
+            return Err("--header-batches requires --backend rest".into());
+        }
+    }
+    if args.benches.is_empty() {
+        return Err("no benchmark selected (use a subcommand or --type)".into());
+    }
+    Ok(Invocation::Bench(Box::new(args)))
+}
+
+/// Time spent and bytes received by one benchmark against one endpoint.
+#[derive(Clone, Copy, Debug, Default)]
+struct Total {
+    duration: Duration,
+    bytes: usize,
+    /// Busy time of the fetch stage (including decompression), and of the decode stage
+    fetch: Duration,
+    decode: Duration,
+}
+
+impl Total {
+    /// Averages each endpoint's totals over the `--iterations` runs.
+    fn mean(runs: &[Vec<Total>]) -> Vec<Total> {
+        let n = runs.len() as u32;
+        (0..runs[0].len())
+            .map(|u| {
+                let sum = runs.iter().fold(Total::default(), |sum, run| Total {
+                    duration: sum.duration + run[u].duration,
+                    bytes: sum.bytes + run[u].bytes,
+                    fetch: sum.fetch + run[u].fetch,
+                    decode: sum.decode + run[u].decode,
+                });
+                Total {
+                    duration: sum.duration / n,
+                    bytes: sum.bytes / n as usize,
+                    fetch: sum.fetch / n,
+                    decode: sum.decode / n,
+                }
+            })
+            .collect()
+    }
+
+    /// Logs how busy each stage was: with `--prefetch` they overlap, so the busier one is the bottleneck.
+    fn log_utilization(&self, name: &str, node: &str) {
+        let wall = self.duration.as_secs_f64().max(f64::MIN_POSITIVE);
+        let (fetch, decode) = (
+            self.fetch.as_secs_f64() / wall,
+            self.decode.as_secs_f64() / wall,
+        );
+        tracing::info!(
+            "{}{}: fetch stage {:.0}% busy, decode stage {:.0}% busy ({}-bound)",
+            name,
+            node,
+            fetch * 100.0,
+            decode * 100.0,
+            if fetch >= decode { "fetch" } else { "decode" },
+        );
+    }
+}
+
+/// Logs the run-to-run spread of each endpoint's total time over the `--iterations` runs.
+fn log_spread(name: &str, endpoints: &[Endpoint], runs: &[Vec<Total>]) {
+    for (u, endpoint) in endpoints.iter().enumerate() {
+        let secs: Vec<f64> = runs
+            .iter()
+            .map(|run| run[u].duration.as_secs_f64())
+            .collect();
+        let n = secs.len() as f64;
+        let mean = secs.iter().sum::<f64>() / n;
+        // Sample standard deviation
+        let stddev = (secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        let min = secs.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = secs.iter().copied().fold(0.0, f64::max);
+        tracing::info!(
+            "{}{}: {} iterations, {:.3}[s] ± {:.3} (cv {:.1}%), min {:.3}[s], max {:.3}[s]",
+            name,
+            if endpoints.len() > 1 {
+                format!(" {}", endpoint.label)
+            } else {
+                String::new()
+            },
+            secs.len(),
+            mean,
+            stddev,
+            100.0 * stddev / mean.max(f64::MIN_POSITIVE),
+            min,
+            max,
+        );
+    }
+}
+
+fn mb_per_sec(bytes: usize, duration: Duration) -> f64 {
+    bytes as f64 / 1e6 / duration.as_secs_f64().max(f64::MIN_POSITIVE)
+}
+
+fn print_comparison(endpoints: &[Endpoint], blocks: usize, totals: &[Total]) {
+    let base = totals[0].duration.as_secs_f64();
+    println!(
+        "{:<40} {:>10} {:>12} {:>12} {:>10} {:>10}",
+        "url", "blocks", "total[s]", "[us/call]", "[MB/s]", "relative"
+    );
+    for (endpoint, total) in endpoints.iter().zip(totals) {
+        println!(
+            "{:<40} {:>10} {:>12.3} {:>12} {:>10.1} {:>9.2}x",
+            endpoint.label,
+            blocks,
+            total.duration.as_secs_f64(),
+            total.duration.div_f64(blocks.max(1) as f64).as_micros(),
+            mb_per_sec(total.bytes, total.duration),
+            total.duration.as_secs_f64() / base,
+        );
+    }
+}
+
+/// The `Counters` kept in a block's record.
+#[derive(Clone, Copy, Debug, Default)]
+struct BlockStats {
+    count: u64,
+    count_by_type: [u64; 7],
+    spent: u64,
+    scripts: u64,
+    /// In satoshi-blocks, see `CoinDays`
+    coin_days: u128,
+}
+
+impl BlockStats {
+    #[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+    fn coin_days(&self) -> u64 {
+        (self.coin_days / SAT_BLOCKS_PER_COIN_DAY) as u64
+    }
+}
+
+impl Counters {
+    fn block_stats(&self) -> BlockStats {
+        BlockStats {
+            count: self.count,
+            count_by_type: self.count_by_type,
+            spent: self.spent as u64,
+            scripts: self.scripts,
+            coin_days: self.coin_days.0,
+        }
+    }
+}
+
+impl std::ops::Add for BlockStats {
+    type Output = BlockStats;
+
+    fn add(self, other: BlockStats) -> BlockStats {
+        let mut count_by_type = self.count_by_type;
+        for (a, b) in count_by_type.iter_mut().zip(other.count_by_type) {
+            *a += b;
+        }
+        BlockStats {
+            count: self.count + other.count,
+            count_by_type,
+            spent: self.spent.wrapping_add(other.spent),
+            scripts: self.scripts + other.scripts,
+            coin_days: self.coin_days + other.coin_days,
+        }
+    }
+}
+
+impl std::iter::Sum for BlockStats {
+    fn sum<I: Iterator<Item = BlockStats>>(iter: I) -> BlockStats {
+        iter.fold(BlockStats::default(), std::ops::Add::add)
+    }
+}
+
+/// Timings of a single fetched block.
+#[derive(Clone, Debug)]
+struct BlockRecord {
+    height: usize,
+    hash: BlockHash,
+    /// Decompressed response size
+    bytes: usize,
+    /// Response size on the wire (differs from `bytes` with `--accept-encoding`)
+    received: usize,
+    fetch: Duration,
+    /// Until the response headers were received (part of `fetch`)
+    ttfb: Duration,
+    /// Opening the connection (part of `ttfb`), when known (see `http::Response::connect`)
+    connect: Duration,
+    /// Decompressing the response (included in `decode` when streaming)
+    inflate: Duration,
+    decode: Duration,
+    /// Made while decoding (only counted with the `alloc-stats` feature)
+    allocs: Allocs,
+    stats: BlockStats,
+}
+
+/// Per-block records of one benchmark against one endpoint.
+struct Series {
+    label: String,
+    blocks: Vec<BlockRecord>,
+}
+
+/// One `--url`, accessed via one of the `--http-client` backends.
+struct Endpoint<'a> {
+    client: &'a Client,
+    url: &'a str,
+    backend: Backend,
+    label: String,
+}
+
+/// Per-run state shared by all benchmarks.
+struct Context<'a> {
+    endpoints: &'a [Endpoint<'a>],
+    args: &'a Args,
+    dashboard: Option<&'a Dashboard>,
+    data: Vec<u8>,
+    /// Compressed response, with `--accept-encoding`
+    raw: Vec<u8>,
+    /// Other responses of the combined benchmarks (e.g. `Verify`, `FeeRates`)
+    others: Vec<Vec<u8>>,
+    scratch: Scratch,
+    /// Only collected when some output needs per-block records.
+    series: Vec<Series>,
+    parquet: Option<ParquetSink>,
+    sqlite: Option<SqliteSink>,
+    state: Option<StateDb>,
+    influx: Option<InfluxSink>,
+    /// Current `--iterations` run (from 0)
+    iteration: u32,
+    status: &'a StatusTrigger,
+    progress: Progress,
+    /// Per benchmark and endpoint, for the end-of-run report
+    summaries: Vec<RunSummary>,
+    trace: Option<RequestLog>,
+    statsd: Option<StatsdSink>,
+    otlp: Option<&'a Otlp>,
+    /// Blocks left out by `--on-error skip` in the current chunk
+    skipped: usize,
+    /// When the current chunk must stop, with `--duration`
+    deadline: Option<Instant>,
+    /// `--chunk-size`, or the current `sweep --chunk-sizes` level
+    chunk_size: usize,
+    /// By height, with `--group-by day` or `month`
+    block_times: BTreeMap<usize, u32>,
+    /// With `--detect-cache` or `--refetch`
+    cache_check: Option<CacheCheck>,
+}
+
+impl Context<'_> {
+    fn keep_records(&self) -> bool {
+        self.args.plot.is_some()
+    }
+
+    fn run_chunk(
+        &mut self,
+        endpoint: &Endpoint,
+        entry: &Entry,
+        bench: &mut dyn Benchmark,
+        chunk: &[(usize, BlockHash)],
+        reducers: &mut Reducers,
+        records: &mut Vec<BlockRecord>,
+    ) -> Result<()> {
+        let endpoints = entry.endpoints;
+        if endpoints.len() > 1 {
+            return self.run_chunk_combined(endpoint, entry, bench, chunk, reducers, records);
+        }
+        if self.args.prefetch > 0 {
+            return self.run_chunk_prefetched(endpoint, entry, bench, chunk, reducers, records);
+        }
+        let client = endpoint.client;
+        let prefix = endpoint.backend.block_url(endpoint.url, endpoints[0])?;
+        for &(height, ref hash) in chunk {
+            if self.dashboard.is_some_and(Dashboard::quit_requested) {
+                return Err("interrupted".into());
+            }
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+            if self.status.due() {
+                self.progress.log(&endpoint.label, height, records);
+            }
+            let _block = tracing::debug_span!("block", height, %hash).entered();
+            let url = prefix.get(height, hash);
+            let mut counters = Counters::default();
+            let result = if self.args.streaming {
+                self.fetch_streaming(client, &url, height, *hash, bench, &mut counters)
+            } else {
+                self.fetch_buffered(client, &url, height, *hash, bench, &mut counters)
+            };
+            let record = match result {
+                Ok(record) => record,
+                Err(e) => {
+                    self.skip_failed(height, e)?;
+                    continue;
+                }
+            };
+            // Streamed responses are only read while decoding
+            let data = [&self.data[..]];
+            let responses = if self.args.streaming { &[][..] } else { &data };
+            reducers.add_block(&BlockView::new(&record, &counters, endpoints, responses))?;
+            if let Some(dashboard) = self.dashboard {
+                dashboard.on_block(height, record.bytes, record.fetch + record.decode);
+            }
+            records.push(record);
+        }
+        Ok(())
+    }
+
+    /// Fetches all the responses of a block (into `data` and `others`), and processes them
+    /// together.
+    fn run_chunk_combined(
+        &mut self,
+        endpoint: &Endpoint,
+        entry: &Entry,
+        bench: &mut dyn Benchmark,
+        chunk: &[(usize, BlockHash)],
+        reducers: &mut Reducers,
+        records: &mut Vec<BlockRecord>,
+    ) -> Result<()> {
+        let prefixes: Vec<BlockUrl> = entry
+            .endpoints
+            .iter()
+            .map(|rest| endpoint.backend.block_url(endpoint.url, rest))
+            .collect::<Result<_>>()?;
+        self.others.resize_with(prefixes.len() - 1, Vec::new);
+        for &(height, ref hash) in chunk {
+            if self.dashboard.is_some_and(Dashboard::quit_requested) {
+                return Err("interrupted".into());
+            }
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+            if self.status.due() {
+                self.progress.log(&endpoint.label, height, records);
+            }
+            let _block = tracing::debug_span!("block", height, %hash).entered();
+            for _ in &prefixes {
+                endpoint.client.throttle();
+            }
+            let mut counters = Counters::default();
+            let mut fetches = vec![Fetch::default(); prefixes.len()];
+            let fetched: Result<()> = {
+                let _fetch = tracing::debug_span!("fetch").entered();
+                let buffers = std::iter::once(&mut self.data).chain(&mut self.others);
+                let raw = &mut self.raw;
+                prefixes.iter().zip(buffers).zip(&mut fetches).try_for_each(
+                    |((prefix, data), fetch)| {
+                        let url = prefix.get(height, hash);
+                        *fetch = fetch_into(endpoint.client, &url, raw, data)?;
+                        check_length(fetch, &mut counters, &url);
+                        Ok(())
+                    },
+                )
+            };
+            let fetched = fetched.and_then(|()| {
+                let Some(check) = &mut self.cache_check else {
+                    return Ok(());
+                };
+                let buffers = std::iter::once(&self.data).chain(&self.others);
+                for ((prefix, data), fetch) in prefixes.iter().zip(buffers).zip(&fetches) {
+                    let url = prefix.get(height, hash);
+                    check.check(endpoint.client, &url, height, data, fetch)?;
+                }
+                Ok(())
+            });
+            if let Err(e) = fetched {
+                self.skip_failed(height, e)?;
+                continue;
+            }
+
+            let allocs = alloc::allocs();
+            let others = self.others.iter().map(Vec::as_slice);
+            let responses: Vec<&[u8]> = std::iter::once(&self.data[..]).chain(others).collect();
+            let t = Instant::now();
+            self.scratch.height = height;
+            {
+                let _decode = tracing::debug_span!("decode").entered();
+                bench.decode(&responses, &mut counters, &mut self.scratch)?;
+            }
+            let decode = t.elapsed();
+            let record = BlockRecord {
+                height,
+                hash: *hash,
+                bytes: self.data.len() + self.others.iter().map(Vec::len).sum::<usize>(),
+                received: fetches.iter().map(|f| f.received).sum(),
+                fetch: fetches.iter().map(Fetch::elapsed).sum(),
+                ttfb: fetches.iter().map(|f| f.ttfb).sum(),
+                connect: fetches.iter().filter_map(|f| f.connect).sum(),
+                inflate: fetches.iter().map(|f| f.inflate).sum(),
+                decode,
+                allocs: alloc::allocs() - allocs,
+                stats: counters.block_stats(),
+            };
+            let view = BlockView::new(&record, &counters, entry.endpoints, &responses);
+            reducers.add_block(&view)?;
+            for (prefix, fetch) in prefixes.iter().zip(&fetches) {
+                let url = prefix.get(height, hash);
+                self.log_request(&url, height, fetch, decode)?;
+            }
+            if let Some(dashboard) = self.dashboard {
+                dashboard.on_block(height, record.bytes, record.fetch + record.decode);
+            }
+            records.push(record);
+        }
+        Ok(())
+    }
+
+    /// Decodes responses downloaded ahead by a separate thread.
+    fn run_chunk_prefetched(
+        &mut self,
+        endpoint: &Endpoint,
+        entry: &Entry,
+        bench: &mut dyn Benchmark,
+        chunk: &[(usize, BlockHash)],
+        reducers: &mut Reducers,
+        records: &mut Vec<BlockRecord>,
+    ) -> Result<()> {
+        let prefix = endpoint
+            .backend
+            .block_url(endpoint.url, entry.endpoints[0])?;
+        let urls: Vec<String> = chunk
+            .iter()
+            .map(|(height, hash)| prefix.get(*height, hash))
+            .collect();
+        let (depth, memory_cap) = (self.args.prefetch, self.args.prefetch_cap());
+        std::thread::scope(|s| {
+            let mut prefetcher =
+                Prefetcher::spawn(s, endpoint.client, urls.clone(), depth, memory_cap);
+            for (&(height, ref hash), url) in chunk.iter().zip(&urls) {
+                if self.dashboard.is_some_and(Dashboard::quit_requested) {
+                    return Err("interrupted".into());
+                }
+                if self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    break;
+                }
+                if self.status.due() {
+                    self.progress.log(&endpoint.label, height, records);
+                }
+                let _block = tracing::debug_span!("block", height, %hash).entered();
+                let fetched = match prefetcher.next() {
+                    Ok(fetched) => fetched,
+                    Err(e) => {
+                        self.skip_failed(height, e)?;
+                        continue;
+                    }
+                };
+
+                let mut counters = Counters::default();
+                let allocs = alloc::allocs();
+                let t = Instant::now();
+                self.scratch.height = height;
+                {
+                    let _decode = tracing::debug_span!("decode").entered();
+                    bench.decode(&[&fetched.data], &mut counters, &mut self.scratch)?;
+                }
+                let (fetch, decode) = (fetched.fetch, t.elapsed());
+                check_length(&fetch, &mut counters, url);
+                self.log_request(url, height, &fetch, decode)?;
+                let record = BlockRecord {
+                    height,
+                    hash: *hash,
+                    bytes: fetched.data.len(),
+                    received: fetch.received,
+                    fetch: fetch.elapsed(),
+                    ttfb: fetch.ttfb,
+                    connect: fetch.connect.unwrap_or_default(),
+                    inflate: fetch.inflate,
+                    decode,
+                    allocs: alloc::allocs() - allocs,
+                    stats: counters.block_stats(),
+                };
+                let responses = [&fetched.data[..]];
+                let view = BlockView::new(&record, &counters, entry.endpoints, &responses);
+                reducers.add_block(&view)?;
+                if let Some(dashboard) = self.dashboard {
+                    dashboard.on_block(height, record.bytes, record.fetch + record.decode);
+                }
+                records.push(record);
+                prefetcher.recycle(fetched);
+            }
+            Ok(())
+        })
+    }
+
+    /// Reports a benchmarked request to `--trace-requests`, `--statsd` and `--otlp`.
+    fn log_request(
+        &mut self,
+        url: &str,
+        height: usize,
+        fetch: &Fetch,
+        decode: Duration,
+    ) -> Result<()> {
+        if let Some(log) = &mut self.trace {
+            log.write(self.progress.name, url, height, fetch, decode)?;
+        }
+        if let Some(statsd) = &self.statsd {
+            statsd.send(self.progress.name, url, fetch, decode);
+        }
+        if let Some(otlp) = self.otlp {
+            otlp.record(self.progress.name, url, fetch, decode);
+        }
+        Ok(())
+    }
+
+    /// Returns `error` unless it's a failed request to be skipped with `--on-error skip`.
+    fn skip_failed(&mut self, height: usize, error: Box<dyn std::error::Error>) -> Result<()> {
+        if self.args.on_error != OnError::Skip || !error.is::<RequestError>() {
+            return Err(error);
+        }
+        tracing::warn!("skipping block {}: {}", height, error);
+        self.skipped += 1;
+        Ok(())
+    }
+
+    /// Reads the whole response, then decodes it.
+    fn fetch_buffered(
+        &mut self,
+        client: &Client,
+        url: &str,
+        height: usize,
+        hash: BlockHash,
+        bench: &mut dyn Benchmark,
+        counters: &mut Counters,
+    ) -> Result<BlockRecord> {
+        let data = &mut self.data;
+        client.throttle();
+        let fetch = {
+            let span = tracing::debug_span!("fetch", bytes = field::Empty).entered();
+            let fetch = fetch_into(client, url, &mut self.raw, data)?;
+            span.record("bytes", fetch.received);
+            fetch
+        };
+        check_length(&fetch, counters, url);
+        if let Some(check) = &mut self.cache_check {
+            check.check(client, url, height, data, &fetch)?;
+        }
+
+        let allocs = alloc::allocs();
+        let t = Instant::now();
+        self.scratch.height = height;
+        {
+            let _decode = tracing::debug_span!("decode").entered();
+            bench.decode(&[data], counters, &mut self.scratch)?;
+        }
+        let decode = t.elapsed();
+        let record = BlockRecord {
+            height,
+            hash,
+            bytes: data.len(),
+            received: fetch.received,
+            fetch: fetch.elapsed(),
+            ttfb: fetch.ttfb,
+            connect: fetch.connect.unwrap_or_default(),
+            inflate: fetch.inflate,
+            decode,
+            allocs: alloc::allocs() - allocs,
+            stats: counters.block_stats(),
+        };
+        self.log_request(url, height, &fetch, decode)?;
+        Ok(record)
+    }
+
+    /// Decodes the response body while it is being received, so `fetch` only covers
+    /// the time until the response headers, and `decode` includes the body transfer.
+    fn fetch_streaming(
+        &mut self,
+        client: &Client,
+        url: &str,
+        height: usize,
+        hash: BlockHash,
+        bench: &mut dyn Benchmark,
+        counters: &mut Counters,
+    ) -> Result<BlockRecord> {
+        client.throttle();
+        let (response, ttfb) = {
+            let _fetch = tracing::debug_span!("fetch").entered();
+            client.with_retries(url, || {
+                let t = Instant::now();
+                let response = client.get_encoded(url)?;
+                Ok((response, t.elapsed()))
+            })?
+        };
+
+        let allocs = alloc::allocs();
+        let t = Instant::now();
+        let mut received = CountingReader::new(response.body);
+        let inner: Box<dyn std::io::Read> = match response.encoding {
+            Some(encoding) => encoding.decoder(&mut received),
+            None => Box::new(&mut received),
+        };
+        let mut reader = CountingReader::new(inner);
+        self.scratch.height = height;
+        {
+            let span = tracing::debug_span!("decode", bytes = field::Empty).entered();
+            decode_stream(bench, &mut reader, counters, &mut self.scratch)?;
+            span.record("bytes", reader.count);
+        }
+        let decode = t.elapsed();
+        let bytes = reader.count as usize;
+        drop(reader);
+        let fetch = Fetch {
+            received: received.count as usize,
+            content_length: response.content_length,
+            status: response.status,
+            connect: response.connect,
+            ttfb,
+            transfer: None,
+            inflate: Duration::ZERO,
+        };
+        check_length(&fetch, counters, url);
+        self.log_request(url, height, &fetch, decode)?;
+        let record = BlockRecord {
+            height,
+            hash,
+            bytes,
+            received: fetch.received,
+            fetch: ttfb,
+            ttfb,
+            connect: fetch.connect.unwrap_or_default(),
+            inflate: Duration::ZERO,
+            decode,
+            allocs: alloc::allocs() - allocs,
+            stats: counters.block_stats(),
+        };
+        Ok(record)
+    }
+
+    /// Fetches the range once from the first endpoint, then loops the decoder over it.
+    fn profile_decode(
+        &self,
+        entry: &Entry,
+        blocks: &[(usize, BlockHash)],
+        duration: Duration,
+        perf: Option<&mut PerfControl>,
+    ) -> Result<()> {
+        let &[endpoint_name] = entry.endpoints else {
+            return Err(format!("{} fetches several responses per block", entry.name).into());
+        };
+        let mut bench = (entry.create)(self.args)?;
+        let endpoint = &self.endpoints[0];
+        let prefix = endpoint.backend.block_url(endpoint.url, endpoint_name)?;
+        let mut responses = Responses::default();
+        let max_memory = self.args.max_memory.unwrap_or(usize::MAX);
+        for &(height, hash) in blocks {
+            let url = prefix.get(height, &hash);
+            responses.push(height, endpoint.client.get(&url)?)?;
+            if responses.bytes() > max_memory {
+                return Err(format!(
+                    "{}: cached responses exceed --max-memory after {} blocks",
+                    entry.name,
+                    responses.len()
+                )
+                .into());
+            }
+        }
+        tracing::info!(
+            "{}: cached {} blocks ({} bytes), decoding for {:?}",
+            entry.name,
+            responses.len(),
+            responses.bytes(),
+            duration
+        );
+        let mut counters = Counters::default();
+        let allocs = alloc::allocs();
+        let (blocks, elapsed) = profile::decode_for(
+            bench.as_mut(),
+            &responses,
+            duration,
+            perf,
+            &mut counters,
+            &mut Scratch::new(self.args),
+        )?;
+        let allocs = alloc::allocs() - allocs;
+        tracing::info!(
+            "{}: decoded {} blocks in {:?}, {:.3}[us/block] {:.1}[allocs/block] {:?}",
+            entry.name,
+            blocks,
+            elapsed,
+            elapsed.as_secs_f64() * 1e6 / blocks as f64,
+            allocs.count as f64 / blocks as f64,
+            counters,
+        );
+        Ok(())
+    }
+
+    /// Returns the total time spent per endpoint.
+    fn run_benchmark(
+        &mut self,
+        entry: &Entry,
+        blocks: &[(usize, BlockHash)],
+    ) -> Result<Vec<Total>> {
+        let args = self.args;
+        let endpoints = self.endpoints;
+        let times = &self.block_times;
+        let group = |&(height, _): &(usize, BlockHash)| match args.group_by {
+            Grouping::Chunk => unreachable!("split by size"),
+            Grouping::Period => height / DIFFICULTY_PERIOD,
+            Grouping::Epoch => height / args.network.halving_interval(),
+            Grouping::Day => calendar::day(times[&height]),
+            Grouping::Month => calendar::month(times[&height]),
+        };
+        let chunks: Vec<&[(usize, BlockHash)]> = match args.group_by {
+            Grouping::Chunk => blocks.chunks(self.chunk_size).collect(),
+            _ => blocks.chunk_by(|a, b| group(a) == group(b)).collect(),
+        };
+        let runs: Vec<(usize, usize)> = if args.paired {
+            // The second request may find the block in the node's caches
+            (0..chunks.len())
+                .flat_map(|c| [(c % 2, c), (1 - c % 2, c)])
+                .collect()
+        } else if args.interleave {
+            (0..chunks.len())
+                .flat_map(|c| (0..endpoints.len()).map(move |u| (u, c)))
+                .collect()
+        } else {
+            (0..endpoints.len())
+                .flat_map(|u| (0..chunks.len()).map(move |c| (u, c)))
+                .collect()
+        };
+
+        let name = entry.name;
+        let mut bench = (entry.create)(args)?;
+        self.progress = Progress::new(name, blocks.len() * endpoints.len());
+        let mut summaries = Vec::with_capacity(endpoints.len());
+        for e in endpoints {
+            let reducers = Reducers::new(args, entry.endpoints)?;
+            summaries.push(RunSummary::new(
+                name,
+                e.label.clone(),
+                self.iteration,
+                args.slowest,
+                reducers,
+            ));
+        }
+        let limiter = endpoints[0].client.limiter.as_deref();
+        let (started, usage) = (Instant::now(), limiter.map(RateLimiter::usage));
+        let first_series = self.series.len();
+        if self.keep_records() {
+            let iteration = if args.iterations > 1 {
+                format!(" #{}", self.iteration + 1)
+            } else {
+                String::new()
+            };
+            self.series.extend(endpoints.iter().map(|e| Series {
+                label: format!("{} {}{}", name, e.label, iteration),
+                blocks: Vec::with_capacity(blocks.len()),
+            }));
+        }
+        let mut records = Vec::with_capacity(self.chunk_size);
+        let mut totals = vec![Total::default(); endpoints.len()];
+        let mut decode_allocs = Allocs::default();
+        let (mut bytes, mut received, mut inflate) = (0, 0, Duration::ZERO);
+        // Fetched blocks, and their lowest and highest heights, per endpoint
+        let mut counts = vec![0; endpoints.len()];
+        let mut heights: Vec<Option<(usize, usize)>> = vec![None; endpoints.len()];
+        let mut pairs = Pairs::default();
+        for (u, c) in runs {
+            let chunk = chunks[c];
+            if let Some(budget) = args.duration {
+                if totals[u].duration >= budget {
+                    continue;
+                }
+                self.deadline = Some(Instant::now() + (budget - totals[u].duration));
+            }
+            let node = if endpoints.len() > 1 {
+                format!(" {}", endpoints[u].label)
+            } else {
+                String::new()
+            };
+            if let Some(dashboard) = self.dashboard {
+                dashboard.set_title(format!("{}{}", name, node));
+            }
+            let mut reducers = Reducers::new(args, entry.endpoints)?;
+            let t = Instant::now();
+            records.clear();
+            self.run_chunk(
+                &endpoints[u],
+                entry,
+                bench.as_mut(),
+                chunk,
+                &mut reducers,
+                &mut records,
+            )?;
+            let duration = t.elapsed() - reducers.elapsed();
+            if args.paired {
+                pairs.add(u, &records);
+            }
+            let date = chunk.first().and_then(|(height, _)| {
+                let time = *self.block_times.get(height)?;
+                Some(match args.group_by {
+                    Grouping::Month => calendar::format_month(time),
+                    _ => calendar::format_day(time),
+                })
+            });
+            self.progress.add_chunk(&records);
+            summaries[u].add_chunk(&records, duration, &reducers, date.clone());
+            summaries[u].add_failures(
+                std::mem::take(&mut self.skipped),
+                endpoints[u].client.failures.take(),
+            );
+            let chunk_bytes: usize = records.iter().map(|r| r.received).sum();
+            totals[u].duration += duration;
+            totals[u].bytes += chunk_bytes;
+            if records.is_empty() {
+                continue;
+            }
+            for r in &records {
+                let (first, last) = heights[u].get_or_insert((r.height, r.height));
+                (*first, *last) = ((*first).min(r.height), (*last).max(r.height));
+            }
+            counts[u] += records.len();
+            // Past the chunk's last block (not the highest one, with `--shuffle`)
+            let height = records[records.len() - 1].height + 1;
+            bench.checkpoint()?;
+            if let Some(state) = &self.state {
+                state.set_progress(name, endpoints[u].url, height - 1)?;
+            }
+            let done = records.len();
+            if let Some(dashboard) = self.dashboard {
+                dashboard.on_chunk(&records);
+            }
+            let allocs = if alloc::ENABLED {
+                let total = records.iter().fold(Allocs::default(), |a, r| a + r.allocs);
+                decode_allocs = decode_allocs + total;
+                format!(
+                    " {}[allocs/block] {}[B/block]",
+                    total.count / done as u64,
+                    total.bytes / done as u64
+                )
+            } else {
+                String::new()
+            };
+            for r in &records {
+                bytes += r.bytes;
+                received += r.received;
+                inflate += r.inflate;
+                totals[u].fetch += r.fetch + r.inflate;
+                totals[u].decode += r.decode;
+            }
+            if let Some(sink) = &mut self.sqlite {
+                sink.write_chunk(name, &endpoints[u].label, duration, &records)?;
+            }
+            if let Some(sink) = &mut self.influx {
+                sink.write_chunk(name, &endpoints[u].label, duration, &records)?;
+            }
+            if let Some(sink) = &mut self.parquet {
+                sink.write(name, &endpoints[u].label, &records)?;
+            }
+            if let Some(series) = self.series.get_mut(first_series + u) {
+                series.blocks.append(&mut records);
+            }
+            tracing::info!(
+                "{}{} @{}{} {}[us/call] {:.1}[MB/s]{} {:?}",
+                name,
+                node,
+                height,
+                date.map_or(String::new(), |date| format!(" ({})", date)),
+                duration.div_f32(done as f32).as_micros(),
+                mb_per_sec(chunk_bytes, duration),
+                allocs,
+                reducers.selected(),
+            );
+        }
+        for (endpoint, total) in endpoints.iter().zip(&totals) {
+            let node = if endpoints.len() > 1 {
+                format!(" {}", endpoint.label)
+            } else {
+                String::new()
+            };
+            tracing::info!(
+                "{}{}: {} bytes in {:.3}[s], {:.1}[MB/s]",
+                name,
+                node,
+                total.bytes,
+                total.duration.as_secs_f64(),
+                mb_per_sec(total.bytes, total.duration),
+            );
+            total.log_utilization(name, &node);
+        }
+        if let Some(budget) = args.duration {
+            for ((endpoint, total), (&count, range)) in endpoints
+                .iter()
+                .zip(&totals)
+                .zip(counts.iter().zip(&heights))
+            {
+                let (first, last) = range.unwrap_or_default();
+                tracing::info!(
+                    "{} {}: {} blocks (heights {}..={}) within --duration {:?}, {:.1}[blocks/s]",
+                    name,
+                    endpoint.label,
+                    count,
+                    first,
+                    last,
+                    budget,
+                    count as f64 / total.duration.as_secs_f64().max(f64::MIN_POSITIVE),
+                );
+            }
+        }
+        if args.paired {
+            pairs.report(name, endpoints);
+        }
+        bench.summarize()?;
+        self.summaries.append(&mut summaries);
+        if let (Some(limiter), Some(before)) = (limiter, usage) {
+            let usage = limiter.usage();
+            let elapsed = started.elapsed();
+            let requests = usage.requests - before.requests;
+            tracing::info!(
+                "{}: {} requests in {:.3}[s], {:.1}[req/s] of --max-rps {}, {:.3}[s] throttled",
+                name,
+                requests,
+                elapsed.as_secs_f64(),
+                requests as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+                limiter.max_rps(),
+                (usage.waited - before.waited).as_secs_f64(),
+            );
+        }
+        if let Some(encoding) = args.accept_encoding {
+            tracing::info!(
+                "{}: {} bytes received ({}), {} bytes decompressed ({:.2}x), {:.3}[s] decompressing",
+                name,
+                received,
+                encoding.name(),
+                bytes,
+                bytes as f64 / received.max(1) as f64,
+                inflate.as_secs_f64(),
+            );
+        }
+        if alloc::ENABLED {
+            let blocks = (blocks.len() * endpoints.len()).max(1) as u64;
+            tracing::info!(
+                "{}: {} allocations ({} bytes) while decoding, {:.2}[allocs/block]",
+                name,
+                decode_allocs.count,
+                decode_allocs.bytes,
+                decode_allocs.count as f64 / blocks as f64,
+            );
+        }
+        Ok(totals)
+    }
+}
+
+fn main() -> Result<()> {
+    let mut args = match parse_args()? {
+        Invocation::Bench(args) => *args,
+        Invocation::SelfTest(bitcoind) => {
+            init_logging(LogFormat::Text, false, None);
+            return selftest::run(&bitcoind);
+        }
+        Invocation::Compare(a, b) => return compare::run(&a, &b),
+        Invocation::Compressor(compressor) => return compressor::run(&compressor),
+    };
+    let otlp = args.otlp.as_deref().map(Otlp::new).transpose()?;
+    init_logging(args.log_format, args.tui, otlp.as_ref());
+    let started = Instant::now();
+    if args.urls.is_empty() {
+        args.urls.push(match args.backend(0) {
+            Backend::Electrum => format!("tcp://localhost:{}", args.network.electrum_port()),
+            _ => format!("http://localhost:{}", args.network.default_port()),
+        });
+    }
+
+    let limiter = args.max_rps.map(|rps| Arc::new(RateLimiter::new(rps)));
+    let clients = args
+        .http_clients
+        .iter()
+        .map(|&kind| Client::new(&args, kind, limiter.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let labelled = clients.len() > 1;
+    let mixed = args.backends.len() > 1;
+    let endpoints: Vec<Endpoint> = clients
+        .iter()
+        .flat_map(|client| {
+            let args = &args;
+            args.urls.iter().enumerate().map(move |(i, url)| {
+                let backend = args.backend(i);
+                // The same node may be compared over several backends
+                let mut label = if mixed {
+                    format!("{} ({})", url, backend.name())
+                } else {
+                    url.clone()
+                };
+                if labelled {
+                    label = format!("{} [{}]", label, client.kind.name());
+                }
+                Endpoint {
+                    client,
+                    url,
+                    backend,
+                    label,
+                }
+            })
+        })
+        .collect();
+    if args.paired && endpoints.len() != 2 {
+        return Err(format!("--paired compares 2 endpoints, not {}", endpoints.len()).into());
+    }
+    let client = &clients[0];
+    // The highest prune height of all nodes, with the node having it
+    let mut pruned: Option<(usize, &str)> = None;
+    // The lowest tip of all nodes, up to which `--duration` may fetch
+    let mut tip = usize::MAX;
+    for (i, url) in args.urls.iter().enumerate() {
+        let info = check_node(client, url, args.backend(i), &args)?;
+        tip = tip.min(info.blocks);
+        if let Some(height) = info.pruneheight {
+            pruned = pruned.max(Some((height, url)));
+        }
+    }
+    if let Some(polls) = &args.mempool {
+        return mempool::run(client, &args.urls[0], polls);
+    }
+    let status = StatusTrigger::new(args.status_interval)?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let count = match args.duration {
+        Some(_) => args.start.map(|start| (tip + 1).saturating_sub(start)),
+        None => args.count,
+    };
+    let mut blocks = match (args.sample, args.start, count) {
+        (Some(n), _, _) => {
+            tracing::info!("sampling {} blocks (--seed {})", n, seed);
+            let first = pruned.map_or(1, |(height, _)| height.max(1));
+            sample_blocks(args.backend(0), client, &args.urls[0], n, first, &mut rng)?
+        }
+        (None, Some(start), Some(count)) => {
+            let (start, count) = match pruned.filter(|&(height, _)| height > start) {
+                None => (start, count),
+                Some((height, url)) if args.clamp_pruned && height < start + count => {
+                    tracing::warn!(
+                        "{} is pruned below height {}, skipping {} blocks from {}",
+                        url,
+                        height,
+                        height - start,
+                        start
+                    );
+                    (height, start + count - height)
+                }
+                Some((height, url)) => {
+                    return Err(format!(
+                        "{} is pruned below height {}, so blocks from {} are unavailable{}",
+                        url,
+                        height,
+                        start,
+                        if args.clamp_pruned {
+                            ""
+                        } else {
+                            " (see --clamp-pruned)"
+                        }
+                    )
+                    .into())
+                }
+            };
+            let hashes = match &args.hash_cache {
+                Some(dir) => cached_blockhashes(dir, &args, client, &args.urls[0], start, count)?,
+                None => fetch_blockhashes(
+                    args.backend(0),
+                    client,
+                    &args.urls[0],
+                    start,
+                    count,
+                    args.header_batch as usize,
+                )?,
+            };
+            (start..).zip(hashes).collect()
+        }
+        _ => unreachable!("checked by parse_args"),
+    };
+    if !args.skip.is_empty() {
+        let before = blocks.len();
+        blocks.retain(|(height, _)| !args.skip.iter().any(|r| r.contains(height)));
+        tracing::info!("skipping {} blocks (--skip)", before - blocks.len());
+    }
+    let state = args.state_db.as_deref().map(StateDb::open).transpose()?;
+    if let Some(state) = &state {
+        // Where the least advanced benchmark stopped
+        let mut resume = Some(usize::MAX);
+        for entry in &args.benches {
+            for url in &args.urls {
+                resume = resume.min(state.progress(entry.name, url)?);
+            }
+        }
+        if let Some(height) = resume {
+            let skipped = blocks.iter().take_while(|&&(h, _)| h <= height).count();
+            tracing::info!(
+                "resuming after height {} (--state-db), skipping {} blocks",
+                height,
+                skipped
+            );
+            blocks.drain(..skipped);
+        }
+    }
+    if args.shuffle {
+        tracing::info!("shuffling blocks (--seed {})", seed);
+        blocks.shuffle(&mut rng);
+    }
+    let block_times = match args.group_by {
+        Grouping::Day | Grouping::Month => {
+            calendar::block_times(client, &args.urls[0], &blocks, args.header_batch as usize)?
+        }
+        _ => BTreeMap::new(),
+    };
+    match args.duration {
+        Some(budget) => tracing::info!("fetching up to {} blocks for {:?}", blocks.len(), budget),
+        None => tracing::info!("fetching {} blocks", blocks.len()),
+    }
+
+    if let Some(target) = args.adaptive_concurrency {
+        for entry in &args.benches {
+            for rest in entry.endpoints {
+                for endpoint in &endpoints {
+                    let prefix = endpoint.backend.block_url(endpoint.url, rest)?;
+                    let max = args.max_concurrency.max(1);
+                    adaptive::run(endpoint, rest, &prefix, &blocks, target, max)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(sweep) = &args.sweep {
+        if !sweep.header_batches.is_empty() {
+            let Some(start) = args.start.filter(|_| args.sample.is_none()) else {
+                return Err("--header-batches requires a --start range".into());
+            };
+            // After `--clamp-pruned`, the range may start later
+            let start = blocks
+                .iter()
+                .map(|&(height, _)| height)
+                .min()
+                .unwrap_or(start);
+            sweep::header_batches(
+                client,
+                &args.urls[0],
+                start,
+                blocks.len(),
+                &sweep.header_batches,
+            )?;
+        }
+        for entry in &args.benches {
+            if sweep.jobs.is_empty() {
+                break;
+            }
+            for rest in entry.endpoints {
+                for endpoint in &endpoints {
+                    let prefix = endpoint.backend.block_url(endpoint.url, rest)?;
+                    sweep::jobs(endpoint, rest, &prefix, &blocks, &sweep.jobs)?;
+                }
+            }
+        }
+        if sweep.chunk_sizes.is_empty() {
+            return Ok(());
+        }
+    }
+
+    if let Some(duration) = args.profile_decode {
+        let mut perf = match &args.perf_ctl {
+            Some(ctl) => Some(PerfControl::open(ctl, args.perf_ack.as_deref())?),
+            None => None,
+        };
+        let ctx = Context {
+            endpoints: &endpoints,
+            args: &args,
+            dashboard: None,
+            data: Vec::new(),
+            raw: Vec::new(),
+            others: Vec::new(),
+            scratch: Scratch::new(&args),
+            series: Vec::new(),
+            parquet: None,
+            sqlite: None,
+            state: None,
+            influx: None,
+            iteration: 0,
+            status: &status,
+            progress: Progress::new("", 0),
+            summaries: Vec::new(),
+            trace: None,
+            statsd: None,
+            otlp: None,
+            skipped: 0,
+            deadline: None,
+            chunk_size: args.chunk_size as usize,
+            block_times: BTreeMap::new(),
+            cache_check: None,
+        };
+        for entry in &args.benches {
+            ctx.profile_decode(entry, &blocks, duration, perf.as_mut())?;
+        }
+        return Ok(());
+    }
+
+    let total_blocks =
+        blocks.len() * endpoints.len() * args.benches.len() * args.iterations as usize;
+    let dashboard = args.tui.then(|| Dashboard::new(total_blocks));
+    let mut ctx = Context {
+        endpoints: &endpoints,
+        args: &args,
+        dashboard: dashboard.as_ref(),
+        data: Vec::with_capacity(10_000_000),
+        raw: Vec::new(),
+        others: Vec::new(),
+        scratch: Scratch::new(&args),
+        series: Vec::new(),
+        parquet: args
+            .parquet
+            .as_deref()
+            .map(ParquetSink::create)
+            .transpose()?,
+        sqlite: args
+            .sqlite
+            .as_deref()
+            .map(|path| SqliteSink::open(path, &args))
+            .transpose()?,
+        state: state.clone(),
+        influx: args
+            .influx
+            .as_deref()
+            .map(|target| InfluxSink::new(target, args.node_version.as_deref()))
+            .transpose()?,
+        iteration: 0,
+        status: &status,
+        progress: Progress::new("", 0),
+        summaries: Vec::new(),
+        trace: args
+            .trace_requests
+            .as_deref()
+            .map(RequestLog::create)
+            .transpose()?,
+        statsd: args
+            .statsd
+            .as_deref()
+            .map(|addr| StatsdSink::connect(addr, &args.statsd_prefix))
+            .transpose()?,
+        otlp: otlp.as_ref(),
+        skipped: 0,
+        deadline: None,
+        chunk_size: args.chunk_size as usize,
+        block_times,
+        cache_check: CacheCheck::new(args.detect_cache, args.refetch),
+    };
+    ctx.scratch.dump_undo = args.dump_undo.as_deref().map(Dump::create).transpose()?;
+    ctx.scratch.dump_scripts = args.dump_scripts.as_deref().map(Dump::create).transpose()?;
+    if let Some(sweep) = &args.sweep {
+        // Indexed by chunk size and benchmark
+        let mut totals = Vec::with_capacity(sweep.chunk_sizes.len());
+        for &size in &sweep.chunk_sizes {
+            tracing::info!("chunk size {}", size);
+            ctx.chunk_size = size;
+            let runs = args
+                .benches
+                .iter()
+                .map(|entry| ctx.run_benchmark(entry, &blocks));
+            totals.push(runs.collect::<Result<Vec<_>>>()?);
+        }
+        for (b, entry) in args.benches.iter().enumerate() {
+            println!("{}", entry.name);
+            let levels = totals.iter().map(|runs| &runs[b][..]);
+            sweep::print_chunk_sizes(&endpoints, blocks.len(), &sweep.chunk_sizes, levels);
+        }
+        return Ok(());
+    }
+    let done = AtomicBool::new(false);
+    let totals = std::thread::scope(|s| {
+        let ui = dashboard.as_ref().map(|d| s.spawn(|| d.run(&done)));
+        // Indexed by benchmark, iteration and endpoint
+        let mut totals = vec![Vec::new(); args.benches.len()];
+        let result = (0..args.iterations).try_for_each(|iteration| {
+            if args.iterations > 1 {
+                tracing::info!("iteration {}/{}", iteration + 1, args.iterations);
+            }
+            ctx.iteration = iteration;
+            for (entry, totals) in args.benches.iter().zip(&mut totals) {
+                totals.push(ctx.run_benchmark(entry, &blocks)?);
+            }
+            Ok::<_, Box<dyn std::error::Error>>(())
+        });
+        let totals = result.map(|()| totals);
+        done.store(true, Ordering::Relaxed);
+        if let Some(ui) = ui {
+            ui.join().expect("TUI thread panicked")?;
+        }
+        totals
+    })?;
+
+    if args.iterations > 1 {
+        for (entry, runs) in args.benches.iter().zip(&totals) {
+            log_spread(entry.name, &endpoints, runs);
+        }
+    }
+    // Endpoints are compared by their mean over all iterations
+    let totals = totals.iter().map(|runs| Total::mean(runs));
+    if endpoints.len() > 1 {
+        for (entry, totals) in args.benches.iter().zip(totals) {
+            println!("{}", entry.name);
+            print_comparison(&endpoints, blocks.len(), &totals);
+        }
+    }
+    if let Some(sink) = ctx.parquet.take() {
+        let rows = sink.close()?;
+        tracing::info!(
+            "wrote {} rows to {}",
+            rows,
+            args.parquet.as_ref().unwrap().display()
+        );
+    }
+    if let Some(path) = &args.plot {
+        plot::render(path, &ctx.series)?;
+        tracing::info!("plotted {} series to {}", ctx.series.len(), path.display());
+    }
+    if let Some(check) = &mut ctx.cache_check {
+        check.summarize();
+    }
+    if let Some(dump) = ctx.scratch.dump_undo.take() {
+        let lines = dump.finish()?;
+        tracing::info!(
+            "dumped {} spent coins to {}",
+            lines,
+            args.dump_undo.as_ref().unwrap().display()
+        );
+    }
+    if let Some(dump) = ctx.scratch.dump_scripts.take() {
+        let lines = dump.finish()?;
+        tracing::info!(
+            "dumped {} scripts to {}",
+            lines,
+            args.dump_scripts.as_ref().unwrap().display()
+        );
+    }
+    if let Some(log) = ctx.trace.take() {
+        let lines = log.finish()?;
+        tracing::info!(
+            "traced {} requests to {}",
+            lines,
+            args.trace_requests.as_ref().unwrap().display()
+        );
+    }
+    summary::report(&ctx.summaries, started, args.summary_out.as_deref())?;
+    if args.compare_passes {
+        summary::compare_passes(&ctx.summaries);
+    }
+    summary::extrapolate(&ctx.summaries, tip + 1, args.sample.is_some());
+    if let Some(path) = &args.github_benchmark {
+        summary::write_github_benchmark(&ctx.summaries, path)?;
+    }
+    if let Some(otlp) = &otlp {
+        otlp.shutdown()?;
+    }
+    if let Some(rss) = alloc::peak_rss() {
+        tracing::info!("peak RSS: {:.1} MB", rss as f64 / 1e6);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_heights, parse_size};
+
+    #[test]
+    fn sizes() {
+        let sizes = [
+            ("0", 0),
+            ("512", 512),
+            ("512b", 512),
+            ("4k", 4_000),
+            (" 4 KB ", 4_000),
+            ("16m", 16_000_000),
+            ("2GB", 2_000_000_000),
+            ("64KiB", 64 << 10),
+            ("1mib", 1 << 20),
+            ("3GiB", 3 << 30),
+        ];
+        for (s, n) in sizes {
+            assert_eq!(parse_size(s), Ok(n), "{}", s);
+        }
+        for s in ["", "k", "1.5m", "-1", "4 kilobytes", "12tb"] {
+            assert!(parse_size(s).is_err(), "{}", s);
+        }
+        let too_large = format!("{}gib", usize::MAX >> 20);
+        assert_eq!(
+            parse_size(&too_large),
+            Err(format!("{}: too large", too_large))
+        );
+    }
+
+    #[test]
+    fn heights() {
+        assert_eq!(parse_heights("91722"), Ok(91722..=91722));
+        assert_eq!(parse_heights("500000-500100"), Ok(500000..=500100));
+        assert_eq!(parse_heights(" 10 - 20 "), Ok(10..=20));
+        assert_eq!(parse_heights("7-7"), Ok(7..=7));
+        assert_eq!(parse_heights("20-10"), Err("20-10: empty range".into()));
+        for s in ["", "-", "1-", "-5", "a-b", "1-2-3", "1.5"] {
+            assert!(parse_heights(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! The `mempool` subcommand: polls `/rest/mempool/contents.json` at an interval, and diffs each
+//! snapshot with the previous one, as a mempool-tracking service would.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{adaptive::percentile, Client, Result};
+
+/// Set by the `mempool` subcommand.
+#[derive(Clone, Debug)]
+pub struct MempoolPolls {
+    pub interval: Duration,
+    pub polls: usize,
+}
+
+/// The fields of a (verbose) mempool entry that trackers typically index.
+#[derive(Deserialize)]
+struct Entry {
+    vsize: u64,
+    fees: Fees,
+}
+
+#[derive(Deserialize)]
+struct Fees {
+    /// In BTC
+    base: f64,
+}
+
+/// One poll's measurements.
+struct Snapshot {
+    bytes: usize,
+    txs: usize,
+    vsize: u64,
+    added: usize,
+    removed: usize,
+    fetch: Duration,
+    parse: Duration,
+    diff: Duration,
+}
+
+pub fn run(client: &Client, base_url: &str, polls: &MempoolPolls) -> Result<()> {
+    let url = format!(
+        "{}/rest/mempool/contents.json",
+        base_url.trim_end_matches('/')
+    );
+    let mut previous: Option<HashMap<String, Entry>> = None;
+    let mut snapshots = Vec::with_capacity(polls.polls);
+    let mut data = Vec::new();
+    let started = Instant::now();
+    for poll in 0..polls.polls {
+        let next = started + polls.interval * poll as u32;
+        std::thread::sleep(next.saturating_duration_since(Instant::now()));
+
+        let t = Instant::now();
+        data.clear();
+        client.get(&url)?.read_to_end(&mut data)?;
+        let fetch = t.elapsed();
+
+        let t = Instant::now();
+        let entries: HashMap<String, Entry> =
+            serde_json::from_slice(&data).map_err(|e| format!("{}: {}", url, e))?;
+        let parse = t.elapsed();
+
+        let t = Instant::now();
+        let (added, removed) = match &previous {
+            Some(previous) => (
+                entries
+                    .keys()
+                    .filter(|k| !previous.contains_key(*k))
+                    .count(),
+                previous
+                    .keys()
+                    .filter(|k| !entries.contains_key(*k))
+                    .count(),
+            ),
+            None => (entries.len(), 0),
+        };
+        let diff = t.elapsed();
+
+        let snapshot = Snapshot {
+            bytes: data.len(),
+            txs: entries.len(),
+            vsize: entries.values().map(|e| e.vsize).sum(),
+            added,
+            removed,
+            fetch,
+            parse,
+            diff,
+        };
+        let fees: f64 = entries.values().map(|e| e.fees.base).sum();
+        tracing::info!(
+            "mempool: {} txs ({} vbytes, {:.8} BTC fees; {} bytes), +{} -{}; fetched in \
+             {:.1}[ms], parsed in {:.1}[ms], diffed in {:.1}[ms]",
+            snapshot.txs,
+            snapshot.vsize,
+            fees,
+            snapshot.bytes,
+            snapshot.added,
+            snapshot.removed,
+            fetch.as_secs_f64() * 1e3,
+            parse.as_secs_f64() * 1e3,
+            diff.as_secs_f64() * 1e3
+        );
+        snapshots.push(snapshot);
+        previous = Some(entries);
+    }
+    summarize(&snapshots, polls.interval);
+    Ok(())
+}
+
+fn summarize(snapshots: &[Snapshot], interval: Duration) {
+    // The first snapshot is all additions
+    let Some(diffs) = snapshots.get(1..).filter(|d| !d.is_empty()) else {
+        return;
+    };
+    let median_max = |select: fn(&Snapshot) -> Duration| {
+        let mut times: Vec<Duration> = snapshots.iter().map(select).collect();
+        times.sort_unstable();
+        (percentile(&times, 50), times[times.len() - 1])
+    };
+    let mean = |select: fn(&Snapshot) -> usize| {
+        diffs.iter().map(select).sum::<usize>() as f64 / diffs.len() as f64
+    };
+    let (fetch, fetch_max) = median_max(|s| s.fetch);
+    let (parse, parse_max) = median_max(|s| s.parse);
+    let (diff, diff_max) = median_max(|s| s.diff);
+    let max_bytes = snapshots.iter().map(|s| s.bytes).max().unwrap_or_default();
+    let (added, removed) = (mean(|s| s.added), mean(|s| s.removed));
+    tracing::info!(
+        "mempool: {} polls every {:?}: {:.0} txs and {:.0} bytes per snapshot on average \
+         ({} bytes at most); +{:.1} -{:.1} txs per poll ({:.1}[txs/s] churn)",
+        snapshots.len(),
+        interval,
+        snapshots.iter().map(|s| s.txs).sum::<usize>() as f64 / snapshots.len() as f64,
+        snapshots.iter().map(|s| s.bytes).sum::<usize>() as f64 / snapshots.len() as f64,
+        max_bytes,
+        added,
+        removed,
+        (added + removed) / interval.as_secs_f64().max(f64::MIN_POSITIVE)
+    );
+    tracing::info!(
+        "mempool: fetch median {:.1}[ms] (max {:.1}), parse median {:.1}[ms] (max {:.1}), \
+         diff median {:.1}[ms] (max {:.1})",
+        fetch.as_secs_f64() * 1e3,
+        fetch_max.as_secs_f64() * 1e3,
+        parse.as_secs_f64() * 1e3,
+        parse_max.as_secs_f64() * 1e3,
+        diff.as_secs_f64() * 1e3,
+        diff_max.as_secs_f64() * 1e3
+    );
+}
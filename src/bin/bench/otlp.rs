@@ -0,0 +1,127 @@
+//! `--otlp`: exports the per-block spans (with their `fetch` and `decode` children) and
+//! per-request metrics to an OpenTelemetry collector over OTLP/HTTP, e.g. for Jaeger or Tempo.
+
+pub use imp::Otlp;
+
+#[cfg(feature = "otlp")]
+mod imp {
+    use std::time::Duration;
+
+    use opentelemetry::{
+        metrics::{Counter, Histogram, MeterProvider},
+        trace::TracerProvider,
+        KeyValue,
+    };
+    use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::SdkTracerProvider, Resource};
+    use tracing::Level;
+    use tracing_subscriber::{filter::Targets, registry::Registry, Layer};
+
+    use crate::{Fetch, Result};
+
+    const SERVICE: &str = "bench";
+
+    pub struct Otlp {
+        tracers: SdkTracerProvider,
+        meters: SdkMeterProvider,
+        /// In seconds
+        fetch: Histogram<f64>,
+        ttfb: Histogram<f64>,
+        decode: Histogram<f64>,
+        received: Counter<u64>,
+    }
+
+    impl Otlp {
+        /// `endpoint` is the collector's base URL, e.g. `http://localhost:4318`.
+        pub fn new(endpoint: &str) -> Result<Self> {
+            let endpoint = endpoint.trim_end_matches('/');
+            let resource = Resource::builder().with_service_name(SERVICE).build();
+            let spans = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint))
+                .build()?;
+            let tracers = SdkTracerProvider::builder()
+                .with_batch_exporter(spans)
+                .with_resource(resource.clone())
+                .build();
+            let metrics = MetricExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/metrics", endpoint))
+                .build()?;
+            let meters = SdkMeterProvider::builder()
+                .with_periodic_exporter(metrics)
+                .with_resource(resource)
+                .build();
+            let meter = meters.meter(SERVICE);
+            let seconds = |name: &'static str| meter.f64_histogram(name).with_unit("s").build();
+            Ok(Otlp {
+                fetch: seconds("bench.fetch.duration"),
+                ttfb: seconds("bench.ttfb.duration"),
+                decode: seconds("bench.decode.duration"),
+                received: meter.u64_counter("bench.received").with_unit("By").build(),
+                tracers,
+                meters,
+            })
+        }
+
+        /// Exports this binary's spans, including the `debug` ones hidden from the log.
+        pub fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+            tracing_opentelemetry::layer()
+                .with_tracer(self.tracers.tracer(SERVICE))
+                .with_filter(Targets::new().with_target(SERVICE, Level::DEBUG))
+                .boxed()
+        }
+
+        pub fn record(&self, bench: &str, url: &str, fetch: &Fetch, decode: Duration) {
+            let (node, path) = url.split_once("/rest/").unwrap_or((url, ""));
+            let resource = path.split('/').next().unwrap_or_default();
+            let attributes = [
+                KeyValue::new("bench", bench.to_owned()),
+                KeyValue::new("node", node.to_owned()),
+                KeyValue::new("resource", resource.to_owned()),
+            ];
+            self.fetch
+                .record(fetch.elapsed().as_secs_f64(), &attributes);
+            self.ttfb.record(fetch.ttfb.as_secs_f64(), &attributes);
+            self.decode.record(decode.as_secs_f64(), &attributes);
+            self.received.add(fetch.received as u64, &attributes);
+        }
+
+        /// Exports what's still buffered.
+        pub fn shutdown(&self) -> Result<()> {
+            self.tracers.shutdown()?;
+            self.meters.shutdown()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "otlp"))]
+mod imp {
+    use std::time::Duration;
+
+    use tracing_subscriber::{registry::Registry, Layer};
+
+    use crate::{Fetch, Result};
+
+    /// Stands in for the real exporter, so that `--otlp` fails with a clear error.
+    pub struct Otlp(std::convert::Infallible);
+
+    impl Otlp {
+        pub fn new(_endpoint: &str) -> Result<Self> {
+            Err("built without the `otlp` feature".into())
+        }
+
+        pub fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+            match self.0 {}
+        }
+
+        pub fn record(&self, _bench: &str, _url: &str, _fetch: &Fetch, _decode: Duration) {
+            match self.0 {}
+        }
+
+        pub fn shutdown(&self) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! `--paired`: compares two endpoints block by block (e.g. a node's REST and RPC), fetching each
+//! chunk from both in turn, so that load and cache changes during the run affect both alike.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{BlockRecord, Endpoint};
+
+/// Blocks with the largest differences, logged per benchmark.
+const MAX_REPORTED: usize = 5;
+
+/// Each block's time (fetching and decoding) from both endpoints, by height.
+#[derive(Default)]
+pub struct Pairs(BTreeMap<usize, [Option<Duration>; 2]>);
+
+impl Pairs {
+    pub fn add(&mut self, endpoint: usize, records: &[BlockRecord]) {
+        for r in records {
+            self.0.entry(r.height).or_default()[endpoint] = Some(r.fetch + r.inflate + r.decode);
+        }
+    }
+
+    /// Prints the distribution of the second endpoint's differences from the first's.
+    pub fn report(&self, name: &str, endpoints: &[Endpoint]) {
+        // In microseconds, by height
+        let mut deltas: Vec<(f64, usize)> = Vec::with_capacity(self.0.len());
+        let mut ratios = Vec::with_capacity(self.0.len());
+        for (&height, pair) in &self.0 {
+            let [Some(a), Some(b)] = *pair else {
+                continue;
+            };
+            deltas.push(((b.as_secs_f64() - a.as_secs_f64()) * 1e6, height));
+            ratios.push(b.as_secs_f64() / a.as_secs_f64().max(f64::MIN_POSITIVE));
+        }
+        if deltas.is_empty() {
+            tracing::warn!("{}: no blocks fetched from both endpoints", name);
+            return;
+        }
+        deltas.sort_by(|x, y| x.0.total_cmp(&y.0));
+        ratios.sort_by(f64::total_cmp);
+        let n = deltas.len();
+        let at = |p: usize| deltas[(n * p).div_ceil(100).max(1) - 1].0;
+        let faster = deltas.iter().filter(|(delta, _)| *delta < 0.0).count();
+        let (a, b) = (&endpoints[0].label, &endpoints[1].label);
+        println!("{}: {} minus {}, per block, over {} blocks", name, b, a, n);
+        println!(
+            "{:>14} {:>14} {:>14} {:>14} {:>12} {:>10}",
+            "p10[us]", "median[us]", "p90[us]", "mean[us]", "ratio", "faster"
+        );
+        println!(
+            "{:>+14.0} {:>+14.0} {:>+14.0} {:>+14.0} {:>11.2}x {:>9.1}%",
+            at(10),
+            at(50),
+            at(90),
+            deltas.iter().map(|(delta, _)| delta).sum::<f64>() / n as f64,
+            ratios[(n / 2).min(n - 1)],
+            100.0 * faster as f64 / n as f64,
+        );
+        let mut largest = deltas.clone();
+        largest.sort_by(|x, y| y.0.abs().total_cmp(&x.0.abs()));
+        let largest: Vec<String> = largest
+            .iter()
+            .take(MAX_REPORTED)
+            .map(|(delta, height)| format!("{} {:+.0}[us]", height, delta))
+            .collect();
+        tracing::info!(
+            "{}: largest differences of {} from {}: {}",
+            name,
+            b,
+            a,
+            largest.join(", ")
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! `--parquet` export, writing one row per block and one row group per chunk.
+
+pub use imp::ParquetSink;
+
+#[cfg(feature = "parquet")]
+mod imp {
+    use std::{fs::File, path::Path, sync::Arc};
+
+    use parquet::{
+        basic::Compression,
+        data_type::{ByteArray, ByteArrayType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    use crate::{BlockRecord, Result};
+
+    const SCHEMA: &str = "
+        message block {
+            required binary bench (UTF8);
+            required binary url (UTF8);
+            required int64 height;
+            required binary hash (UTF8);
+            required int64 bytes;
+            required int64 received;
+            required int64 fetch_ns;
+            required int64 ttfb_ns;
+            required int64 inflate_ns;
+            required int64 decode_ns;
+            required int64 allocs;
+            required int64 alloc_bytes;
+            required int64 coins;
+            required int64 spent;
+            required int64 scripts;
+            required int64 coin_days;
+        }
+    ";
+
+    pub struct ParquetSink {
+        writer: SerializedFileWriter<File>,
+        rows: u64,
+    }
+
+    impl ParquetSink {
+        pub fn create(path: &Path) -> Result<Self> {
+            let schema = Arc::new(parse_message_type(SCHEMA)?);
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let writer = SerializedFileWriter::new(File::create(path)?, schema, Arc::new(props))?;
+            Ok(ParquetSink { writer, rows: 0 })
+        }
+
+        pub fn write(&mut self, bench: &str, url: &str, records: &[BlockRecord]) -> Result<()> {
+            if records.is_empty() {
+                return Ok(());
+            }
+            let ints = |f: fn(&BlockRecord) -> u64| -> Vec<i64> {
+                records.iter().map(|r| f(r) as i64).collect()
+            };
+
+            let mut row_group = self.writer.next_row_group()?;
+            let mut column = 0;
+            // Columns are visited in `SCHEMA` order.
+            while let Some(mut writer) = row_group.next_column()? {
+                if let 0 | 1 | 3 = column {
+                    let values: Vec<ByteArray> = match column {
+                        0 => vec![bench.into(); records.len()],
+                        1 => vec![url.into(); records.len()],
+                        _ => records
+                            .iter()
+                            .map(|r| r.hash.to_string().as_str().into())
+                            .collect(),
+                    };
+                    writer
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                } else {
+                    let values = match column {
+                        2 => ints(|r| r.height as u64),
+                        4 => ints(|r| r.bytes as u64),
+                        5 => ints(|r| r.received as u64),
+                        6 => ints(|r| r.fetch.as_nanos() as u64),
+                        7 => ints(|r| r.ttfb.as_nanos() as u64),
+                        8 => ints(|r| r.inflate.as_nanos() as u64),
+                        9 => ints(|r| r.decode.as_nanos() as u64),
+                        10 => ints(|r| r.allocs.count),
+                        11 => ints(|r| r.allocs.bytes),
+                        12 => ints(|r| r.stats.count),
+                        13 => ints(|r| r.stats.spent),
+                        14 => ints(|r| r.stats.scripts),
+                        15 => ints(|r| r.stats.coin_days()),
+                        _ => unreachable!("column {} is not in SCHEMA", column),
+                    };
+                    writer
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                writer.close()?;
+                column += 1;
+            }
+            row_group.close()?;
+            self.rows += records.len() as u64;
+            Ok(())
+        }
+
+        /// Writes the footer, returning the number of rows written.
+        pub fn close(self) -> Result<u64> {
+            self.writer.close()?;
+            Ok(self.rows)
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+mod imp {
+    use std::path::Path;
+
+    use crate::{BlockRecord, Result};
+
+    /// Stands in for the real sink, so that `--parquet` fails with a clear error.
+    pub struct ParquetSink(std::convert::Infallible);
+
+    impl ParquetSink {
+        pub fn create(_path: &Path) -> Result<Self> {
+            Err("built without the `parquet` feature".into())
+        }
+
+        pub fn write(&mut self, _bench: &str, _url: &str, _records: &[BlockRecord]) -> Result<()> {
+            match self.0 {}
+        }
+
+        pub fn close(self) -> Result<u64> {
+            match self.0 {}
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use plotters::{coord::Shift, prelude::*};
+
+use crate::{Result, Series};
+
+const SIZE: (u32, u32) = (1600, 900);
+
+/// Renders per-block latency (left axis) and response size (right axis) versus height.
+/// The output format is chosen by the file extension (`.svg` or `.png`).
+pub fn render(path: &Path, series: &[Series]) -> Result<()> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => draw(SVGBackend::new(path, SIZE).into_drawing_area(), series),
+        Some("png") => draw(BitMapBackend::new(path, SIZE).into_drawing_area(), series),
+        _ => Err(format!("{}: expected a .svg or .png path", path.display()).into()),
+    }
+}
+
+fn draw<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, series: &[Series]) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let blocks = || series.iter().flat_map(|s| &s.blocks);
+    let (Some(min_height), Some(max_height)) = (
+        blocks().map(|b| b.height).min(),
+        blocks().map(|b| b.height).max(),
+    ) else {
+        return Err("no blocks to plot".into());
+    };
+    let max_latency = blocks().map(latency_ms).fold(0.0, f64::max);
+    let max_size = blocks().map(|b| b.bytes as f64 / 1e3).fold(0.0, f64::max);
+
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("latency and response size by height", ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .right_y_label_area_size(70)
+        .build_cartesian_2d(min_height..max_height + 1, 0.0..max_latency * 1.05)?
+        .set_secondary_coord(min_height..max_height + 1, 0.0..max_size * 1.05);
+    chart
+        .configure_mesh()
+        .x_desc("height")
+        .y_desc("latency [ms]")
+        .draw()?;
+    chart
+        .configure_secondary_axes()
+        .y_desc("response size [kB]")
+        .draw()?;
+
+    for (i, s) in series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        let size_color = color.mix(0.3);
+        chart
+            .draw_series(LineSeries::new(
+                s.blocks.iter().map(|b| (b.height, latency_ms(b))),
+                color.stroke_width(1),
+            ))?
+            .label(s.label.as_str())
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], color));
+        chart
+            .draw_secondary_series(LineSeries::new(
+                s.blocks.iter().map(|b| (b.height, b.bytes as f64 / 1e3)),
+                size_color,
+            ))?
+            .label(format!("{} size", s.label))
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], size_color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+fn latency_ms(block: &crate::BlockRecord) -> f64 {
+    (block.fetch + block.decode).as_secs_f64() * 1e3
+}
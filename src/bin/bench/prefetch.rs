@@ -0,0 +1,145 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Arc, Condvar, Mutex,
+    },
+    thread::Scope,
+};
+
+use crate::{failures::OnError, fetch_into, Client, Fetch, RequestError, Result};
+
+/// Sent from the downloader; failed requests keep their `RequestError` for `--on-error`.
+type Download = std::result::Result<Fetched, Box<dyn std::error::Error + Send + Sync>>;
+
+/// A response downloaded ahead of the decoder.
+pub struct Fetched {
+    pub data: Vec<u8>,
+    pub fetch: Fetch,
+}
+
+#[derive(Default)]
+struct Usage {
+    bytes: usize,
+    closed: bool,
+}
+
+/// Bytes held by fetched-but-not-yet-decoded responses.
+struct Budget {
+    usage: Mutex<Usage>,
+    released: Condvar,
+    cap: Option<usize>,
+}
+
+impl Budget {
+    /// Waits until there is room for another response (the cap may be exceeded by one response,
+    /// since its size is only known after it is downloaded). Returns `false` if the decoder is gone.
+    fn acquire(&self) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        if let Some(cap) = self.cap {
+            while usage.bytes >= cap && !usage.closed {
+                usage = self.released.wait(usage).unwrap();
+            }
+        }
+        !usage.closed
+    }
+
+    fn add(&self, bytes: usize) {
+        self.usage.lock().unwrap().bytes += bytes;
+    }
+
+    fn release(&self, bytes: usize) {
+        self.usage.lock().unwrap().bytes -= bytes;
+        self.released.notify_all();
+    }
+
+    fn close(&self) {
+        self.usage.lock().unwrap().closed = true;
+        self.released.notify_all();
+    }
+}
+
+/// Downloads `urls` in order on a separate thread, keeping at most `depth` responses
+/// (and roughly `memory_cap` bytes) queued ahead of the decoder.
+pub struct Prefetcher {
+    fetched: Receiver<Download>,
+    recycle: Sender<Vec<u8>>,
+    budget: Arc<Budget>,
+}
+
+impl Prefetcher {
+    pub fn spawn<'scope>(
+        scope: &'scope Scope<'scope, '_>,
+        client: &'scope Client,
+        urls: Vec<String>,
+        depth: usize,
+        memory_cap: Option<usize>,
+    ) -> Self {
+        let (tx, fetched) = mpsc::sync_channel(depth);
+        let (recycle, recycled) = mpsc::channel();
+        let budget = Arc::new(Budget {
+            usage: Mutex::default(),
+            released: Condvar::new(),
+            cap: memory_cap,
+        });
+        let downloader_budget = Arc::clone(&budget);
+        scope.spawn(move || download(client, urls, tx, recycled, &downloader_budget));
+        Prefetcher {
+            fetched,
+            recycle,
+            budget,
+        }
+    }
+
+    pub fn next(&mut self) -> Result<Fetched> {
+        match self.fetched.recv() {
+            Ok(result) => result.map_err(|e| -> Box<dyn std::error::Error> { e }),
+            Err(_) => Err("prefetch thread exited".into()),
+        }
+    }
+
+    /// Returns a decoded response's buffer, to be reused for a later download.
+    pub fn recycle(&self, fetched: Fetched) {
+        self.budget.release(fetched.data.len());
+        let _ = self.recycle.send(fetched.data);
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.budget.close();
+    }
+}
+
+fn download(
+    client: &Client,
+    urls: Vec<String>,
+    tx: SyncSender<Download>,
+    recycled: Receiver<Vec<u8>>,
+    budget: &Budget,
+) {
+    let mut raw = Vec::new();
+    for url in urls {
+        if !budget.acquire() {
+            return;
+        }
+        let mut data = recycled.try_recv().unwrap_or_default();
+        client.throttle();
+        let result = fetch_into(client, &url, &mut raw, &mut data)
+            .map(|fetch| {
+                budget.add(data.len());
+                Fetched { data, fetch }
+            })
+            .map_err(|e| match e.downcast::<RequestError>() {
+                Ok(e) => e as Box<dyn std::error::Error + Send + Sync>,
+                Err(e) => e.to_string().into(),
+            });
+        // With `--on-error skip`, the decoder skips the failed block and expects the next one.
+        let stop = match &result {
+            Ok(_) => false,
+            Err(e) => client.on_error != OnError::Skip || !e.is::<RequestError>(),
+        };
+        if tx.send(result).is_err() || stop {
+            return;
+        }
+    }
+}
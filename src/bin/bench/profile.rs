@@ -0,0 +1,116 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{benchmarks::Benchmark, Counters, Result, Scratch};
+
+/// Responses of a whole range, stored back-to-back to keep the decode loop allocation-free.
+#[derive(Default)]
+pub struct Responses {
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+    heights: Vec<usize>,
+}
+
+impl Responses {
+    pub fn push(&mut self, height: usize, mut reader: impl std::io::Read) -> Result<()> {
+        if self.offsets.is_empty() {
+            self.offsets.push(0);
+        }
+        reader.read_to_end(&mut self.data)?;
+        self.offsets.push(self.data.len());
+        self.heights.push(height);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.offsets.windows(2).map(|w| &self.data[w[0]..w[1]])
+    }
+}
+
+/// Drives `perf record --delay=-1 --control fifo:CTL[,ACK]`, so that only the decode loop is sampled.
+pub struct PerfControl {
+    ctl: File,
+    ack: Option<BufReader<File>>,
+}
+
+impl PerfControl {
+    pub fn open(ctl: &Path, ack: Option<&Path>) -> Result<Self> {
+        let ctl = OpenOptions::new().write(true).open(ctl)?;
+        let ack = ack.map(File::open).transpose()?.map(BufReader::new);
+        Ok(PerfControl { ctl, ack })
+    }
+
+    fn command(&mut self, cmd: &str) -> Result<()> {
+        writeln!(self.ctl, "{}", cmd)?;
+        self.ctl.flush()?;
+        if let Some(ack) = &mut self.ack {
+            let mut line = String::new();
+            ack.read_line(&mut line)?;
+            if !line.starts_with("ack") {
+                return Err(format!("unexpected perf reply to {:?}: {:?}", cmd, line).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes `responses` in a loop for `duration`, returning the number of decoded blocks.
+pub fn decode_for(
+    bench: &mut dyn Benchmark,
+    responses: &Responses,
+    duration: Duration,
+    perf: Option<&mut PerfControl>,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<(u64, Duration)> {
+    let mut perf = perf;
+    if let Some(perf) = perf.as_deref_mut() {
+        perf.command("enable")?;
+    }
+    tracing::info!("decode loop started");
+    let result = decode_loop(bench, responses, duration, counters, scratch);
+    tracing::info!("decode loop finished");
+    if let Some(perf) = perf {
+        perf.command("disable")?;
+    }
+    result
+}
+
+/// Kept out-of-line, so it shows up as a single root in flamegraphs.
+#[inline(never)]
+fn decode_loop(
+    bench: &mut dyn Benchmark,
+    responses: &Responses,
+    duration: Duration,
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<(u64, Duration)> {
+    if responses.len() == 0 {
+        return Err("no blocks to decode".into());
+    }
+    let t = Instant::now();
+    let mut blocks = 0;
+    loop {
+        for (&height, data) in responses.heights.iter().zip(responses.iter()) {
+            scratch.height = height;
+            bench.decode(&[data], counters, scratch)?;
+            blocks += 1;
+            let elapsed = t.elapsed();
+            if elapsed >= duration {
+                return Ok((blocks, elapsed));
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! `--max-rps`: spaces out REST requests, so that a production node still serves its other clients.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+struct State {
+    /// Earliest time the next request may be sent
+    next: Instant,
+    requests: u64,
+    waited: Duration,
+}
+
+/// Shared by all HTTP clients (and prefetch threads), so the cap applies to the whole run.
+pub struct RateLimiter {
+    max_rps: f64,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+/// Requests sent (and time spent waiting for the cap) up to some point of the run.
+#[derive(Clone, Copy)]
+pub struct Usage {
+    pub requests: u64,
+    pub waited: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(max_rps: f64) -> Self {
+        RateLimiter {
+            max_rps,
+            interval: Duration::from_secs_f64(1.0 / max_rps),
+            state: Mutex::new(State {
+                next: Instant::now(),
+                requests: 0,
+                waited: Duration::ZERO,
+            }),
+        }
+    }
+
+    pub fn max_rps(&self) -> f64 {
+        self.max_rps
+    }
+
+    /// Blocks until another request may be sent. Idle time isn't saved up for later bursts.
+    pub fn acquire(&self) {
+        let now = Instant::now();
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let slot = state.next.max(now);
+            state.next = slot + self.interval;
+            state.requests += 1;
+            state.waited += slot - now;
+            slot - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        let state = self.state.lock().unwrap();
+        Usage {
+            requests: state.requests,
+            waited: state.waited,
+        }
+    }
+}
+
+/// Parses a positive `--max-rps` value.
+pub fn parse_rps(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rps) if rps > 0.0 && rps.is_finite() => Ok(rps),
+        _ => Err(format!("expected a positive rate, got {:?}", s)),
+    }
+}
@@ -0,0 +1,1029 @@
+//! `--stats` reducers, each owning and summarizing one aspect of the blocks in its log line.
+//!
+//! Decoders only fill a block's plain `Counters`, which some reducers add up (and the `block`
+//! decoder the `BlockFields` some read, when selected). Others analyze the block's responses
+//! themselves, after it's decoded (and timed), so optional analyses (e.g. spent-coin ages) are
+//! only computed when selected. Each chunk has its own reducers, merged into the run's for the
+//! end-of-run summary.
+
+mod ages;
+mod inscriptions;
+mod locktimes;
+mod sequences;
+mod shape;
+mod sigops;
+mod spends;
+mod taproot;
+mod versions;
+mod weight;
+
+use std::{
+    any::Any,
+    cell::OnceCell,
+    fmt::{self, DebugStruct},
+    marker::PhantomData,
+    ops::{ControlFlow, Range},
+    time::{Duration, Instant},
+};
+
+use bitcoin::TxIn;
+use bitcoin_slices::{bsl, Visit, Visitor};
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    script_type::ScriptType, spenttxouts_parse, undo, Args, BlockRecord, CoinDays, Counters,
+    FeeRates, Result, SAT_BLOCKS_PER_COIN_DAY,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StatsKind {
+    /// Decoded coins (or outputs), scripthashes and `verify` mismatches
+    Counts,
+    /// Per-block fetch and decode latency, in microseconds (min/avg/max)
+    Latency,
+    /// Histograms of time-to-first-byte and of body transfer time (not when streaming)
+    Ttfb,
+    /// Decompressed response sizes (min/avg/max)
+    Sizes,
+    /// Coins per script type, and total decompressed script size
+    Scripts,
+    /// Spent value (and coin-days destroyed, with `--coin-days`)
+    Values,
+    /// Spent-coin ages and coinbase spends, from undo data (`undo`, `verify`, `fee-rates`)
+    Ages,
+    /// Fee rate histogram (`fee-rates`)
+    Fees,
+    /// Legacy, P2SH and witness signature operations, and the largest block's sigop cost, from
+    /// blocks (estimated without the outputs they spend)
+    Sigops,
+    /// Total weight and vsize of blocks, and how full they are on average and at most, from blocks
+    Weight,
+    /// Histograms of inputs and outputs per transaction, and of transactions per block, from blocks
+    Shape,
+    /// Transaction versions, and the version bits blocks signal, from blocks
+    Versions,
+    /// Inputs signalling replaceability (BIP 125) or using relative locktimes (BIP 68), from blocks
+    Sequences,
+    /// Transaction locktimes: zero, block heights or timestamps, from blocks
+    Locktimes,
+    /// Spent coins by output kind: legacy, P2SH (and P2SH-wrapped segwit v0, with blocks),
+    /// segwit v0, taproot or another witness version, from undo data or spent outputs
+    Spends,
+    /// Key path and script path spends of taproot outputs, and those with an annex, from blocks
+    /// and the outputs they spend (`fees`, `filters`, `silent-payments`, `wallet`, `scripts`)
+    Taproot,
+    /// Envelopes (`OP_FALSE OP_IF ... OP_ENDIF`) in the tapscripts of taproot script path spends,
+    /// and the bytes they push (`fees`, `filters`, `silent-payments`, `wallet`, `scripts`)
+    Inscriptions,
+}
+
+pub trait Reducer: Merge {
+    /// Called for every block of the chunk, after it's decoded.
+    fn add_block(&mut self, block: &BlockView) -> Result<()>;
+
+    /// Adds another chunk's state.
+    fn merge(&mut self, other: &Self)
+    where
+        Self: Sized;
+
+    /// Adds this reducer's fields to a chunk's (or the run's) summary.
+    fn report(&self, out: &mut DebugStruct);
+
+    /// Adds this reducer's totals to the run's `--summary-out` JSON.
+    fn json(&self, _out: &mut Map<String, Value>) {}
+
+    /// Logs the run's totals that don't fit its summary line, `run` naming the benchmark and
+    /// endpoint, whose blocks took `decode` to decode.
+    fn summarize(&self, _run: &str, _decode: Duration) {}
+}
+
+/// `Reducer::merge`, for reducers behind `dyn Reducer` (of the same kind).
+pub trait Merge {
+    fn merge_dyn(&mut self, other: &dyn Reducer);
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<R: Reducer + 'static> Merge for R {
+    fn merge_dyn(&mut self, other: &dyn Reducer) {
+        let other = other
+            .as_any()
+            .downcast_ref()
+            .expect("a reducer of the same kind");
+        self.merge(other);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The kinds reading `BlockFields`, which the `block` decoder then collects as it visits.
+const FIELDS: [StatsKind; 5] = [
+    StatsKind::Versions,
+    StatsKind::Sequences,
+    StatsKind::Locktimes,
+    StatsKind::Spends,
+    StatsKind::Taproot,
+];
+
+/// Whether the `--stats` kinds read `BlockFields`.
+pub fn reads_fields(stats: &[StatsKind]) -> bool {
+    FIELDS.iter().any(|kind| stats.contains(kind))
+}
+
+/// The fields of a block's transactions that some reducers read, collected by a single
+/// `bitcoin_slices` visit (the `block` decoder's, or else `BlockView::fields`).
+#[derive(Debug, Default)]
+pub struct BlockFields {
+    pub version: i32,
+    pub txs: Vec<TxFields>,
+    /// Of all the transactions, in order
+    pub inputs: Vec<InputFields>,
+    /// Of the transaction being visited
+    first_input: usize,
+    witness: usize,
+}
+
+#[derive(Debug)]
+pub struct TxFields {
+    pub version: i32,
+    pub lock_time: u32,
+    /// Its inputs, in `BlockFields::inputs`
+    pub inputs: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct InputFields {
+    pub sequence: u32,
+    /// Witness elements, and whether the last of several is an annex (BIP 341)
+    pub witness_len: usize,
+    pub annex: bool,
+    /// Whether the scriptSig is a single push of a v0 witness program (BIP 141's P2SH-wrapped
+    /// segwit)
+    pub pushes_v0_program: bool,
+}
+
+impl InputFields {
+    /// Whether the witness is a taproot script path spend's, given it spends a taproot output.
+    pub fn is_script_path(&self) -> bool {
+        self.witness_len - self.annex as usize >= 2
+    }
+}
+
+impl Visitor for BlockFields {
+    fn visit_block_header(&mut self, header: &bsl::BlockHeader) -> ControlFlow<()> {
+        self.version = header.version();
+        ControlFlow::Continue(())
+    }
+
+    fn visit_tx_ins(&mut self, _total_inputs: usize) {
+        self.first_input = self.inputs.len();
+    }
+
+    fn visit_tx_in(&mut self, _vin: usize, tx_in: &bsl::TxIn) -> ControlFlow<()> {
+        let pushes_v0_program = match tx_in.script_sig() {
+            [0x16, 0x00, 0x14, program @ ..] => program.len() == 20,
+            [0x22, 0x00, 0x20, program @ ..] => program.len() == 32,
+            _ => false,
+        };
+        self.inputs.push(InputFields {
+            sequence: tx_in.sequence(),
+            pushes_v0_program,
+            ..InputFields::default()
+        });
+        ControlFlow::Continue(())
+    }
+
+    fn visit_witness(&mut self, vin: usize) -> ControlFlow<()> {
+        self.witness = self.first_input + vin;
+        ControlFlow::Continue(())
+    }
+
+    fn visit_witness_total_element(&mut self, witness_total: usize) {
+        self.inputs[self.witness].witness_len = witness_total;
+    }
+
+    fn visit_witness_element(&mut self, witness_i: usize, witness_element: &[u8]) {
+        let input = &mut self.inputs[self.witness];
+        if witness_i > 0 && witness_i + 1 == input.witness_len {
+            input.annex = witness_element.first() == Some(&0x50);
+        }
+    }
+
+    fn visit_transaction(&mut self, tx: &bsl::Transaction) -> ControlFlow<()> {
+        self.txs.push(TxFields {
+            version: tx.version(),
+            lock_time: tx.locktime(),
+            inputs: self.first_input..self.inputs.len(),
+        });
+        ControlFlow::Continue(())
+    }
+}
+
+/// A decoded block, as the reducers see it.
+pub struct BlockView<'a> {
+    pub record: &'a BlockRecord,
+    pub counters: &'a Counters,
+    endpoints: &'a [&'a str],
+    /// Of `endpoints`, in order (none when streaming)
+    responses: &'a [&'a [u8]],
+    /// Decoded from the `block` response by the first reducer reading it
+    block: OnceCell<bitcoin::Block>,
+    /// Visited by the first reducer reading them, unless the decoder collected them
+    fields: OnceCell<BlockFields>,
+    spent_types: OnceCell<Vec<Vec<ScriptType>>>,
+}
+
+impl<'a> BlockView<'a> {
+    pub fn new(
+        record: &'a BlockRecord,
+        counters: &'a Counters,
+        endpoints: &'a [&'a str],
+        responses: &'a [&'a [u8]],
+    ) -> Self {
+        BlockView {
+            record,
+            counters,
+            endpoints,
+            responses,
+            block: OnceCell::new(),
+            fields: OnceCell::new(),
+            spent_types: OnceCell::new(),
+        }
+    }
+
+    /// The response of `endpoint` (e.g. `blockundo`), which a reducer is only created for if the
+    /// benchmark fetches it.
+    pub fn response(&self, endpoint: &str) -> Result<&'a [u8]> {
+        let index = self.endpoints.iter().position(|&e| e == endpoint);
+        index
+            .and_then(|index| self.responses.get(index).copied())
+            .ok_or_else(|| format!("block {}: no {} response", self.record.height, endpoint).into())
+    }
+
+    /// The `block` response, decoded once for all the reducers (its decoder checks that it's
+    /// fully consumed).
+    pub fn block(&self) -> Result<&bitcoin::Block> {
+        if let Some(block) = self.block.get() {
+            return Ok(block);
+        }
+        let (block, _) = bitcoin::consensus::deserialize_partial(self.response("block")?)?;
+        Ok(self.block.get_or_init(|| block))
+    }
+
+    /// The fields of the block's transactions, as its decoder collected them, or else visiting
+    /// the `block` response once for all the reducers.
+    pub fn fields(&self) -> Result<&BlockFields> {
+        if let Some(fields) = self.counters.fields.as_ref().or(self.fields.get()) {
+            return Ok(fields);
+        }
+        let mut fields = BlockFields::default();
+        bsl::Block::visit(self.response("block")?, &mut fields)
+            .map_err(|e| format!("block {}: invalid block: {:?}", self.record.height, e))?;
+        Ok(self.fields.get_or_init(|| fields))
+    }
+
+    /// The types of the outputs spent by each non-coinbase transaction, from the `spenttxouts`
+    /// response, or else the `blockundo` one.
+    pub fn spent_types(&self) -> Result<&[Vec<ScriptType>]> {
+        if let Some(types) = self.spent_types.get() {
+            return Ok(types);
+        }
+        let mut types = Vec::new();
+        if self.endpoints.contains(&"spenttxouts") {
+            let mut spent = Vec::new();
+            spenttxouts_parse(self.response("spenttxouts")?, &mut spent)?;
+            let outputs = spent.iter().map(|outputs| {
+                let scripts = outputs.iter().map(|txout| &txout.script_pubkey);
+                scripts.map(|script| ScriptType::of(script)).collect()
+            });
+            types.extend(outputs);
+        } else {
+            let undo = self.response("blockundo")?;
+            types.resize_with(undo::tx_count(undo)?, Vec::new);
+            undo::visit_txs(undo, |tx, coin| types[tx].push(coin.script.script_type()))?;
+        }
+        Ok(self.spent_types.get_or_init(|| types))
+    }
+
+    /// The fields of the non-coinbase transactions' inputs, with the types of the outputs they
+    /// spend, checking that these match.
+    pub fn spent_inputs(&self) -> Result<impl Iterator<Item = (&InputFields, ScriptType)>> {
+        let fields = self.fields()?;
+        let txs = fields.txs.get(1..).unwrap_or_default();
+        let spent = self.spent_types()?;
+        if !txs
+            .iter()
+            .map(|tx| tx.inputs.len())
+            .eq(spent.iter().map(Vec::len))
+        {
+            let height = self.record.height;
+            return Err(format!("block {}: spent outputs don't match its inputs", height).into());
+        }
+        let first = txs
+            .first()
+            .map_or(fields.inputs.len(), |tx| tx.inputs.start);
+        Ok(fields.inputs[first..]
+            .iter()
+            .zip(spent.iter().flatten().copied()))
+    }
+
+    /// The non-coinbase transactions' inputs, with the types of the outputs they spend,
+    /// checking that these match.
+    pub fn spent_txins(&self) -> Result<impl Iterator<Item = (&TxIn, ScriptType)>> {
+        let txs = self.block()?.txdata.get(1..).unwrap_or_default();
+        let spent = self.spent_types()?;
+        let inputs = txs.iter().map(|tx| tx.input.len());
+        if !inputs.eq(spent.iter().map(Vec::len)) {
+            let height = self.record.height;
+            return Err(format!("block {}: spent outputs don't match its inputs", height).into());
+        }
+        let inputs = txs.iter().flat_map(|tx| &tx.input);
+        Ok(inputs.zip(spent.iter().flatten().copied()))
+    }
+}
+
+/// Weight units per virtual byte, and per legacy sigop (BIP 141).
+const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// Reduced even when not selected, for the end-of-run summary.
+const TOTALS: [StatsKind; 4] = [
+    StatsKind::Counts,
+    StatsKind::Scripts,
+    StatsKind::Values,
+    StatsKind::Fees,
+];
+
+/// The reducers selected by `--stats` (and the `TOTALS`), created anew for every chunk.
+pub struct Reducers {
+    /// The selected ones first, in their order
+    reducers: Vec<Box<dyn Reducer>>,
+    selected: usize,
+    /// Spent in `add_block`, which the chunk's wall time leaves out
+    elapsed: Duration,
+}
+
+impl Reducers {
+    /// For a benchmark fetching `endpoints`, leaving out the analyses of responses it doesn't
+    /// fetch.
+    pub fn new(args: &Args, endpoints: &[&str]) -> Result<Self> {
+        let mut reducers = Vec::new();
+        for &kind in &args.stats {
+            reducers.extend(create(kind, args, endpoints)?);
+        }
+        let selected = reducers.len();
+        for kind in TOTALS.into_iter().filter(|kind| !args.stats.contains(kind)) {
+            reducers.extend(create(kind, args, endpoints)?);
+        }
+        Ok(Reducers {
+            reducers,
+            selected,
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    pub fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let t = Instant::now();
+        for reducer in &mut self.reducers {
+            reducer.add_block(block)?;
+        }
+        self.elapsed += t.elapsed();
+        Ok(())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Adds another chunk's reducers, created with the same arguments.
+    pub fn merge(&mut self, other: &Reducers) {
+        for (reducer, other) in self.reducers.iter_mut().zip(&other.reducers) {
+            reducer.merge_dyn(other.as_ref());
+        }
+    }
+
+    /// Of the reducers selected by `--stats`, for a chunk's log line.
+    pub fn selected(&self) -> Summary<'_> {
+        Summary(&self.reducers[..self.selected])
+    }
+
+    /// Of all the reducers, for the end-of-run summary.
+    pub fn all(&self) -> Summary<'_> {
+        Summary(&self.reducers)
+    }
+
+    pub fn json(&self) -> Value {
+        let mut out = Map::new();
+        for reducer in &self.reducers {
+            reducer.json(&mut out);
+        }
+        Value::Object(out)
+    }
+
+    pub fn summarize(&self, run: &str, decode: Duration) {
+        for reducer in &self.reducers {
+            reducer.summarize(run, decode);
+        }
+    }
+}
+
+/// `None` if the benchmark doesn't fetch the responses `kind` reads.
+fn create(kind: StatsKind, args: &Args, endpoints: &[&str]) -> Result<Option<Box<dyn Reducer>>> {
+    Ok(Some(match kind {
+        StatsKind::Counts => Box::new(Counts {
+            scripthash: args.scripthash,
+            txid: args.txid || args.verify_merkle,
+            ..Counts::default()
+        }),
+        StatsKind::Latency => Box::new(Latency::default()),
+        StatsKind::Ttfb => Box::new(Ttfb {
+            streaming: args.streaming,
+            ttfb: Durations::default(),
+            transfer: Durations::default(),
+        }),
+        StatsKind::Sizes => Box::new(Sizes::default()),
+        StatsKind::Scripts => Box::new(Scripts::default()),
+        StatsKind::Values => Box::new(Values {
+            coin_days: args.coin_days,
+            ..Values::default()
+        }),
+        StatsKind::Ages => return reading(kind, args, ages::Ages::new(endpoints)),
+        StatsKind::Fees => Box::new(Fees::default()),
+        StatsKind::Sigops => return reading(kind, args, sigops::Sigops::new(endpoints)),
+        StatsKind::Weight => return reading(kind, args, weight::Weights::new(endpoints)),
+        StatsKind::Shape => return reading(kind, args, shape::Shape::new(endpoints)),
+        StatsKind::Versions => return reading(kind, args, versions::Versions::new(endpoints)),
+        StatsKind::Sequences => return reading(kind, args, sequences::Sequences::new(endpoints)),
+        StatsKind::Locktimes => return reading(kind, args, locktimes::Locktimes::new(endpoints)),
+        StatsKind::Spends => return reading(kind, args, spends::Spends::new(args, endpoints)),
+        StatsKind::Taproot => return reading(kind, args, taproot::Taproot::new(endpoints)),
+        StatsKind::Inscriptions => {
+            return reading(kind, args, inscriptions::Inscriptions::new(endpoints))
+        }
+    }))
+}
+
+/// Boxes `reducer`, which reads the responses that streaming doesn't keep.
+fn reading<R: Reducer + 'static>(
+    kind: StatsKind,
+    args: &Args,
+    reducer: Option<R>,
+) -> Result<Option<Box<dyn Reducer>>> {
+    if reducer.is_some() && args.streaming {
+        let name = kind.to_possible_value().expect("not skipped");
+        return Err(format!("--stats {} can't be used with --streaming", name.get_name()).into());
+    }
+    Ok(reducer.map(|reducer| Box::new(reducer) as Box<dyn Reducer>))
+}
+
+/// Formats like `Stats { count: ..., sizes: ... }`, with the reducers' fields.
+pub struct Summary<'a>(&'a [Box<dyn Reducer>]);
+
+impl fmt::Debug for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = f.debug_struct("Stats");
+        for reducer in self.0 {
+            reducer.report(&mut out);
+        }
+        out.finish()
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    /// Reporting scripthashes (`--scripthash`), and txids (`--txid` or `--verify-merkle`)
+    scripthash: bool,
+    txid: bool,
+    blocks: u64,
+    count: u64,
+    hashes: u64,
+    txids: u64,
+    txid_time: Duration,
+    pubkeys: u64,
+    pubkey_time: Duration,
+    merkle_roots: u64,
+    bad_merkle_roots: u64,
+    commitments: u64,
+    bad_commitments: u64,
+    verify_time: Duration,
+    mismatches: u64,
+    trailing: u64,
+    short_reads: u64,
+}
+
+impl Reducer for Counts {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let c = block.counters;
+        self.blocks += 1;
+        self.count += c.count;
+        self.hashes += c.hashes;
+        self.txids += c.txids;
+        self.txid_time += c.txid_time;
+        self.pubkeys += c.pubkeys;
+        self.pubkey_time += c.pubkey_time;
+        self.merkle_roots += c.merkle_roots;
+        self.bad_merkle_roots += c.bad_merkle_roots;
+        self.commitments += c.commitments;
+        self.bad_commitments += c.bad_commitments;
+        self.verify_time += c.verify_time;
+        self.mismatches += c.mismatches;
+        self.trailing += c.trailing;
+        self.short_reads += c.short_reads;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.blocks += other.blocks;
+        self.count += other.count;
+        self.hashes += other.hashes;
+        self.txids += other.txids;
+        self.txid_time += other.txid_time;
+        self.pubkeys += other.pubkeys;
+        self.pubkey_time += other.pubkey_time;
+        self.merkle_roots += other.merkle_roots;
+        self.bad_merkle_roots += other.bad_merkle_roots;
+        self.commitments += other.commitments;
+        self.bad_commitments += other.bad_commitments;
+        self.verify_time += other.verify_time;
+        self.mismatches += other.mismatches;
+        self.trailing += other.trailing;
+        self.short_reads += other.short_reads;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("count", &self.count);
+        if self.scripthash {
+            out.field("hashes", &self.hashes);
+        }
+        if self.txid {
+            out.field("txids", &self.txids);
+        }
+        if self.mismatches > 0 {
+            out.field("mismatches", &self.mismatches);
+        }
+        if self.trailing > 0 {
+            out.field("trailing", &self.trailing);
+        }
+        if self.short_reads > 0 {
+            out.field("short_reads", &self.short_reads);
+        }
+        if self.bad_merkle_roots > 0 {
+            out.field("bad_merkle_roots", &self.bad_merkle_roots);
+        }
+        if self.bad_commitments > 0 {
+            out.field("bad_commitments", &self.bad_commitments);
+        }
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        out.extend([
+            ("count".into(), json!(self.count)),
+            ("hashes".into(), json!(self.hashes)),
+            ("txids".into(), json!(self.txids)),
+            ("txid_time".into(), json!(self.txid_time.as_secs_f64())),
+            ("pubkeys".into(), json!(self.pubkeys)),
+            ("pubkey_time".into(), json!(self.pubkey_time.as_secs_f64())),
+            ("merkle_roots".into(), json!(self.merkle_roots)),
+            ("bad_merkle_roots".into(), json!(self.bad_merkle_roots)),
+            ("commitments".into(), json!(self.commitments)),
+            ("bad_commitments".into(), json!(self.bad_commitments)),
+            ("verify_time".into(), json!(self.verify_time.as_secs_f64())),
+            ("mismatches".into(), json!(self.mismatches)),
+            ("trailing".into(), json!(self.trailing)),
+            ("short_reads".into(), json!(self.short_reads)),
+        ]);
+    }
+
+    fn summarize(&self, run: &str, decode: Duration) {
+        if self.txids > 0 {
+            let hashing = self.txid_time.as_secs_f64();
+            tracing::info!(
+                "summary: {}: {} txids hashed in {:.3}[s], {:.0}[txid/s]",
+                run,
+                self.txids,
+                hashing,
+                self.txids as f64 / hashing.max(f64::MIN_POSITIVE)
+            );
+        }
+        if self.pubkeys > 0 {
+            let decompressing = self.pubkey_time.as_secs_f64();
+            tracing::info!(
+                "summary: {}: {} public keys decompressed in {:.3}[s], {:.0}[ns/key], {:.1}% of \
+                 decoding",
+                run,
+                self.pubkeys,
+                decompressing,
+                decompressing * 1e9 / self.pubkeys as f64,
+                100.0 * decompressing / decode.as_secs_f64().max(f64::MIN_POSITIVE)
+            );
+        }
+        if self.merkle_roots > 0 {
+            // Including the txids, which plain decoding doesn't need
+            let verifying = (self.verify_time + self.txid_time).as_secs_f64();
+            let decoding = decode.as_secs_f64() - verifying;
+            tracing::info!(
+                "summary: {}: {} merkle roots ({} mismatching) and {} witness commitments \
+                 ({} mismatching) verified in {:.3}[s], {:.0}[blocks/s]; decoding alone {:.3}[s], \
+                 {:.0}[blocks/s]",
+                run,
+                self.merkle_roots,
+                self.bad_merkle_roots,
+                self.commitments,
+                self.bad_commitments,
+                verifying,
+                self.merkle_roots as f64 / verifying.max(f64::MIN_POSITIVE),
+                decoding,
+                self.blocks as f64 / decoding.max(f64::MIN_POSITIVE)
+            );
+        }
+    }
+}
+
+/// Min/avg/max of some per-block value.
+#[derive(Default)]
+struct MinAvgMax {
+    count: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl MinAvgMax {
+    fn add(&mut self, value: u64) {
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.total += value;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &MinAvgMax) {
+        if other.count == 0 {
+            return;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.count += other.count;
+    }
+}
+
+impl fmt::Debug for MinAvgMax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let avg = self.total / self.count.max(1);
+        write!(f, "{}/{}/{}", self.min, avg, self.max)
+    }
+}
+
+#[derive(Default)]
+struct Latency(MinAvgMax);
+
+impl Reducer for Latency {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let record = block.record;
+        let elapsed = record.fetch + record.inflate + record.decode;
+        self.0.add(elapsed.as_micros() as u64);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("latency", &self.0);
+    }
+}
+
+/// The buckets of a `Histogram`: their upper bounds (exclusive) and labels, the last one being
+/// unbounded.
+pub trait Buckets<const N: usize> {
+    const BUCKETS: [(u64, &'static str); N];
+}
+
+/// Counts of some value in each of `B`'s buckets.
+pub struct Histogram<B, const N: usize> {
+    counts: [u64; N],
+    buckets: PhantomData<B>,
+}
+
+impl<B: Buckets<N>, const N: usize> Histogram<B, N> {
+    pub fn add(&mut self, value: u64) {
+        self.add_below(|bound| value < bound);
+    }
+
+    /// Counts a value in the first bucket whose bound it's `below` (e.g. a ratio, compared
+    /// without rounding it), or else the last.
+    pub fn add_below(&mut self, below: impl Fn(u64) -> bool) {
+        let bucket = B::BUCKETS
+            .iter()
+            .position(|&(bound, _)| below(bound))
+            .unwrap_or(N - 1);
+        self.counts[bucket] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (a, b) in self.counts.iter_mut().zip(other.counts) {
+            *a += b;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&n| n == 0)
+    }
+
+    /// The buckets' labels and counts.
+    fn entries(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        let labels = B::BUCKETS.iter().map(|&(_, label)| label);
+        labels.zip(self.counts.iter().copied())
+    }
+
+    pub fn json(&self) -> Value {
+        let entries = self
+            .entries()
+            .map(|(label, n)| (label.to_string(), json!(n)));
+        Value::Object(entries.collect())
+    }
+}
+
+impl<B, const N: usize> Default for Histogram<B, N> {
+    fn default() -> Self {
+        Histogram {
+            counts: [0; N],
+            buckets: PhantomData,
+        }
+    }
+}
+
+impl<B: Buckets<N>, const N: usize> fmt::Debug for Histogram<B, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.entries()).finish()
+    }
+}
+
+/// Durations in microseconds.
+struct DurationBuckets;
+
+impl Buckets<11> for DurationBuckets {
+    const BUCKETS: [(u64, &'static str); 11] = [
+        (100, "<100us"),
+        (300, "<300us"),
+        (1_000, "<1ms"),
+        (3_000, "<3ms"),
+        (10_000, "<10ms"),
+        (30_000, "<30ms"),
+        (100_000, "<100ms"),
+        (300_000, "<300ms"),
+        (1_000_000, "<1s"),
+        (3_000_000, "<3s"),
+        (u64::MAX, ">=3s"),
+    ];
+}
+
+/// Histogram of some per-block duration.
+#[derive(Default)]
+struct Durations(Histogram<DurationBuckets, 11>);
+
+impl Durations {
+    fn add(&mut self, duration: Duration) {
+        self.0.add(duration.as_micros() as u64);
+    }
+
+    fn merge(&mut self, other: &Durations) {
+        self.0.merge(&other.0);
+    }
+}
+
+impl fmt::Debug for Durations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = self.0.entries().filter(|&(_, n)| n > 0);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+/// Separates the node's lookup (and disk seek) until the headers are sent from the body transfer.
+struct Ttfb {
+    /// The body is received while decoding, so its transfer isn't timed separately
+    streaming: bool,
+    ttfb: Durations,
+    transfer: Durations,
+}
+
+impl Reducer for Ttfb {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let record = block.record;
+        self.ttfb.add(record.ttfb);
+        self.transfer.add(record.fetch - record.ttfb);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.ttfb.merge(&other.ttfb);
+        self.transfer.merge(&other.transfer);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("ttfb", &self.ttfb);
+        if !self.streaming {
+            out.field("transfer", &self.transfer);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sizes(MinAvgMax);
+
+impl Reducer for Sizes {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        self.0.add(block.record.bytes as u64);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("sizes", &self.0);
+    }
+}
+
+#[derive(Default)]
+struct Scripts {
+    count_by_type: [u64; 7],
+    scripts: u64,
+}
+
+impl Reducer for Scripts {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let c = block.counters;
+        for (a, b) in self.count_by_type.iter_mut().zip(c.count_by_type) {
+            *a += b;
+        }
+        self.scripts += c.scripts;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (a, b) in self.count_by_type.iter_mut().zip(other.count_by_type) {
+            *a += b;
+        }
+        self.scripts += other.scripts;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("count_by_type", &self.count_by_type);
+        out.field("scripts", &self.scripts);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        out.insert("count_by_type".into(), json!(self.count_by_type));
+        out.insert("scripts".into(), json!(self.scripts));
+    }
+}
+
+#[derive(Default)]
+struct Values {
+    /// Reporting coin-days destroyed (`--coin-days`)
+    coin_days: bool,
+    spent: u128,
+    coin_days_destroyed: CoinDays,
+}
+
+impl Reducer for Values {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        self.spent += block.counters.spent;
+        self.coin_days_destroyed.0 += block.counters.coin_days.0;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.spent += other.spent;
+        self.coin_days_destroyed.0 += other.coin_days_destroyed.0;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("spent", &self.spent);
+        if self.coin_days {
+            out.field("coin_days", &self.coin_days_destroyed);
+        }
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        // As a string, since JSON numbers may lose precision above 2^53
+        out.insert("spent".into(), json!(self.spent.to_string()));
+        let coin_days = self.coin_days_destroyed.0 as f64 / SAT_BLOCKS_PER_COIN_DAY as f64;
+        out.insert("coin_days".into(), json!(coin_days));
+    }
+}
+
+/// Skipped for benchmarks that don't compute fees.
+#[derive(Default)]
+struct Fees(FeeRates);
+
+impl Reducer for Fees {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        self.0.merge(&block.counters.fee_rates);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        if !self.0.is_empty() {
+            out.field("fee_rates", &self.0);
+        }
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        out.insert("fee_rates".into(), self.0.json());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use bitcoin::{hashes::Hash, BlockHash};
+    use clap::{Parser, ValueEnum};
+
+    use super::{BlockView, Reducers, StatsKind};
+    use crate::{block_decode, Args, BlockRecord, Counters, Scratch};
+
+    const ENDPOINTS: [&str; 3] = ["block", "blockundo", "spenttxouts"];
+
+    /// The responses of the recorded regtest blocks, in `ENDPOINTS` order.
+    fn fixture() -> Vec<Vec<Vec<u8>>> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/regtest");
+        let mut names: Vec<_> = fs::read_dir(dir.join("block"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        let read = |name| ENDPOINTS.map(|e| fs::read(dir.join(e).join(name)).unwrap());
+        names.iter().map(|name| read(name).to_vec()).collect()
+    }
+
+    #[test]
+    fn merges_like_one_chunk() {
+        let stats: Vec<_> = StatsKind::value_variants()
+            .iter()
+            .map(|kind| kind.to_possible_value().unwrap().get_name().to_owned())
+            .collect();
+        let stats = stats.join(",");
+        let args = ["bench", "--stats", &stats, "--coin-days", "--scripthash"];
+        let args = Args::try_parse_from(args).unwrap();
+        let blocks = fixture();
+        assert!(blocks.len() > 10);
+        let add = |reducers: &mut Reducers, height: usize, responses: &[Vec<u8>]| {
+            let mut counters = Counters::default();
+            let mut scratch = Scratch::new(&args);
+            block_decode(&responses[0], &mut counters, &mut scratch).unwrap();
+            let micros = Duration::from_micros(97 * height as u64 % 5000);
+            let record = BlockRecord {
+                height,
+                hash: BlockHash::all_zeros(),
+                bytes: responses[0].len(),
+                received: responses[0].len(),
+                fetch: micros * 3,
+                ttfb: micros * 2,
+                connect: micros,
+                inflate: Duration::ZERO,
+                decode: micros,
+                allocs: Default::default(),
+                stats: Default::default(),
+            };
+            let responses: Vec<&[u8]> = responses.iter().map(Vec::as_slice).collect();
+            let view = BlockView::new(&record, &counters, &ENDPOINTS, &responses);
+            reducers.add_block(&view).unwrap();
+        };
+        let mut whole = Reducers::new(&args, &ENDPOINTS).unwrap();
+        let mut merged = Reducers::new(&args, &ENDPOINTS).unwrap();
+        for (c, chunk) in blocks.chunks(7).enumerate() {
+            let mut reducers = Reducers::new(&args, &ENDPOINTS).unwrap();
+            for (i, responses) in chunk.iter().enumerate() {
+                let height = 1000 + 7 * c + i;
+                add(&mut reducers, height, responses);
+                add(&mut whole, height, responses);
+            }
+            merged.merge(&reducers);
+        }
+        assert_eq!(merged.json(), whole.json());
+        assert_eq!(format!("{:?}", merged.all()), format!("{:?}", whole.all()));
+        assert_eq!(
+            format!("{:?}", merged.selected()),
+            format!("{:?}", whole.selected())
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! `--stats ages`: a histogram of spent-coin ages (in blocks), and the spent coinbase outputs,
+//! from undo data.
+
+use std::fmt::DebugStruct;
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Buckets, Histogram, Reducer};
+use crate::{undo, Result};
+
+/// Ages in blocks.
+struct AgeBuckets;
+
+impl Buckets<6> for AgeBuckets {
+    const BUCKETS: [(u64, &'static str); 6] = [
+        (6, "<1h"),
+        (144, "<1d"),
+        (1008, "<1w"),
+        (4320, "<1m"),
+        (52560, "<1y"),
+        (u64::MAX, ">=1y"),
+    ];
+}
+
+#[derive(Default)]
+pub struct Ages {
+    coinbase_spends: u64,
+    ages: Histogram<AgeBuckets, 6>,
+}
+
+impl Ages {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"blockundo").then(Ages::default)
+    }
+}
+
+impl Reducer for Ages {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let height = block.record.height;
+        undo::visit(block.response("blockundo")?, |coin| {
+            self.ages
+                .add(height.saturating_sub(coin.height as usize) as u64);
+            self.coinbase_spends += coin.coinbase as u64;
+        })?;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.ages.merge(&other.ages);
+        self.coinbase_spends += other.coinbase_spends;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("coinbase_spends", &self.coinbase_spends);
+        out.field("ages", &self.ages);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        out.insert("coinbase_spends".into(), json!(self.coinbase_spends));
+        out.insert("ages".into(), self.ages.json());
+    }
+}
@@ -0,0 +1,85 @@
+//! `--stats inscriptions`: envelopes (`OP_FALSE OP_IF ... OP_ENDIF`, as ordinals inscriptions use)
+//! in the tapscripts of taproot script path spends, from blocks and the outputs they spend.
+
+use std::fmt::DebugStruct;
+
+use bitcoin::{
+    opcodes::all::{OP_ENDIF, OP_IF},
+    script::Instruction,
+    taproot::LeafVersion,
+    Script,
+};
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer};
+use crate::{script_type::ScriptType, Result};
+
+#[derive(Debug, Default)]
+pub struct Inscriptions {
+    envelopes: u64,
+    /// Pushed within the envelopes
+    payload: u64,
+}
+
+impl Inscriptions {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        let spent = ["blockundo", "spenttxouts"];
+        let spent = spent.iter().any(|e| endpoints.contains(e));
+        (endpoints.contains(&"block") && spent).then(Inscriptions::default)
+    }
+
+    fn scan(&mut self, tapscript: &Script) {
+        // Of the envelope being scanned, and whether the last instruction was `OP_FALSE`
+        let mut payload = None;
+        let mut op_false = false;
+        for instruction in tapscript.instructions() {
+            let Ok(instruction) = instruction else {
+                break;
+            };
+            match (instruction, &mut payload) {
+                (Instruction::Op(OP_IF), None) if op_false => payload = Some(0),
+                (Instruction::Op(OP_ENDIF), Some(bytes)) => {
+                    self.envelopes += 1;
+                    self.payload += *bytes;
+                    payload = None;
+                }
+                (Instruction::PushBytes(push), Some(bytes)) => *bytes += push.len() as u64,
+                _ => {}
+            }
+            op_false = matches!(instruction, Instruction::PushBytes(push) if push.is_empty());
+        }
+    }
+}
+
+impl Reducer for Inscriptions {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        for (txin, script_type) in block.spent_txins()? {
+            if script_type != ScriptType::P2tr {
+                continue;
+            }
+            // Other leaf versions aren't tapscripts (BIP 342)
+            let leaf = txin.witness.taproot_leaf_script();
+            if let Some(leaf) = leaf.filter(|leaf| leaf.version == LeafVersion::TapScript) {
+                self.scan(leaf.script);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.envelopes += other.envelopes;
+        self.payload += other.payload;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("inscriptions", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let inscriptions = json!({
+            "envelopes": self.envelopes,
+            "payload": self.payload,
+        });
+        out.insert("inscriptions".into(), inscriptions);
+    }
+}
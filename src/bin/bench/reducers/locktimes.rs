@@ -0,0 +1,56 @@
+//! `--stats locktimes`: transaction locktimes, unset or a block height or a timestamp.
+
+use std::fmt::DebugStruct;
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer};
+use crate::Result;
+
+/// Locktimes below are block heights, others timestamps.
+const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+#[derive(Debug, Default)]
+pub struct Locktimes {
+    zero: u64,
+    height: u64,
+    time: u64,
+}
+
+impl Locktimes {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"block").then(Locktimes::default)
+    }
+}
+
+impl Reducer for Locktimes {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        for tx in &block.fields()?.txs {
+            match tx.lock_time {
+                0 => self.zero += 1,
+                1..LOCKTIME_THRESHOLD => self.height += 1,
+                _ => self.time += 1,
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.zero += other.zero;
+        self.height += other.height;
+        self.time += other.time;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("locktimes", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let locktimes = json!({
+            "zero": self.zero,
+            "height": self.height,
+            "time": self.time,
+        });
+        out.insert("locktimes".into(), locktimes);
+    }
+}
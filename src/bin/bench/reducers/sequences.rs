@@ -0,0 +1,87 @@
+//! `--stats sequences`: input sequence numbers of non-coinbase transactions, their
+//! replaceability signals (BIP 125) and relative locktimes (BIP 68).
+
+use std::fmt::DebugStruct;
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockFields, BlockView, Reducer, TxFields};
+use crate::Result;
+
+/// BIP 68's flags, and the largest sequence number not signalling replaceability (BIP 125).
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+const MAX_BIP125_RBF_SEQUENCE: u32 = 0xffff_fffd;
+
+#[derive(Debug, Default)]
+pub struct Sequences {
+    inputs: u64,
+    /// Inputs signalling replaceability, and transactions with any such input
+    rbf: u64,
+    rbf_txs: u64,
+    /// Inputs with a relative locktime, in blocks or in units of 512 seconds
+    relative_height: u64,
+    relative_time: u64,
+}
+
+impl Sequences {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"block").then(Sequences::default)
+    }
+
+    fn add_tx(&mut self, tx: &TxFields, fields: &BlockFields) {
+        let mut rbf = false;
+        for sequence in fields.inputs[tx.inputs.clone()]
+            .iter()
+            .map(|input| input.sequence)
+        {
+            self.inputs += 1;
+            if sequence <= MAX_BIP125_RBF_SEQUENCE {
+                self.rbf += 1;
+                rbf = true;
+            }
+            // Only enforced from version 2
+            if tx.version >= 2 && sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 {
+                if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG == 0 {
+                    self.relative_height += 1;
+                } else {
+                    self.relative_time += 1;
+                }
+            }
+        }
+        self.rbf_txs += rbf as u64;
+    }
+}
+
+impl Reducer for Sequences {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let fields = block.fields()?;
+        for tx in fields.txs.iter().skip(1) {
+            self.add_tx(tx, fields);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.inputs += other.inputs;
+        self.rbf += other.rbf;
+        self.rbf_txs += other.rbf_txs;
+        self.relative_height += other.relative_height;
+        self.relative_time += other.relative_time;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("sequences", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let sequences = json!({
+            "inputs": self.inputs,
+            "rbf": self.rbf,
+            "rbf_txs": self.rbf_txs,
+            "relative_height": self.relative_height,
+            "relative_time": self.relative_time,
+        });
+        out.insert("sequences".into(), sequences);
+    }
+}
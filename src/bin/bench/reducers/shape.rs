@@ -0,0 +1,69 @@
+//! `--stats shape`: histograms of inputs and outputs per transaction, and of transactions per
+//! block.
+
+use std::fmt::DebugStruct;
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Buckets, Histogram, Reducer};
+use crate::Result;
+
+/// Counts (e.g. of a transaction's inputs).
+struct CountBuckets;
+
+impl Buckets<8> for CountBuckets {
+    const BUCKETS: [(u64, &'static str); 8] = [
+        (2, "1"),
+        (3, "2"),
+        (5, "<5"),
+        (10, "<10"),
+        (100, "<100"),
+        (1000, "<1000"),
+        (10000, "<10000"),
+        (u64::MAX, ">=10000"),
+    ];
+}
+
+#[derive(Debug, Default)]
+pub struct Shape {
+    inputs: Histogram<CountBuckets, 8>,
+    outputs: Histogram<CountBuckets, 8>,
+    txs: Histogram<CountBuckets, 8>,
+}
+
+impl Shape {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"block").then(Shape::default)
+    }
+}
+
+impl Reducer for Shape {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let txdata = &block.block()?.txdata;
+        self.txs.add(txdata.len() as u64);
+        for tx in txdata {
+            self.inputs.add(tx.input.len() as u64);
+            self.outputs.add(tx.output.len() as u64);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.inputs.merge(&other.inputs);
+        self.outputs.merge(&other.outputs);
+        self.txs.merge(&other.txs);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("shape", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let shape = json!({
+            "inputs": self.inputs.json(),
+            "outputs": self.outputs.json(),
+            "txs": self.txs.json(),
+        });
+        out.insert("shape".into(), shape);
+    }
+}
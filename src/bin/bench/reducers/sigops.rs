@@ -0,0 +1,171 @@
+//! `--stats sigops`: signature operations, counted like Bitcoin Core's legacy, P2SH and witness
+//! sigops, from blocks. P2SH and witness sigops depend on the spent outputs: without them, P2SH
+//! redeem scripts aren't counted and witness sigops are estimated from the witnesses alone.
+
+use std::fmt::{self, DebugStruct};
+
+use bitcoin::{opcodes::all::OP_PUSHNUM_16, script::Instruction, Script, TxIn};
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer, WITNESS_SCALE_FACTOR};
+use crate::{script_type::ScriptType, Result};
+
+#[derive(Default)]
+pub struct Sigops {
+    /// Whether the spent outputs are fetched, or else P2SH and witness sigops are estimated
+    exact: bool,
+    legacy: u64,
+    p2sh: u64,
+    witness: u64,
+    /// Of a single block, in BIP 141 units (limited to 80000 by consensus)
+    max_cost: u64,
+}
+
+impl Sigops {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        let spent = ["blockundo", "spenttxouts"];
+        endpoints.contains(&"block").then(|| Sigops {
+            exact: spent.iter().any(|e| endpoints.contains(e)),
+            ..Sigops::default()
+        })
+    }
+
+    /// The names of the witness sigops and the largest cost, estimated or not.
+    fn names(&self) -> (&'static str, &'static str) {
+        if self.exact {
+            ("witness", "max_cost")
+        } else {
+            ("witness_estimate", "max_cost_estimate")
+        }
+    }
+}
+
+impl fmt::Debug for Sigops {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (witness, max_cost) = self.names();
+        let mut f = f.debug_struct("Sigops");
+        f.field("legacy", &self.legacy);
+        if self.exact {
+            f.field("p2sh", &self.p2sh);
+        }
+        f.field(witness, &self.witness)
+            .field(max_cost, &self.max_cost)
+            .finish()
+    }
+}
+
+/// P2SH and witness sigops of an input spending an output of `script_type`, like Bitcoin Core's
+/// `GetP2SHSigOpCount` and `CountWitnessSigOps`: a P2SH redeem script's, and those of the v0
+/// witness program it spends (natively or wrapped in P2SH), 1 for P2WPKH and its witness
+/// script's for P2WSH.
+fn spent_sigops(txin: &TxIn, script_type: ScriptType) -> (u64, u64) {
+    let (program, p2sh) = match script_type {
+        ScriptType::P2sh => match redeem_script(&txin.script_sig) {
+            Some(redeem) => (ScriptType::of(redeem), redeem.count_sigops() as u64),
+            None => return (0, 0),
+        },
+        _ => (script_type, 0),
+    };
+    let witness = match program {
+        ScriptType::P2wpkh => 1,
+        ScriptType::P2wsh => txin.witness.last().map_or(0, |witness_script| {
+            Script::from_bytes(witness_script).count_sigops() as u64
+        }),
+        _ => 0,
+    };
+    (p2sh, witness)
+}
+
+/// The redeem script of a P2SH spend, its scriptSig's last push, if that's push-only.
+fn redeem_script(script_sig: &Script) -> Option<&Script> {
+    let mut redeem = None;
+    for instruction in script_sig.instructions() {
+        redeem = match instruction.ok()? {
+            Instruction::PushBytes(push) => Some(push.as_bytes()),
+            Instruction::Op(op) if op.to_u8() <= OP_PUSHNUM_16.to_u8() => Some(&[][..]),
+            Instruction::Op(_) => return None,
+        };
+    }
+    redeem.map(Script::from_bytes)
+}
+
+/// Sigops of an input's witness, whose last of `items` elements is `last`. Without the spent
+/// output, its program is inferred from the witness: P2WPKH counts 1, P2WSH its witness script's
+/// and taproot none.
+fn witness_sigops(items: usize, last: &[u8]) -> u64 {
+    match (items, last.first()) {
+        (0, _) => 0,
+        // Signature and compressed public key
+        (2, Some(2 | 3)) if last.len() == 33 => 1,
+        // Key path spend
+        (1, _) if matches!(last.len(), 64 | 65) => 0,
+        // Annex, or script path spend
+        (2.., Some(0x50)) => 0,
+        (2.., _) if is_control_block(last) => 0,
+        _ => Script::from_bytes(last).count_sigops() as u64,
+    }
+}
+
+/// Whether a witness element looks like a taproot control block (BIP 341).
+fn is_control_block(element: &[u8]) -> bool {
+    element.len() >= 33 && (element.len() - 33).is_multiple_of(32) && element[0] & 0xfe == 0xc0
+}
+
+impl Reducer for Sigops {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let (mut legacy, mut p2sh, mut witness) = (0, 0, 0);
+        let txdata = &block.block()?.txdata;
+        for tx in txdata {
+            for txin in &tx.input {
+                legacy += txin.script_sig.count_sigops_legacy() as u64;
+            }
+            for txout in &tx.output {
+                legacy += txout.script_pubkey.count_sigops_legacy() as u64;
+            }
+        }
+        if self.exact {
+            for (txin, script_type) in block.spent_txins()? {
+                let (n, m) = spent_sigops(txin, script_type);
+                p2sh += n;
+                witness += m;
+            }
+        } else {
+            // The coinbase's witness is its commitment's reserved value
+            for txin in txdata.iter().skip(1).flat_map(|tx| &tx.input) {
+                if let Some(last) = txin.witness.last() {
+                    witness += witness_sigops(txin.witness.len(), last);
+                }
+            }
+        }
+        self.legacy += legacy;
+        self.p2sh += p2sh;
+        self.witness += witness;
+        let cost = (legacy + p2sh) * WITNESS_SCALE_FACTOR + witness;
+        self.max_cost = self.max_cost.max(cost);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.legacy += other.legacy;
+        self.p2sh += other.p2sh;
+        self.witness += other.witness;
+        self.max_cost = self.max_cost.max(other.max_cost);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("sigops", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let (witness, max_cost) = self.names();
+        let mut sigops = json!({
+            "legacy": self.legacy,
+            witness: self.witness,
+            max_cost: self.max_cost,
+        });
+        if self.exact {
+            sigops["p2sh"] = json!(self.p2sh);
+        }
+        out.insert("sigops".into(), sigops);
+    }
+}
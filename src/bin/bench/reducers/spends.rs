@@ -0,0 +1,116 @@
+//! `--stats spends`: spent coins by the kind of output they were, legacy, P2SH or native segwit
+//! (by version), from undo data or spent outputs. With the block, P2SH spends wrapping segwit v0
+//! are told apart from the others (without it, they're counted as P2SH).
+
+use std::fmt::{self, DebugStruct};
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, InputFields, Reducer};
+use crate::{
+    script_type::{ScriptFilter, ScriptType},
+    Args, Result,
+};
+
+pub struct Spends {
+    /// Only the selected coins are counted (`--filter-script-type`)
+    script_filter: ScriptFilter,
+    /// Whether the block is fetched, whose scriptSigs and witnesses tell wrapped segwit apart
+    block: bool,
+    legacy: u64,
+    p2sh: u64,
+    /// P2SH spends with a witness, whose scriptSig is a single push of a v0 witness program
+    p2sh_v0: u64,
+    v0: u64,
+    v1: u64,
+    /// Other witness versions (and P2A)
+    unknown: u64,
+}
+
+impl Spends {
+    pub fn new(args: &Args, endpoints: &[&str]) -> Option<Self> {
+        let spent = ["blockundo", "spenttxouts"];
+        spent.iter().any(|e| endpoints.contains(e)).then(|| Spends {
+            script_filter: ScriptFilter(args.filter_script_type.clone()),
+            block: endpoints.contains(&"block"),
+            legacy: 0,
+            p2sh: 0,
+            p2sh_v0: 0,
+            v0: 0,
+            v1: 0,
+            unknown: 0,
+        })
+    }
+
+    fn add_coin(&mut self, script_type: ScriptType, input: Option<&InputFields>) {
+        if !self.script_filter.selects_type(script_type) {
+            return;
+        }
+        let wraps_v0 = input.is_some_and(|input| input.pushes_v0_program && input.witness_len > 0);
+        let n = match script_type {
+            ScriptType::P2sh if wraps_v0 => &mut self.p2sh_v0,
+            ScriptType::P2sh => &mut self.p2sh,
+            ScriptType::P2wpkh | ScriptType::P2wsh => &mut self.v0,
+            ScriptType::P2tr => &mut self.v1,
+            ScriptType::WitnessUnknown => &mut self.unknown,
+            _ => &mut self.legacy,
+        };
+        *n += 1;
+    }
+}
+
+impl fmt::Debug for Spends {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut f = f.debug_struct("Spends");
+        f.field("legacy", &self.legacy).field("p2sh", &self.p2sh);
+        if self.block {
+            f.field("p2sh_v0", &self.p2sh_v0);
+        }
+        f.field("v0", &self.v0)
+            .field("v1", &self.v1)
+            .field("unknown", &self.unknown)
+            .finish()
+    }
+}
+
+impl Reducer for Spends {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        if self.block {
+            for (input, script_type) in block.spent_inputs()? {
+                self.add_coin(script_type, Some(input));
+            }
+        } else {
+            for &script_type in block.spent_types()?.iter().flatten() {
+                self.add_coin(script_type, None);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.legacy += other.legacy;
+        self.p2sh += other.p2sh;
+        self.p2sh_v0 += other.p2sh_v0;
+        self.v0 += other.v0;
+        self.v1 += other.v1;
+        self.unknown += other.unknown;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("spends", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let mut spends = json!({
+            "legacy": self.legacy,
+            "p2sh": self.p2sh,
+            "v0": self.v0,
+            "v1": self.v1,
+            "unknown": self.unknown,
+        });
+        if self.block {
+            spends["p2sh_v0"] = json!(self.p2sh_v0);
+        }
+        out.insert("spends".into(), spends);
+    }
+}
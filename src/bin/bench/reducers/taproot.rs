@@ -0,0 +1,62 @@
+//! `--stats taproot`: key path and script path spends of taproot outputs (BIP 341), and those
+//! with an annex, from blocks and the outputs they spend.
+
+use std::fmt::DebugStruct;
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer};
+use crate::{script_type::ScriptType, Result};
+
+#[derive(Debug, Default)]
+pub struct Taproot {
+    keypath: u64,
+    scriptpath: u64,
+    /// Of the above, those with an annex
+    annex: u64,
+}
+
+impl Taproot {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        let spent = ["blockundo", "spenttxouts"];
+        let spent = spent.iter().any(|e| endpoints.contains(e));
+        (endpoints.contains(&"block") && spent).then(Taproot::default)
+    }
+}
+
+impl Reducer for Taproot {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        for (input, script_type) in block.spent_inputs()? {
+            // Only invalid spends have no witness
+            if script_type != ScriptType::P2tr || input.witness_len == 0 {
+                continue;
+            }
+            if input.is_script_path() {
+                self.scriptpath += 1;
+            } else {
+                self.keypath += 1;
+            }
+            self.annex += input.annex as u64;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.keypath += other.keypath;
+        self.scriptpath += other.scriptpath;
+        self.annex += other.annex;
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("taproot", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let taproot = json!({
+            "keypath": self.keypath,
+            "scriptpath": self.scriptpath,
+            "annex": self.annex,
+        });
+        out.insert("taproot".into(), taproot);
+    }
+}
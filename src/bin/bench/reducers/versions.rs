@@ -0,0 +1,112 @@
+//! `--stats versions`: transaction versions, and the version bits (BIP 9) that blocks signal.
+
+use std::fmt::{self, DebugStruct};
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer};
+use crate::Result;
+
+const VERSION_BITS: usize = 29;
+
+/// BIP 9's top 3 bits, and their mask.
+const VERSION_BITS_TOP: u32 = 0x2000_0000;
+const VERSION_BITS_TOP_MASK: u32 = 0xe000_0000;
+
+#[derive(Default)]
+pub struct Versions {
+    /// Of versions 1, 2 and 3 (TRUC, BIP 431), and of any other
+    txs: [u64; 4],
+    blocks: u64,
+    /// Blocks whose version has BIP 9's top bits (`001`), and those signalling each bit
+    bip9: u64,
+    bits: [u64; VERSION_BITS],
+}
+
+impl Versions {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"block").then(Versions::default)
+    }
+
+    fn add_tx(&mut self, version: i32) {
+        let index = match version {
+            1..=3 => version as usize - 1,
+            _ => 3,
+        };
+        self.txs[index] += 1;
+    }
+
+    /// The bits signalled by any block, with their counts.
+    fn signalled(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.bits
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, n)| n > 0)
+    }
+}
+
+impl fmt::Debug for Versions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [v1, v2, v3, other] = self.txs;
+        write!(
+            f,
+            "txs v1 {} v2 {} v3 {} other {}; {} of {} blocks with version bits",
+            v1, v2, v3, other, self.bip9, self.blocks
+        )?;
+        for (bit, n) in self.signalled() {
+            write!(f, ", bit {}: {}", bit, n)?;
+        }
+        Ok(())
+    }
+}
+
+impl Reducer for Versions {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let fields = block.fields()?;
+        for tx in &fields.txs {
+            self.add_tx(tx.version);
+        }
+        let version = fields.version as u32;
+        self.blocks += 1;
+        if version & VERSION_BITS_TOP_MASK == VERSION_BITS_TOP {
+            self.bip9 += 1;
+            for (bit, n) in self.bits.iter_mut().enumerate() {
+                *n += (version >> bit & 1) as u64;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (a, b) in self.txs.iter_mut().zip(other.txs) {
+            *a += b;
+        }
+        self.blocks += other.blocks;
+        self.bip9 += other.bip9;
+        for (a, b) in self.bits.iter_mut().zip(other.bits) {
+            *a += b;
+        }
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("versions", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let bits = self.signalled();
+        let bits: Map<_, _> = bits.map(|(bit, n)| (bit.to_string(), json!(n))).collect();
+        let versions = json!({
+            "txs": {
+                "1": self.txs[0],
+                "2": self.txs[1],
+                "3": self.txs[2],
+                "other": self.txs[3],
+            },
+            "blocks": self.blocks,
+            "bip9": self.bip9,
+            "bits": bits,
+        });
+        out.insert("versions".into(), versions);
+    }
+}
@@ -0,0 +1,77 @@
+//! `--stats weight`: block weights (BIP 141), in weight units, and how full blocks are.
+
+use std::fmt::{self, DebugStruct};
+
+use serde_json::{json, Map, Value};
+
+use super::{BlockView, Reducer, WITNESS_SCALE_FACTOR};
+use crate::Result;
+
+const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+#[derive(Default)]
+pub struct Weights {
+    blocks: u64,
+    weight: u64,
+    /// Rounded up per block, like Bitcoin Core's
+    vsize: u64,
+    max: u64,
+}
+
+impl Weights {
+    pub fn new(endpoints: &[&str]) -> Option<Self> {
+        endpoints.contains(&"block").then(Weights::default)
+    }
+
+    /// Of the average block, and of the largest one, in percent of `MAX_BLOCK_WEIGHT`.
+    fn fullness(&self) -> (f64, f64) {
+        let percent = |weight: f64| 100.0 * weight / MAX_BLOCK_WEIGHT as f64;
+        (
+            percent(self.weight as f64 / self.blocks.max(1) as f64),
+            percent(self.max as f64),
+        )
+    }
+}
+
+impl fmt::Debug for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (avg, max) = self.fullness();
+        write!(
+            f,
+            "{} WU ({} vB), {:.1}% full on average (max {:.1}%)",
+            self.weight, self.vsize, avg, max
+        )
+    }
+}
+
+impl Reducer for Weights {
+    fn add_block(&mut self, block: &BlockView) -> Result<()> {
+        let weight = block.block()?.weight().to_wu();
+        self.blocks += 1;
+        self.weight += weight;
+        self.vsize += weight.div_ceil(WITNESS_SCALE_FACTOR);
+        self.max = self.max.max(weight);
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.blocks += other.blocks;
+        self.weight += other.weight;
+        self.vsize += other.vsize;
+        self.max = self.max.max(other.max);
+    }
+
+    fn report(&self, out: &mut DebugStruct) {
+        out.field("weight", self);
+    }
+
+    fn json(&self, out: &mut Map<String, Value>) {
+        let weight = json!({
+            "total": self.weight,
+            "vsize": self.vsize,
+            "max": self.max,
+            "fullness": self.fullness().0,
+        });
+        out.insert("weight".into(), weight);
+    }
+}
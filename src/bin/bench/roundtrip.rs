@@ -0,0 +1,97 @@
+//! `Roundtrip` benchmark: decodes each block with `bitcoin::consensus`, encodes it again, and
+//! checks that the bytes match the response's, timing the encoding separately.
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    consensus::{Decodable, Encodable},
+    io::Cursor,
+    Block,
+};
+
+use crate::{benchmarks::Benchmark, check_consumed, Args, Counters, Result, Scratch};
+
+/// Logged per run, so that a broken encoder doesn't flood the output.
+const MAX_REPORTED: u64 = 10;
+
+pub struct Roundtrip {
+    blocks: u64,
+    bytes: u64,
+    /// Blocks whose encoding differs from the response
+    mismatches: u64,
+    decoding: Duration,
+    encoding: Duration,
+}
+
+impl Roundtrip {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(Roundtrip {
+            blocks: 0,
+            bytes: 0,
+            mismatches: 0,
+            decoding: Duration::ZERO,
+            encoding: Duration::ZERO,
+        }))
+    }
+}
+
+impl Benchmark for Roundtrip {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let t = Instant::now();
+        let mut c = Cursor::new(responses[0]);
+        let block = Block::consensus_decode(&mut c)?;
+        self.decoding += t.elapsed();
+        let wire = &responses[0][..c.position() as usize];
+        let rest = responses[0].len() - wire.len();
+        check_consumed(rest, counters, scratch.height, "block");
+
+        let t = Instant::now();
+        scratch.bytes.clear();
+        block.consensus_encode(&mut scratch.bytes)?;
+        self.encoding += t.elapsed();
+
+        if scratch.bytes != wire {
+            self.mismatches += 1;
+            if self.mismatches <= MAX_REPORTED {
+                let offset = scratch
+                    .bytes
+                    .iter()
+                    .zip(wire)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(scratch.bytes.len().min(wire.len()));
+                tracing::warn!(
+                    "roundtrip: block {}: encoded to {} bytes instead of {}, differing at offset {}",
+                    scratch.height,
+                    scratch.bytes.len(),
+                    wire.len(),
+                    offset
+                );
+            }
+        }
+        self.blocks += 1;
+        self.bytes += wire.len() as u64;
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let mb = self.bytes as f64 / 1e6;
+        let (decoding, encoding) = (self.decoding.as_secs_f64(), self.encoding.as_secs_f64());
+        tracing::info!(
+            "roundtrip: {} blocks ({:.1} MB) decoded in {:.3}[s] ({:.1}[MB/s]), encoded in \
+             {:.3}[s] ({:.1}[MB/s]); {} differ",
+            self.blocks,
+            mb,
+            decoding,
+            mb / decoding.max(f64::MIN_POSITIVE),
+            encoding,
+            mb / encoding.max(f64::MIN_POSITIVE),
+            self.mismatches
+        );
+        Ok(())
+    }
+}
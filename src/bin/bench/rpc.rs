@@ -0,0 +1,139 @@
+//! bitcoind's JSON-RPC (`--backend rpc`, on the same port as REST), serving the benchmarks'
+//! requests (see `backend::BlockUrl`) via:
+//!
+//! - `rpc+http://host:port/block/<hash>`: `getblock <hash> 0`, as the raw block
+//! - `rpc+http://host:port/header/<hash>`: `getblockheader <hash> false`, as the raw header
+//! - `rpc+http://host:port/hashes/<height>/<count>`: a batch of `getblockhash` calls, as a JSON
+//!   array of their results
+//! - `rpc+http://host:port/call/<method>/<params>...`: any method (with integer parameters), as
+//!   its JSON result
+
+use std::io::Cursor;
+
+use bitcoin::hex::FromHex;
+use serde_json::{json, Value};
+
+use crate::{
+    http::{Response, StatusError, Transport},
+    Result,
+};
+
+/// Prefixed to the node's `http://` URL.
+pub const SCHEME: &str = "rpc+";
+
+pub struct Rpc(ureq::Agent);
+
+impl Default for Rpc {
+    fn default() -> Self {
+        let config = ureq::Agent::config_builder().http_status_as_error(false);
+        Rpc(config.build().into())
+    }
+}
+
+impl Rpc {
+    /// Posts `request` (a call, or a batch of them) to `url`, returning the reply.
+    fn post(&self, url: &str, headers: &[(&str, &str)], request: Value) -> Result<Value> {
+        let mut post = self.0.post(url);
+        // Only `--auth` applies (bitcoind doesn't compress responses)
+        for &(name, value) in headers.iter().filter(|(name, _)| *name == "Authorization") {
+            post = post.header(name, value);
+        }
+        let mut response = post.send(serde_json::to_vec(&request)?)?;
+        let status = response.status().as_u16();
+        let body = response
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_vec()?;
+        // bitcoind replies to failed calls with an HTTP error (e.g. 404 for unknown blocks)
+        if !(200..300).contains(&status) {
+            let message = match serde_json::from_slice::<Value>(&body) {
+                Ok(reply) => match reply["error"]["message"].as_str() {
+                    Some(message) => message.to_owned(),
+                    None => reply["error"].to_string(),
+                },
+                Err(_) => String::from_utf8_lossy(&body).into_owned(),
+            };
+            return Err(StatusError::new(url, status, message.as_bytes()).into());
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    fn call(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        method: &str,
+        params: Value,
+    ) -> Result<Value> {
+        let request = json!({ "jsonrpc": "1.0", "id": 0, "method": method, "params": params });
+        let mut reply = self.post(url, headers, request)?;
+        match reply["error"].take() {
+            Value::Null => Ok(reply["result"].take()),
+            error => Err(format!("{}: {}: {}", url, method, error).into()),
+        }
+    }
+
+    fn request(&self, url: &str, headers: &[(&str, &str)], path: &str) -> Result<Vec<u8>> {
+        let mut parts = path.split('/');
+        let kind = parts.next().unwrap_or_default();
+        let arg = parts.next().unwrap_or_default();
+        match kind {
+            "block" => hex(&self.call(url, headers, "getblock", json!([arg, 0]))?),
+            "header" => hex(&self.call(url, headers, "getblockheader", json!([arg, false]))?),
+            "hashes" => {
+                let start: u64 = arg.parse()?;
+                let count: u64 = parts.next().unwrap_or_default().parse()?;
+                let batch: Vec<Value> = (start..start + count)
+                    .map(|height| {
+                        json!({ "jsonrpc": "1.0", "id": height, "method": "getblockhash", "params": [height] })
+                    })
+                    .collect();
+                let mut replies = match self.post(url, headers, batch.into())? {
+                    Value::Array(replies) => replies,
+                    reply => return Err(format!("{}: unexpected reply {}", url, reply).into()),
+                };
+                replies.sort_by_key(|reply| reply["id"].as_u64());
+                Ok(serde_json::to_vec(&replies)?)
+            }
+            "call" => {
+                let params = parts
+                    .map(|p| p.parse::<u64>().map(Value::from))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                let result = self.call(url, headers, arg, params.into())?;
+                Ok(serde_json::to_vec(&result)?)
+            }
+            _ => Err(format!("unsupported RPC request {:?}", path).into()),
+        }
+    }
+}
+
+/// Decodes a JSON-RPC result (e.g. a raw block).
+pub fn hex(value: &Value) -> Result<Vec<u8>> {
+    let hex = value
+        .as_str()
+        .ok_or_else(|| format!("expected a hex string, got {}", value))?;
+    Ok(Vec::from_hex(hex)?)
+}
+
+impl Transport for Rpc {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response> {
+        let http = url
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("{}: not an RPC URL (expected {}http://...)", url, SCHEME))?;
+        // The node's URL is everything before the request's path
+        let path_start = http
+            .find("://")
+            .and_then(|i| http[i + 3..].find('/').map(|j| i + 3 + j))
+            .ok_or_else(|| format!("{}: missing request path", url))?;
+        let (node, path) = (&http[..path_start], &http[path_start + 1..]);
+        let body = self.request(node, headers, path)?;
+        Ok(Response {
+            body: Box::new(Cursor::new(body)),
+            content_encoding: None,
+            content_length: None,
+            status: 200,
+            connect: None,
+        })
+    }
+}
@@ -0,0 +1,79 @@
+//! Standard scriptPubKey types, as Bitcoin Core's `Solver` classifies them.
+
+use bitcoin::Script;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScriptType {
+    P2pk,
+    P2pkh,
+    P2sh,
+    Multisig,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// Future witness versions (and P2A)
+    WitnessUnknown,
+    /// Unspendable `OP_RETURN` data
+    OpReturn,
+    Nonstandard,
+}
+
+impl ScriptType {
+    pub fn of(script: &Script) -> Self {
+        if script.is_p2pkh() {
+            ScriptType::P2pkh
+        } else if script.is_p2sh() {
+            ScriptType::P2sh
+        } else if script.is_p2wpkh() {
+            ScriptType::P2wpkh
+        } else if script.is_p2wsh() {
+            ScriptType::P2wsh
+        } else if script.is_p2tr() {
+            ScriptType::P2tr
+        } else if script.is_witness_program() {
+            ScriptType::WitnessUnknown
+        } else if script.is_op_return() {
+            ScriptType::OpReturn
+        } else if script.is_p2pk() {
+            ScriptType::P2pk
+        } else if script.is_multisig() {
+            ScriptType::Multisig
+        } else {
+            ScriptType::Nonstandard
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScriptType::P2pk => "p2pk",
+            ScriptType::P2pkh => "p2pkh",
+            ScriptType::P2sh => "p2sh",
+            ScriptType::Multisig => "multisig",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2wsh => "p2wsh",
+            ScriptType::P2tr => "p2tr",
+            ScriptType::WitnessUnknown => "witness-unknown",
+            ScriptType::OpReturn => "op-return",
+            ScriptType::Nonstandard => "nonstandard",
+        }
+    }
+}
+
+/// The types selected by `--filter-script-type` (all of them, if none).
+#[derive(Default)]
+pub struct ScriptFilter(pub Vec<ScriptType>);
+
+impl ScriptFilter {
+    pub fn is_active(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    pub fn selects(&self, script: &Script) -> bool {
+        self.selects_type(ScriptType::of(script))
+    }
+
+    pub fn selects_type(&self, script_type: ScriptType) -> bool {
+        self.0.is_empty() || self.0.contains(&script_type)
+    }
+}
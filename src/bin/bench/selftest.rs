@@ -0,0 +1,335 @@
+//! `bench selftest`: end-to-end check of every benchmark against a throwaway regtest node.
+//!
+//! Mines a few blocks spending coins of every standard script type, then runs each registered
+//! benchmark (as a child `bench` process) and compares its logged stats with the totals
+//! computed from `getblock <hash> 3`.
+
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use base64::prelude::*;
+use serde_json::{json, Value};
+
+use crate::{benchmarks::REGISTRY, Result};
+
+/// Address types funded (and later spent) by the test wallet.
+const ADDRESS_TYPES: [&str; 4] = ["legacy", "p2sh-segwit", "bech32", "bech32m"];
+
+/// Rounds of transactions, each confirmed by its own block.
+const ROUNDS: usize = 5;
+
+/// Coinbase maturity, so that the wallet can spend its first coins.
+const MATURITY: usize = 101;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Extra flags for the runs of each benchmark (every one also runs with defaults).
+fn variants(name: &str) -> &'static [&'static [&'static str]] {
+    match name {
+        "block" => &[&["--prefetch", "4"]],
+        "block-undo" => &[&["--decoder", "slices"], &["--streaming"]],
+        "spent-txouts" => &[&["--streaming"]],
+        _ => &[],
+    }
+}
+
+/// Summed over the whole chain (excluding the genesis block).
+#[derive(Debug, Default)]
+struct Totals {
+    /// Non-coinbase inputs
+    inputs: u128,
+    /// Value of the spent outputs, in satoshis
+    spent: u128,
+    /// Total size of the spent outputs' scripts
+    spent_scripts: u128,
+    /// Total size of the created outputs' scripts
+    output_scripts: u128,
+}
+
+/// Fields of the benchmark's `Stats { .. }` log lines, summed over all chunks.
+fn expected(name: &str, totals: &Totals) -> Vec<(&'static str, u128)> {
+    match name {
+        "block" | "index-write" => vec![("scripts", totals.output_scripts)],
+        "block-undo" | "spent-txouts" => vec![
+            ("count", totals.inputs),
+            ("spent", totals.spent),
+            ("scripts", totals.spent_scripts),
+        ],
+        "verify" => vec![
+            ("count", totals.inputs),
+            ("spent", totals.spent),
+            ("scripts", totals.spent_scripts),
+            ("mismatches", 0),
+        ],
+        "fee-rates" => vec![("count", totals.inputs), ("spent", totals.spent)],
+        _ => vec![],
+    }
+}
+
+/// JSON-RPC over the node's cookie auth.
+struct Rpc {
+    agent: ureq::Agent,
+    url: String,
+    cookie: PathBuf,
+}
+
+impl Rpc {
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        self.call_at("", method, params)
+    }
+
+    fn wallet(&self, method: &str, params: Value) -> Result<Value> {
+        self.call_at("/wallet/selftest", method, params)
+    }
+
+    fn call_at(&self, path: &str, method: &str, params: Value) -> Result<Value> {
+        let cookie = std::fs::read_to_string(&self.cookie)?;
+        let request = json!({ "jsonrpc": "1.0", "id": 0, "method": method, "params": params });
+        let mut response = self
+            .agent
+            .post(format!("{}{}", self.url, path))
+            .header(
+                "Authorization",
+                format!("Basic {}", BASE64_STANDARD.encode(cookie.trim())),
+            )
+            .send(serde_json::to_vec(&request)?)?;
+        let body = response
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_vec()?;
+        let mut reply: Value = serde_json::from_slice(&body)
+            .map_err(|e| format!("{}: invalid response ({})", method, e))?;
+        if !reply["error"].is_null() {
+            return Err(format!("{}: {}", method, reply["error"]).into());
+        }
+        Ok(reply["result"].take())
+    }
+}
+
+/// A regtest bitcoind in a temporary data directory, stopped and removed on drop.
+struct Node {
+    process: Child,
+    datadir: PathBuf,
+    rpc: Rpc,
+    port: u16,
+}
+
+impl Node {
+    fn start(bitcoind: &Path) -> Result<Self> {
+        let datadir = std::env::temp_dir().join(format!("bench-selftest-{}", std::process::id()));
+        std::fs::create_dir_all(&datadir)?;
+        // Ask the OS for an unused port (racy, but fine for a local test)
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let process = Command::new(bitcoind)
+            .arg("-regtest")
+            .arg("-rest")
+            .arg("-server")
+            .arg("-listen=0")
+            .arg("-fallbackfee=0.0002")
+            .arg(format!("-datadir={}", datadir.display()))
+            .arg(format!("-rpcport={}", port))
+            .stdout(Stdio::null())
+            .spawn();
+        let process = match process {
+            Ok(process) => process,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&datadir);
+                return Err(format!("{}: {}", bitcoind.display(), e).into());
+            }
+        };
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+        let rpc = Rpc {
+            agent,
+            url: format!("http://127.0.0.1:{}", port),
+            cookie: datadir.join("regtest").join(".cookie"),
+        };
+        let mut node = Node {
+            process,
+            datadir,
+            rpc,
+            port,
+        };
+        node.wait_ready()?;
+        Ok(node)
+    }
+
+    /// Retries until RPC (and thus REST) is served, i.e. after the node's warmup.
+    fn wait_ready(&mut self) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.process.try_wait()? {
+                return Err(format!("bitcoind exited during startup ({})", status).into());
+            }
+            match self.rpc.call("getblockchaininfo", json!([])) {
+                Ok(_) => return Ok(()),
+                Err(e) if start.elapsed() > STARTUP_TIMEOUT => {
+                    return Err(format!("bitcoind didn't start: {}", e).into())
+                }
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        if self.rpc.call("stop", json!([])).is_err() {
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+        if let Err(e) = std::fs::remove_dir_all(&self.datadir) {
+            tracing::warn!("failed to remove {}: {}", self.datadir.display(), e);
+        }
+    }
+}
+
+/// Mines the test chain, and returns its tip height.
+fn mine(rpc: &Rpc) -> Result<u64> {
+    rpc.call("createwallet", json!(["selftest"]))?;
+    let addresses = ADDRESS_TYPES
+        .iter()
+        .map(|kind| rpc.wallet("getnewaddress", json!(["", kind])))
+        .collect::<Result<Vec<_>>>()?;
+    rpc.wallet("generatetoaddress", json!([MATURITY, addresses[0]]))?;
+    for round in 0..ROUNDS {
+        // Later rounds spend the coins received by earlier ones
+        for (i, address) in addresses.iter().enumerate() {
+            let amount = format!("{}.{}", 1 + i, round + 1);
+            rpc.wallet("sendtoaddress", json!([address, amount]))?;
+        }
+        if round == 0 {
+            let outputs = json!([{ "data": hex(b"bench selftest") }]);
+            rpc.wallet("send", json!([outputs]))?;
+        }
+        let miner = &addresses[round % addresses.len()];
+        rpc.wallet("generatetoaddress", json!([1, miner]))?;
+    }
+    let tip = rpc.call("getblockcount", json!([]))?;
+    tip.as_u64().ok_or_else(|| "invalid block count".into())
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Script length of a `scriptPubKey` JSON object.
+fn script_len(script: &Value) -> Result<u128> {
+    let hex = script["hex"].as_str().ok_or("missing scriptPubKey")?;
+    Ok(hex.len() as u128 / 2)
+}
+
+fn totals(rpc: &Rpc, tip: u64) -> Result<Totals> {
+    let mut totals = Totals::default();
+    for height in 1..=tip {
+        let hash = rpc.call("getblockhash", json!([height]))?;
+        let block = rpc.call("getblock", json!([hash, 3]))?;
+        for tx in block["tx"].as_array().ok_or("missing transactions")? {
+            for input in tx["vin"].as_array().ok_or("missing inputs")? {
+                if input.get("coinbase").is_some() {
+                    continue;
+                }
+                let prevout = &input["prevout"];
+                let value = prevout["value"].as_f64().ok_or("missing prevout")?;
+                totals.inputs += 1;
+                totals.spent += (value * 1e8).round() as u128;
+                totals.spent_scripts += script_len(&prevout["scriptPubKey"])?;
+            }
+            for output in tx["vout"].as_array().ok_or("missing outputs")? {
+                totals.output_scripts += script_len(&output["scriptPubKey"])?;
+            }
+        }
+    }
+    Ok(totals)
+}
+
+/// Parses `name: <digits>` out of a `Stats { .. }` log line.
+fn field(message: &str, name: &str) -> Option<u128> {
+    let start = message.find(&format!(" {}: ", name))? + name.len() + 3;
+    let digits = &message[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end].parse().ok()
+}
+
+/// Runs a single benchmark against the node, and checks its summed stats.
+fn check(node: &Node, tip: u64, name: &str, flags: &[&str], totals: &Totals) -> Result<()> {
+    let mut cmd = Command::new(std::env::current_exe()?);
+    // Don't pick up the user's settings
+    for (var, _) in std::env::vars_os() {
+        if var.to_string_lossy().starts_with("BENCH_") {
+            cmd.env_remove(var);
+        }
+    }
+    let output = cmd
+        .args(["--network", "regtest", "--log-format", "json"])
+        .args(["--stats", "counts,scripts,values"])
+        .arg(format!("--url=http://127.0.0.1:{}", node.port))
+        .arg("--start=1")
+        .arg(format!("--count={}", tip))
+        .arg(format!("--type={}", name))
+        .args(flags)
+        .env("RUST_LOG", "info")
+        .output()?;
+    let log = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("{} {:?} failed ({}):\n{}", name, flags, output.status, log).into());
+    }
+    let messages: Vec<String> = log
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|event| event["fields"]["message"].as_str().map(str::to_owned))
+        // Per chunk, as the end-of-run summary repeats their totals
+        .filter(|message| message.contains("Stats {") && !message.starts_with("summary:"))
+        .collect();
+    if messages.is_empty() {
+        return Err(format!("{} {:?} logged no stats:\n{}", name, flags, log).into());
+    }
+    for (key, want) in expected(name, totals) {
+        let got: u128 = messages.iter().filter_map(|m| field(m, key)).sum();
+        if got != want {
+            return Err(
+                format!("{} {:?}: {} = {}, expected {}", name, flags, key, got, want).into(),
+            );
+        }
+    }
+    tracing::info!("{} {:?}: ok", name, flags);
+    Ok(())
+}
+
+pub fn run(bitcoind: &Path) -> Result<()> {
+    let node = Node::start(bitcoind)?;
+    let tip = mine(&node.rpc)?;
+    let totals = totals(&node.rpc, tip)?;
+    tracing::info!("mined {} blocks: {:?}", tip, totals);
+    if totals.inputs == 0 {
+        return Err("no coins were spent".into());
+    }
+    let mut failures = 0;
+    for entry in REGISTRY {
+        if entry.name == "index-write" && !cfg!(feature = "rocksdb") {
+            tracing::info!("{}: skipped (requires the `rocksdb` feature)", entry.name);
+            continue;
+        }
+        let flags = std::iter::once(&[][..]).chain(variants(entry.name).iter().copied());
+        for flags in flags {
+            if let Err(e) = check(&node, tip, entry.name, flags, &totals) {
+                tracing::error!("{}", e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(format!("selftest: {} runs failed", failures).into());
+    }
+    tracing::info!("selftest passed");
+    Ok(())
+}
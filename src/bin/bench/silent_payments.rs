@@ -0,0 +1,247 @@
+//! `SilentPayments` benchmark: BIP352 scanning, computing each eligible transaction's tweak
+//! from its inputs' public keys (found via the spent outputs), as an indexing server would, and
+//! then scanning its taproot outputs with a (random) scan and spend key, as a wallet would.
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    consensus::{Decodable, Encodable},
+    hashes::{hash160, sha256, Hash, HashEngine},
+    io::Cursor,
+    secp256k1::{All, Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey},
+    Block, Transaction, TxIn, TxOut,
+};
+
+use crate::{
+    benchmarks::Benchmark, bloom::pushes, spenttxouts_parse, Args, Counters, Result, Scratch,
+};
+
+/// Unspendable internal key (BIP341's "H"), which script-path spends use to opt out.
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// The public key of an input, if it's eligible (i.e. spending P2TR, P2WPKH, P2SH-P2WPKH or
+/// P2PKH, and exposing a compressed key).
+fn input_key(txin: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    let script = &prevout.script_pubkey;
+    if script.is_p2tr() {
+        let mut witness: Vec<&[u8]> = txin.witness.iter().collect();
+        if witness.len() > 1 && witness.last().is_some_and(|a| a.first() == Some(&0x50)) {
+            witness.pop(); // the annex
+        }
+        if witness.len() > 1 {
+            let control = witness.last()?;
+            if control.get(1..33) == Some(&NUMS_H[..]) {
+                return None;
+            }
+        }
+        let key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).ok()?;
+        return Some(PublicKey::from_x_only_public_key(key, Parity::Even));
+    }
+    let compressed = |data: &[u8]| match data.len() {
+        33 => PublicKey::from_slice(data).ok(),
+        _ => None,
+    };
+    if script.is_p2wpkh() {
+        return compressed(txin.witness.last()?);
+    }
+    if script.is_p2sh() {
+        let redeem = txin.script_sig.redeem_script()?;
+        return redeem
+            .is_p2wpkh()
+            .then(|| compressed(txin.witness.last()?))
+            .flatten();
+    }
+    if script.is_p2pkh() {
+        // The last key push that hashes to the script's (as scriptSigs may be malleated)
+        let hash = &script.as_bytes()[3..23];
+        let pushes: Vec<&[u8]> = pushes(&txin.script_sig).collect();
+        return pushes
+            .into_iter()
+            .rev()
+            .find(|data| data.len() == 33 && hash160::Hash::hash(data)[..] == *hash)
+            .and_then(compressed);
+    }
+    None
+}
+
+/// A scanning wallet's keys.
+struct Keys {
+    scan: Scalar,
+    spend: PublicKey,
+}
+
+pub struct SilentPayments {
+    secp: Secp256k1<All>,
+    keys: Keys,
+    /// Outputs spent by each non-coinbase transaction of the block
+    spent: Vec<Vec<TxOut>>,
+    txs: u64,
+    /// With a taproot output and an eligible input
+    eligible: u64,
+    /// Eligible inputs, of the eligible transactions
+    inputs: u64,
+    /// Outputs paying the wallet (none, given its random keys, unless the code is wrong)
+    found: u64,
+    blocks: u64,
+    /// Computing the tweaks (excluding decoding)
+    tweaking: Duration,
+    /// Deriving the shared secrets and outputs, given the tweaks
+    scanning: Duration,
+}
+
+impl SilentPayments {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        let secp = Secp256k1::new();
+        let scan = SecretKey::from_slice(&rand::random::<[u8; 32]>())?;
+        let spend = SecretKey::from_slice(&rand::random::<[u8; 32]>())?;
+        let keys = Keys {
+            scan: Scalar::from(scan),
+            spend: spend.public_key(&secp),
+        };
+        Ok(Box::new(SilentPayments {
+            secp,
+            keys,
+            spent: Vec::new(),
+            txs: 0,
+            eligible: 0,
+            inputs: 0,
+            found: 0,
+            blocks: 0,
+            tweaking: Duration::ZERO,
+            scanning: Duration::ZERO,
+        }))
+    }
+
+    /// The transaction's tweak (`input_hash·A`), if it's eligible.
+    fn tweak(&mut self, tx: &Transaction, spent: &[TxOut]) -> Result<Option<PublicKey>> {
+        if !tx.output.iter().any(|o| o.script_pubkey.is_p2tr()) {
+            return Ok(None);
+        }
+        let mut keys = Vec::with_capacity(spent.len());
+        for (txin, prevout) in tx.input.iter().zip(spent) {
+            // Spending a future segwit version makes the transaction ineligible
+            if prevout
+                .script_pubkey
+                .witness_version()
+                .is_some_and(|v| v.to_num() > 1)
+            {
+                return Ok(None);
+            }
+            keys.extend(input_key(txin, prevout));
+        }
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        let Ok(sum) = PublicKey::combine_keys(&keys.iter().collect::<Vec<_>>()) else {
+            return Ok(None); // the keys cancel out
+        };
+        let mut smallest = [0xff; 36];
+        for txin in &tx.input {
+            let mut outpoint = [0; 36];
+            txin.previous_output
+                .consensus_encode(&mut &mut outpoint[..])?;
+            smallest = smallest.min(outpoint);
+        }
+        let input_hash = tagged_hash("BIP0352/Inputs", &[&smallest, &sum.serialize()]);
+        let Ok(input_hash) = Scalar::from_be_bytes(input_hash) else {
+            return Ok(None);
+        };
+        self.eligible += 1;
+        self.inputs += keys.len() as u64;
+        Ok(Some(sum.mul_tweak(&self.secp, &input_hash)?))
+    }
+
+    /// Counts the outputs paying the wallet (only checking the first, `k = 0`).
+    fn scan(&mut self, tweak: &PublicKey, tx: &Transaction) -> Result<()> {
+        let shared = tweak.mul_tweak(&self.secp, &self.keys.scan)?;
+        let t = tagged_hash("BIP0352/SharedSecret", &[&shared.serialize(), &[0; 4]]);
+        let output = self
+            .keys
+            .spend
+            .add_exp_tweak(&self.secp, &Scalar::from_be_bytes(t)?)?;
+        let expected = output.x_only_public_key().0.serialize();
+        let paying = tx
+            .output
+            .iter()
+            .filter(|o| o.script_pubkey.is_p2tr() && o.script_pubkey.as_bytes()[2..] == expected);
+        self.found += paying.count() as u64;
+        Ok(())
+    }
+}
+
+impl Benchmark for SilentPayments {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+        spenttxouts_parse(responses[1], &mut self.spent)?;
+        let txs = block.txdata.get(1..).unwrap_or_default();
+        if txs.len() != self.spent.len() {
+            return Err(format!(
+                "block {}: {} transactions, but spent outputs of {}",
+                scratch.height,
+                txs.len(),
+                self.spent.len()
+            )
+            .into());
+        }
+        let spent = std::mem::take(&mut self.spent);
+        for (tx, prevouts) in txs.iter().zip(&spent) {
+            let t = Instant::now();
+            let tweak = self.tweak(tx, prevouts)?;
+            self.tweaking += t.elapsed();
+
+            if let Some(tweak) = tweak {
+                let t = Instant::now();
+                self.scan(&tweak, tx)?;
+                self.scanning += t.elapsed();
+            }
+        }
+        self.spent = spent;
+        self.txs += txs.len() as u64;
+        self.blocks += 1;
+        counters.count += txs.len() as u64;
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let tweaking = self.tweaking.as_secs_f64();
+        let scanning = self.scanning.as_secs_f64();
+        let total = (tweaking + scanning).max(f64::MIN_POSITIVE);
+        tracing::info!(
+            "silent-payments: {} of {} txs eligible ({} inputs); tweaks computed in {:.3}[s] \
+             ({:.0}[tweaks/s]), scanned in {:.3}[s]; {:.0}[blocks/s]",
+            self.eligible,
+            self.txs,
+            self.inputs,
+            tweaking,
+            self.eligible as f64 / tweaking.max(f64::MIN_POSITIVE),
+            scanning,
+            self.blocks as f64 / total
+        );
+        if self.found > 0 {
+            tracing::warn!(
+                "silent-payments: {} outputs unexpectedly paying the random keys",
+                self.found
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! `--sqlite` history, appending one row per run and one row per benchmarked chunk.
+
+pub use imp::SqliteSink;
+
+#[cfg(feature = "sqlite")]
+mod imp {
+    use std::{
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use rusqlite::{params, Connection};
+
+    use crate::{Args, BlockRecord, Result};
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS runs (
+            id INTEGER PRIMARY KEY,
+            started INTEGER NOT NULL, -- unix time
+            command TEXT NOT NULL,
+            network TEXT NOT NULL,
+            version TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS chunks (
+            run INTEGER NOT NULL REFERENCES runs(id),
+            bench TEXT NOT NULL,
+            url TEXT NOT NULL,
+            height INTEGER NOT NULL, -- first block of the chunk
+            blocks INTEGER NOT NULL,
+            duration_us INTEGER NOT NULL,
+            received INTEGER NOT NULL,
+            bytes INTEGER NOT NULL,
+            fetch_us INTEGER NOT NULL,
+            decode_us INTEGER NOT NULL,
+            coins INTEGER NOT NULL,
+            spent INTEGER NOT NULL,
+            scripts INTEGER NOT NULL
+        );
+    ";
+
+    pub struct SqliteSink {
+        db: Connection,
+        run: i64,
+    }
+
+    impl SqliteSink {
+        pub fn open(path: &Path, args: &Args) -> Result<Self> {
+            let db = Connection::open(path)?;
+            db.execute_batch(SCHEMA)?;
+            let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let command = std::env::args().collect::<Vec<_>>().join(" ");
+            db.execute(
+                "INSERT INTO runs (started, command, network, version) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    started as i64,
+                    command,
+                    args.network.chain_name(),
+                    env!("CARGO_PKG_VERSION")
+                ],
+            )?;
+            let run = db.last_insert_rowid();
+            Ok(SqliteSink { db, run })
+        }
+
+        pub fn write_chunk(
+            &mut self,
+            bench: &str,
+            url: &str,
+            duration: Duration,
+            records: &[BlockRecord],
+        ) -> Result<()> {
+            let Some(first) = records.first() else {
+                return Ok(());
+            };
+            let sum =
+                |f: fn(&BlockRecord) -> u64| -> i64 { records.iter().map(f).sum::<u64>() as i64 };
+            self.db.execute(
+                "INSERT INTO chunks VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    self.run,
+                    bench,
+                    url,
+                    first.height as i64,
+                    records.len() as i64,
+                    duration.as_micros() as i64,
+                    sum(|r| r.received as u64),
+                    sum(|r| r.bytes as u64),
+                    sum(|r| r.fetch.as_micros() as u64),
+                    sum(|r| r.decode.as_micros() as u64),
+                    sum(|r| r.stats.count),
+                    sum(|r| r.stats.spent),
+                    sum(|r| r.stats.scripts),
+                ],
+            )?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod imp {
+    use std::{path::Path, time::Duration};
+
+    use crate::{Args, BlockRecord, Result};
+
+    /// Stands in for the real sink, so that `--sqlite` fails with a clear error.
+    pub struct SqliteSink(std::convert::Infallible);
+
+    impl SqliteSink {
+        pub fn open(_path: &Path, _args: &Args) -> Result<Self> {
+            Err("built without the `sqlite` feature".into())
+        }
+
+        pub fn write_chunk(
+            &mut self,
+            _bench: &str,
+            _url: &str,
+            _duration: Duration,
+            _records: &[BlockRecord],
+        ) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! `--state-db`: scan progress (the last chunk each benchmark completed against each node) and
+//! the `utxo` benchmark's coins, kept in a sled database so that an interrupted scan resumes
+//! where it stopped.
+
+pub use imp::StateDb;
+
+/// Entries written atomically, removing those without a value.
+pub type Batch = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+#[cfg(feature = "sled")]
+mod imp {
+    use std::{
+        path::Path,
+        sync::{Mutex, PoisonError},
+    };
+
+    use super::Batch;
+    use crate::Result;
+
+    /// Shared by the run loop and the benchmarks, since sled locks its directory.
+    static OPENED: Mutex<Option<sled::Db>> = Mutex::new(None);
+
+    #[derive(Clone)]
+    pub struct StateDb(sled::Db);
+
+    impl StateDb {
+        /// Opens (or creates) the database at `dir`, once per process.
+        pub fn open(dir: &Path) -> Result<Self> {
+            let mut opened = OPENED.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(db) = opened.as_ref() {
+                return Ok(StateDb(db.clone()));
+            }
+            let db = sled::open(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            *opened = Some(db.clone());
+            Ok(StateDb(db))
+        }
+
+        /// The last height `bench` completed against `url`.
+        pub fn progress(&self, bench: &str, url: &str) -> Result<Option<usize>> {
+            let key = format!("{} {}", bench, url);
+            let value = self.0.open_tree("progress")?.get(key)?;
+            Ok(value.map(|v| u64::from_be_bytes(v.as_ref().try_into().expect("8 bytes")) as usize))
+        }
+
+        /// Records that `bench` completed `height` against `url` (unless it got further before).
+        pub fn set_progress(&self, bench: &str, url: &str, height: usize) -> Result<()> {
+            let key = format!("{} {}", bench, url);
+            self.0.open_tree("progress")?.fetch_and_update(key, |old| {
+                let old = old.map_or(0, |v| u64::from_be_bytes(v.try_into().expect("8 bytes")));
+                Some(old.max(height as u64).to_be_bytes().to_vec())
+            })?;
+            self.0.flush()?;
+            Ok(())
+        }
+
+        /// All entries of `tree`, in key order.
+        pub fn load(&self, tree: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            self.0
+                .open_tree(tree)?
+                .iter()
+                .map(|entry| {
+                    let (key, value) = entry?;
+                    Ok((key.to_vec(), value.to_vec()))
+                })
+                .collect()
+        }
+
+        /// Applies `batch` to `tree` atomically, and waits until it's durable.
+        pub fn commit(&self, tree: &str, batch: Batch) -> Result<()> {
+            let mut writes = sled::Batch::default();
+            for (key, value) in batch {
+                match value {
+                    Some(value) => writes.insert(key, value),
+                    None => writes.remove(key),
+                }
+            }
+            self.0.open_tree(tree)?.apply_batch(writes)?;
+            self.0.flush()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "sled"))]
+mod imp {
+    use std::path::Path;
+
+    use super::Batch;
+    use crate::Result;
+
+    /// Stands in for the real database, so that `--state-db` fails with a clear error.
+    #[derive(Clone)]
+    pub struct StateDb(std::convert::Infallible);
+
+    impl StateDb {
+        pub fn open(_dir: &Path) -> Result<Self> {
+            Err("built without the `sled` feature".into())
+        }
+
+        pub fn progress(&self, _bench: &str, _url: &str) -> Result<Option<usize>> {
+            match self.0 {}
+        }
+
+        pub fn set_progress(&self, _bench: &str, _url: &str, _height: usize) -> Result<()> {
+            match self.0 {}
+        }
+
+        pub fn load(&self, _tree: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            match self.0 {}
+        }
+
+        pub fn commit(&self, _tree: &str, _batch: Batch) -> Result<()> {
+            match self.0 {}
+        }
+    }
+}
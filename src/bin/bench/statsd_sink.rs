@@ -0,0 +1,69 @@
+//! `--statsd`: per-request latency timers and byte counters, sent over UDP as
+//! `<prefix>.<node>.<bench>.<resource>.<metric>`, e.g.
+//! `bench.127_0_0_1_8332.verify.blockundo.fetch`.
+
+use std::{net::UdpSocket, time::Duration};
+
+use crate::{Fetch, Result};
+
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+/// Replaces the characters graphite and statsd treat specially in a metric's path component.
+fn component(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e3
+}
+
+impl StatsdSink {
+    pub fn connect(addr: &str, prefix: &str) -> Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket
+            .connect(addr)
+            .map_err(|e| format!("statsd {}: {}", addr, e))?;
+        Ok(StatsdSink {
+            socket,
+            prefix: prefix.to_owned(),
+        })
+    }
+
+    /// Sends one request's metrics as a single (multi-metric) packet.
+    pub fn send(&self, bench: &str, url: &str, fetch: &Fetch, decode: Duration) {
+        let (node, path) = url.split_once("/rest/").unwrap_or((url, ""));
+        let node = node.split_once("://").map_or(node, |(_, rest)| rest);
+        let resource = path.split('/').next().unwrap_or_default();
+        let name = format!(
+            "{}.{}.{}.{}",
+            self.prefix,
+            component(node),
+            component(bench),
+            component(resource)
+        );
+        let mut packet = format!(
+            "{name}.requests:1|c\n{name}.bytes:{}|c\n{name}.fetch:{:.3}|ms\n{name}.ttfb:{:.3}|ms\n\
+             {name}.decode:{:.3}|ms",
+            fetch.received,
+            millis(fetch.elapsed()),
+            millis(fetch.ttfb),
+            millis(decode),
+        );
+        if !fetch.inflate.is_zero() {
+            packet += &format!("\n{name}.inflate:{:.3}|ms", millis(fetch.inflate));
+        }
+        // Like other statsd clients, drops metrics nobody is listening for
+        let _ = self.socket.send(packet.as_bytes());
+    }
+}
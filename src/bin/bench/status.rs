@@ -0,0 +1,94 @@
+//! Status snapshots on `SIGUSR1` (or every `--status-interval`), for long runs that look stuck.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{mb_per_sec, BlockRecord, BlockStats, Result};
+
+/// Polled once per block, so a snapshot never interrupts a request or a decode.
+pub struct StatusTrigger {
+    signalled: Arc<AtomicBool>,
+    interval: Option<Duration>,
+    last: Cell<Instant>,
+}
+
+impl StatusTrigger {
+    pub fn new(interval: Option<Duration>) -> Result<Self> {
+        let signalled = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        signal_hook::flag::register(signal_hook::consts::SIGUSR1, Arc::clone(&signalled))?;
+        Ok(StatusTrigger {
+            signalled,
+            interval,
+            last: Cell::new(Instant::now()),
+        })
+    }
+
+    /// Returns whether a snapshot is due, restarting the interval if so.
+    pub fn due(&self) -> bool {
+        let now = Instant::now();
+        let elapsed = self
+            .interval
+            .is_some_and(|interval| now - self.last.get() >= interval);
+        if self.signalled.swap(false, Ordering::Relaxed) || elapsed {
+            self.last.set(now);
+            return true;
+        }
+        false
+    }
+}
+
+/// Totals of the current benchmark's completed chunks.
+pub struct Progress {
+    pub name: &'static str,
+    pub started: Instant,
+    /// Blocks to fetch, over all endpoints
+    pub total: usize,
+    pub blocks: usize,
+    pub bytes: usize,
+    pub stats: BlockStats,
+}
+
+impl Progress {
+    pub fn new(name: &'static str, total: usize) -> Self {
+        Progress {
+            name,
+            started: Instant::now(),
+            total,
+            blocks: 0,
+            bytes: 0,
+            stats: BlockStats::default(),
+        }
+    }
+
+    /// Adds a completed chunk.
+    pub fn add_chunk(&mut self, records: &[BlockRecord]) {
+        self.blocks += records.len();
+        self.bytes += records.iter().map(|r| r.received).sum::<usize>();
+        self.stats = self.stats + records.iter().map(|r| r.stats).sum();
+    }
+
+    /// Logs the totals so far, including the current chunk's blocks.
+    pub fn log(&self, label: &str, height: usize, records: &[BlockRecord]) {
+        let blocks = self.blocks + records.len();
+        let bytes = self.bytes + records.iter().map(|r| r.received).sum::<usize>();
+        let elapsed = self.started.elapsed();
+        tracing::info!(
+            "status: {} {} at height {}, {}/{} blocks in {:.1}[s], {:.1}[MB/s] {:?}",
+            self.name,
+            label,
+            height,
+            blocks,
+            self.total,
+            elapsed.as_secs_f64(),
+            mb_per_sec(bytes, elapsed),
+            self.stats + records.iter().map(|r| r.stats).sum(),
+        );
+    }
+}
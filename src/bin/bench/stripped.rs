@@ -0,0 +1,104 @@
+//! `Stripped` benchmark: encodes each block without its witnesses (the serialization of
+//! pre-segwit nodes, and of txids), timing the encoding and reporting the bytes it saves.
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    block::Header,
+    consensus::{Decodable, Encodable},
+    io::Cursor,
+    Block, Transaction, VarInt,
+};
+
+use crate::{benchmarks::Benchmark, check_consumed, Args, Counters, Result, Scratch};
+
+pub struct Stripped {
+    blocks: u64,
+    /// Of the responses, and of their stripped encodings
+    bytes: u64,
+    stripped: u64,
+    encoding: Duration,
+}
+
+impl Stripped {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(Stripped {
+            blocks: 0,
+            bytes: 0,
+            stripped: 0,
+            encoding: Duration::ZERO,
+        }))
+    }
+}
+
+/// Encodes `tx` as `Transaction::compute_txid` hashes it (a witness-less transaction's encoding
+/// is the same either way).
+fn encode_stripped(tx: &Transaction, out: &mut Vec<u8>) -> Result<()> {
+    tx.version.consensus_encode(out)?;
+    tx.input.consensus_encode(out)?;
+    tx.output.consensus_encode(out)?;
+    tx.lock_time.consensus_encode(out)?;
+    Ok(())
+}
+
+impl Benchmark for Stripped {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let mut c = Cursor::new(responses[0]);
+        let block = Block::consensus_decode(&mut c)?;
+        let size = c.position() as usize;
+        check_consumed(responses[0].len() - size, counters, scratch.height, "block");
+
+        let t = Instant::now();
+        scratch.bytes.clear();
+        block.header.consensus_encode(&mut scratch.bytes)?;
+        VarInt::from(block.txdata.len()).consensus_encode(&mut scratch.bytes)?;
+        for tx in &block.txdata {
+            encode_stripped(tx, &mut scratch.bytes)?;
+        }
+        self.encoding += t.elapsed();
+
+        let expected = Header::SIZE
+            + VarInt::from(block.txdata.len()).size()
+            + block
+                .txdata
+                .iter()
+                .map(Transaction::base_size)
+                .sum::<usize>();
+        if scratch.bytes.len() != expected {
+            return Err(format!(
+                "block {}: stripped to {} bytes, instead of {}",
+                scratch.height,
+                scratch.bytes.len(),
+                expected
+            )
+            .into());
+        }
+
+        self.blocks += 1;
+        self.bytes += size as u64;
+        self.stripped += scratch.bytes.len() as u64;
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let encoding = self.encoding.as_secs_f64();
+        let saved = self.bytes - self.stripped;
+        tracing::info!(
+            "stripped: {} blocks ({} bytes) stripped to {} bytes, saving {} ({:.1}%); encoded in \
+             {:.3}[s] ({:.1}[MB/s] of stripped output)",
+            self.blocks,
+            self.bytes,
+            self.stripped,
+            saved,
+            100.0 * saved as f64 / self.bytes.max(1) as f64,
+            encoding,
+            self.stripped as f64 / 1e6 / encoding.max(f64::MIN_POSITIVE)
+        );
+        Ok(())
+    }
+}
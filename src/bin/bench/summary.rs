@@ -0,0 +1,384 @@
+//! End-of-run report aggregating all chunks, also written as JSON with `--summary-out`.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::{failures::Failure, reducers::Reducers, BlockRecord, Result};
+
+/// One benchmark against one endpoint, in one `--iterations` run.
+pub struct RunSummary {
+    name: &'static str,
+    endpoint: String,
+    /// From 1
+    iteration: u32,
+    blocks: usize,
+    /// Decompressed, and on the wire
+    bytes: usize,
+    received: usize,
+    wall: Duration,
+    fetch: Duration,
+    /// Part of `fetch`
+    ttfb: Duration,
+    /// Part of `ttfb`
+    connect: Duration,
+    inflate: Duration,
+    decode: Duration,
+    /// Merged from every chunk's
+    reducers: Reducers,
+    /// With `--on-error skip`
+    skipped: usize,
+    /// Including retried requests
+    failures: BTreeMap<Failure, u64>,
+    /// The `--slowest` blocks by latency, slowest first
+    slowest: Vec<BlockRecord>,
+    max_slowest: usize,
+    /// For `compare`
+    chunks: Vec<Chunk>,
+}
+
+struct Chunk {
+    /// Of the first block
+    height: usize,
+    blocks: usize,
+    wall: Duration,
+    /// Including decompression
+    fetch: Duration,
+    decode: Duration,
+    /// Of the first block, with `--group-by day` or `month`
+    date: Option<String>,
+}
+
+/// Fetch (including decompression) and decode time of a block.
+fn latency(r: &BlockRecord) -> Duration {
+    r.fetch + r.inflate + r.decode
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e3
+}
+
+impl RunSummary {
+    pub fn new(
+        name: &'static str,
+        endpoint: String,
+        iteration: u32,
+        max_slowest: usize,
+        reducers: Reducers,
+    ) -> Self {
+        RunSummary {
+            name,
+            endpoint,
+            iteration: iteration + 1,
+            blocks: 0,
+            bytes: 0,
+            received: 0,
+            wall: Duration::ZERO,
+            fetch: Duration::ZERO,
+            ttfb: Duration::ZERO,
+            connect: Duration::ZERO,
+            inflate: Duration::ZERO,
+            decode: Duration::ZERO,
+            reducers,
+            skipped: 0,
+            failures: BTreeMap::new(),
+            slowest: Vec::new(),
+            max_slowest,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn add_chunk(
+        &mut self,
+        records: &[BlockRecord],
+        wall: Duration,
+        reducers: &Reducers,
+        date: Option<String>,
+    ) {
+        if let Some(first) = records.first() {
+            self.chunks.push(Chunk {
+                height: first.height,
+                blocks: records.len(),
+                wall,
+                fetch: records.iter().map(|r| r.fetch + r.inflate).sum(),
+                decode: records.iter().map(|r| r.decode).sum(),
+                date,
+            });
+        }
+        self.blocks += records.len();
+        self.wall += wall;
+        for r in records {
+            self.bytes += r.bytes;
+            self.received += r.received;
+            self.fetch += r.fetch;
+            self.ttfb += r.ttfb;
+            self.connect += r.connect;
+            self.inflate += r.inflate;
+            self.decode += r.decode;
+        }
+        self.reducers.merge(reducers);
+        if self.max_slowest > 0 {
+            self.slowest.extend_from_slice(records);
+            self.slowest.sort_by_key(|r| std::cmp::Reverse(latency(r)));
+            self.slowest.truncate(self.max_slowest);
+        }
+    }
+
+    pub fn add_failures(&mut self, skipped: usize, failures: BTreeMap<Failure, u64>) {
+        self.skipped += skipped;
+        for (failure, n) in failures {
+            *self.failures.entry(failure).or_default() += n;
+        }
+    }
+
+    /// Keyed by `Failure`'s display form.
+    fn failures(&self) -> BTreeMap<String, u64> {
+        self.failures
+            .iter()
+            .map(|(failure, &n)| (failure.to_string(), n))
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "endpoint": self.endpoint,
+            "iteration": self.iteration,
+            "blocks": self.blocks,
+            "bytes": self.bytes,
+            "received": self.received,
+            "wall_time": self.wall.as_secs_f64(),
+            "fetch_time": self.fetch.as_secs_f64(),
+            "ttfb_time": self.ttfb.as_secs_f64(),
+            "connect_time": self.connect.as_secs_f64(),
+            "inflate_time": self.inflate.as_secs_f64(),
+            "decode_time": self.decode.as_secs_f64(),
+            "skipped": self.skipped,
+            "failures": self.failures(),
+            "slowest": self.slowest.iter().map(|r| json!({
+                "height": r.height,
+                "hash": r.hash.to_string(),
+                "bytes": r.bytes,
+                "received": r.received,
+                "fetch_time": r.fetch.as_secs_f64(),
+                "inflate_time": r.inflate.as_secs_f64(),
+                "decode_time": r.decode.as_secs_f64(),
+            })).collect::<Vec<_>>(),
+            "chunks": self.chunks.iter().map(|c| json!({
+                "height": c.height,
+                "blocks": c.blocks,
+                "wall_time": c.wall.as_secs_f64(),
+                "fetch_time": c.fetch.as_secs_f64(),
+                "decode_time": c.decode.as_secs_f64(),
+                "date": c.date,
+            })).collect::<Vec<_>>(),
+            "stats": self.reducers.json(),
+        })
+    }
+}
+
+/// Logs the time and bandwidth each run would need for all `chain_blocks` blocks, at its measured
+/// per-block averages.
+pub fn extrapolate(runs: &[RunSummary], chain_blocks: usize, sampled: bool) {
+    let iterations = runs.iter().map(|r| r.iteration).max().unwrap_or(1);
+    for run in runs
+        .iter()
+        .filter(|r| r.blocks > 0 && r.iteration == iterations)
+    {
+        let scale = chain_blocks as f64 / run.blocks as f64;
+        let wall = run.wall.mul_f64(scale);
+        // Sub-second precision only matters for short estimates
+        let wall = if wall.as_secs() >= 60 {
+            Duration::from_secs(wall.as_secs())
+        } else {
+            Duration::from_millis(wall.as_millis() as u64)
+        };
+        let received = run.received as f64 * scale;
+        tracing::info!(
+            "summary: full chain ({} blocks) for {} {}: ~{}, {:.1} {} received",
+            chain_blocks,
+            run.name,
+            run.endpoint,
+            humantime::format_duration(wall),
+            if received >= 1e9 {
+                received / 1e9
+            } else {
+                received / 1e6
+            },
+            if received >= 1e9 { "GB" } else { "MB" },
+        );
+    }
+    if !sampled {
+        tracing::info!(
+            "summary: full chain estimates assume the range is typical (block sizes grow with \
+             height); --sample measures a representative subset"
+        );
+    }
+}
+
+/// Writes per-block wall, fetch and decode times in `github-action-benchmark`'s
+/// `customSmallerIsBetter` format.
+pub fn write_github_benchmark(runs: &[RunSummary], path: &Path) -> Result<()> {
+    let iterations = runs.iter().map(|r| r.iteration).max().unwrap_or(1);
+    let mut entries = Vec::new();
+    for run in runs.iter().filter(|r| r.blocks > 0) {
+        let name = if iterations > 1 {
+            format!("{} {} #{}", run.name, run.endpoint, run.iteration)
+        } else {
+            format!("{} {}", run.name, run.endpoint)
+        };
+        let extra = format!(
+            "{} blocks, {} bytes ({} received)",
+            run.blocks, run.bytes, run.received
+        );
+        for (phase, total) in [
+            ("wall", run.wall),
+            ("fetch", run.fetch + run.inflate),
+            ("decode", run.decode),
+        ] {
+            entries.push(json!({
+                "name": format!("{}: {}", name, phase),
+                "unit": "us/block",
+                "value": total.as_secs_f64() * 1e6 / run.blocks as f64,
+                "extra": extra,
+            }));
+        }
+    }
+    std::fs::write(path, serde_json::to_vec_pretty(&entries)?)?;
+    tracing::info!(
+        "wrote {} benchmark entries to {}",
+        entries.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Logs every run's totals, and writes them to `out` (if given).
+pub fn report(runs: &[RunSummary], started: Instant, out: Option<&Path>) -> Result<()> {
+    let iterations = runs.iter().map(|r| r.iteration).max().unwrap_or(1);
+    for run in runs {
+        tracing::info!(
+            "summary: {} {}{}: {} blocks, {} bytes ({} received) in {:.3}[s]; \
+             fetch {:.3}[s] (ttfb {:.3}[s]{}), inflate {:.3}[s], decode {:.3}[s]{}; {:?}",
+            run.name,
+            run.endpoint,
+            if iterations > 1 {
+                format!(" #{}", run.iteration)
+            } else {
+                String::new()
+            },
+            run.blocks,
+            run.bytes,
+            run.received,
+            run.wall.as_secs_f64(),
+            run.fetch.as_secs_f64(),
+            run.ttfb.as_secs_f64(),
+            if run.connect.is_zero() {
+                String::new()
+            } else {
+                format!(", of which connect {:.3}[s]", run.connect.as_secs_f64())
+            },
+            run.inflate.as_secs_f64(),
+            run.decode.as_secs_f64(),
+            if run.failures.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "; {} blocks skipped, failures {:?}",
+                    run.skipped,
+                    run.failures()
+                )
+            },
+            run.reducers.all(),
+        );
+        let label = format!("{} {}", run.name, run.endpoint);
+        run.reducers.summarize(&label, run.decode);
+        if !run.slowest.is_empty() {
+            let blocks: Vec<String> = run
+                .slowest
+                .iter()
+                .map(|r| {
+                    format!(
+                        "{} {:.1}[ms] (fetch {:.1}, decode {:.1}, {} bytes)",
+                        r.height,
+                        millis(latency(r)),
+                        millis(r.fetch + r.inflate),
+                        millis(r.decode),
+                        r.bytes
+                    )
+                })
+                .collect();
+            tracing::info!(
+                "summary: slowest {} {}: {}",
+                run.name,
+                run.endpoint,
+                blocks.join(", ")
+            );
+        }
+    }
+    let wall = started.elapsed();
+    tracing::info!("summary: finished in {:.3}[s]", wall.as_secs_f64());
+    if let Some(path) = out {
+        let report = json!({
+            "wall_time": wall.as_secs_f64(),
+            "runs": runs.iter().map(RunSummary::to_json).collect::<Vec<_>>(),
+        });
+        std::fs::write(path, serde_json::to_vec_pretty(&report)?)?;
+        tracing::info!("wrote summary to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Prints each benchmark's fetch latency per chunk in the first pass against the second
+/// (`--compare-passes`), the difference being the node's caching of the range.
+pub fn compare_passes(runs: &[RunSummary]) {
+    for first in runs.iter().filter(|r| r.iteration == 1) {
+        let Some(second) = runs
+            .iter()
+            .find(|r| r.iteration == 2 && r.name == first.name && r.endpoint == first.endpoint)
+        else {
+            continue;
+        };
+        println!("{} {}", first.name, first.endpoint);
+        println!(
+            "{:>10} {:>8} {:>14} {:>14} {:>9}",
+            "height", "blocks", "first[ms/blk]", "second[ms/blk]", "speedup"
+        );
+        let per_block = |c: &Chunk| millis(c.fetch) / c.blocks.max(1) as f64;
+        let (mut cold, mut warm, mut blocks) = (Duration::ZERO, Duration::ZERO, 0);
+        // `--duration` may have cut either pass short
+        for (a, b) in first.chunks.iter().zip(&second.chunks) {
+            if a.height != b.height || a.blocks != b.blocks {
+                break;
+            }
+            let (a_ms, b_ms) = (per_block(a), per_block(b));
+            println!(
+                "{:>10} {:>8} {:>14.3} {:>14.3} {:>8.2}x",
+                a.height,
+                a.blocks,
+                a_ms,
+                b_ms,
+                a_ms / b_ms.max(f64::MIN_POSITIVE)
+            );
+            cold += a.fetch;
+            warm += b.fetch;
+            blocks += a.blocks;
+        }
+        let (cold, warm) = (
+            millis(cold) / blocks.max(1) as f64,
+            millis(warm) / blocks.max(1) as f64,
+        );
+        println!(
+            "{:>10} {:>8} {:>14.3} {:>14.3} {:>8.2}x",
+            "total",
+            blocks,
+            cold,
+            warm,
+            cold / warm.max(f64::MIN_POSITIVE)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! The `sweep` subcommand: repeats the range at each level of a setting, and prints a table
+//! comparing them. `--jobs` fetches (without decoding) with that many requests in flight,
+//! `--chunk-sizes` runs the benchmarks, and `--header-batches` only resolves the block hashes.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bitcoin::BlockHash;
+
+use crate::{
+    adaptive::percentile,
+    backend::{Backend, BlockUrl},
+    fetch_blockhashes, fetch_into, mb_per_sec, Client, Endpoint, Result, Total,
+};
+
+/// The swept settings' flags, with their help.
+pub const LEVELS: [(&str, &str); 3] = [
+    ("jobs", "Requests in flight, e.g. `1,2,4,8,16`"),
+    (
+        "chunk-sizes",
+        "Blocks per chunk (see --chunk-size), e.g. `10,100,1000`",
+    ),
+    (
+        "header-batches",
+        "Headers per request (see --header-batch), e.g. `10,100,2000`",
+    ),
+];
+
+/// The levels of each setting to sweep, in order (empty if not swept).
+#[derive(Clone, Debug, Default)]
+pub struct Sweep {
+    pub jobs: Vec<usize>,
+    pub chunk_sizes: Vec<usize>,
+    pub header_batches: Vec<usize>,
+}
+
+/// The results of one concurrency level.
+struct Level {
+    jobs: usize,
+    elapsed: Duration,
+    bytes: usize,
+    /// Sorted
+    latencies: Vec<Duration>,
+}
+
+impl Level {
+    fn rps(&self) -> f64 {
+        self.latencies.len() as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Fetches every block from `prefix` with `jobs` requests in flight.
+fn fetch_all(
+    endpoint: &Endpoint,
+    prefix: &BlockUrl,
+    blocks: &[(usize, BlockHash)],
+    jobs: usize,
+) -> Result<Level> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((Vec::with_capacity(blocks.len()), 0));
+    let t = Instant::now();
+    let errors: Vec<String> = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                s.spawn(|| {
+                    let (mut raw, mut data) = (Vec::new(), Vec::new());
+                    while let Some((height, hash)) =
+                        blocks.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let url = prefix.get(*height, hash);
+                        endpoint.client.throttle();
+                        let t = Instant::now();
+                        let fetch = fetch_into(endpoint.client, &url, &mut raw, &mut data)
+                            .map_err(|e| e.to_string())?;
+                        let mut results = results.lock().unwrap();
+                        results.0.push(t.elapsed());
+                        results.1 += fetch.received;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        let results = workers
+            .into_iter()
+            .map(|w| w.join().expect("worker panicked"));
+        results.filter_map(std::result::Result::err).collect()
+    });
+    if let Some(e) = errors.into_iter().next() {
+        return Err(e.into());
+    }
+    let (mut latencies, bytes) = results.into_inner().unwrap();
+    latencies.sort_unstable();
+    Ok(Level {
+        jobs,
+        elapsed: t.elapsed(),
+        bytes,
+        latencies,
+    })
+}
+
+/// Runs every level in order; later levels may find the node's caches warmer.
+pub fn jobs(
+    endpoint: &Endpoint,
+    rest: &str,
+    prefix: &BlockUrl,
+    blocks: &[(usize, BlockHash)],
+    jobs: &[usize],
+) -> Result<()> {
+    let mut levels = Vec::with_capacity(jobs.len());
+    for &n in jobs {
+        let level = fetch_all(endpoint, prefix, blocks, n)?;
+        tracing::info!(
+            "sweep: {} {} with {} jobs: {:.1}[req/s] in {:.3}[s]",
+            rest,
+            endpoint.label,
+            n,
+            level.rps(),
+            level.elapsed.as_secs_f64()
+        );
+        levels.push(level);
+    }
+
+    println!("{} {}", rest, endpoint.label);
+    println!(
+        "{:>6} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "jobs", "[req/s]", "[MB/s]", "p50[ms]", "p99[ms]", "speedup"
+    );
+    let base = levels[0].rps();
+    for level in &levels {
+        println!(
+            "{:>6} {:>10.1} {:>10.1} {:>10.2} {:>10.2} {:>9.2}x",
+            level.jobs,
+            level.rps(),
+            mb_per_sec(level.bytes, level.elapsed),
+            percentile(&level.latencies, 50).as_secs_f64() * 1e3,
+            percentile(&level.latencies, 99).as_secs_f64() * 1e3,
+            level.rps() / base.max(f64::MIN_POSITIVE),
+        );
+    }
+    Ok(())
+}
+
+/// Resolves the range's hashes with every batch size.
+pub fn header_batches(
+    client: &Client,
+    base_url: &str,
+    start: usize,
+    count: usize,
+    batches: &[usize],
+) -> Result<()> {
+    let mut levels = Vec::with_capacity(batches.len());
+    for &batch in batches {
+        let t = Instant::now();
+        let hashes = fetch_blockhashes(Backend::Rest, client, base_url, start, count, batch)?;
+        let elapsed = t.elapsed();
+        tracing::info!(
+            "sweep: {} hashes in batches of {} in {:.3}[s]",
+            hashes.len(),
+            batch,
+            elapsed.as_secs_f64()
+        );
+        levels.push((batch, elapsed));
+    }
+
+    println!("{} headers", base_url);
+    println!(
+        "{:>8} {:>10} {:>12} {:>12} {:>10}",
+        "batch", "requests", "total[s]", "[hashes/s]", "relative"
+    );
+    let base = levels[0].1.as_secs_f64();
+    for &(batch, elapsed) in &levels {
+        // Each batch also resolves its first hash by height
+        let requests = 2 * count.div_ceil(batch);
+        println!(
+            "{:>8} {:>10} {:>12.3} {:>12.0} {:>9.2}x",
+            batch,
+            requests,
+            elapsed.as_secs_f64(),
+            count as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+            elapsed.as_secs_f64() / base.max(f64::MIN_POSITIVE),
+        );
+    }
+    Ok(())
+}
+
+/// Prints one benchmark's totals per chunk size (in `levels`) and endpoint, relative to the
+/// first chunk size.
+pub fn print_chunk_sizes<'a>(
+    endpoints: &[Endpoint],
+    blocks: usize,
+    sizes: &[usize],
+    levels: impl Iterator<Item = &'a [Total]>,
+) {
+    println!(
+        "{:>8} {:<40} {:>12} {:>12} {:>10} {:>10}",
+        "chunk", "url", "total[s]", "[us/call]", "[MB/s]", "relative"
+    );
+    let levels: Vec<&[Total]> = levels.collect();
+    for (&size, totals) in sizes.iter().zip(&levels) {
+        for (u, (endpoint, total)) in endpoints.iter().zip(totals.iter()).enumerate() {
+            let base = levels[0][u].duration.as_secs_f64();
+            println!(
+                "{:>8} {:<40} {:>12.3} {:>12} {:>10.1} {:>9.2}x",
+                size,
+                endpoint.label,
+                total.duration.as_secs_f64(),
+                total.duration.div_f64(blocks.max(1) as f64).as_micros(),
+                mb_per_sec(total.bytes, total.duration),
+                total.duration.as_secs_f64() / base.max(f64::MIN_POSITIVE),
+            );
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! `--trace-requests`: one JSON line per benchmarked request, for offline (e.g. tail) latency analysis.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{Fetch, Result};
+
+pub struct RequestLog {
+    writer: BufWriter<File>,
+    lines: u64,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    bench: &'a str,
+    url: &'a str,
+    height: usize,
+    status: u16,
+    /// On the wire
+    bytes: usize,
+    connect_us: Option<u64>,
+    ttfb_us: u64,
+    /// Missing when streaming, since the body is received while decoding
+    transfer_us: Option<u64>,
+    inflate_us: u64,
+    /// Of the whole block, for benchmarks fetching several responses per block
+    decode_us: u64,
+}
+
+fn micros(d: Duration) -> u64 {
+    d.as_micros() as u64
+}
+
+impl RequestLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(RequestLog {
+            writer: BufWriter::new(file),
+            lines: 0,
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        bench: &str,
+        url: &str,
+        height: usize,
+        fetch: &Fetch,
+        decode: Duration,
+    ) -> Result<()> {
+        let line = Line {
+            bench,
+            url,
+            height,
+            status: fetch.status,
+            bytes: fetch.received,
+            connect_us: fetch.connect.map(micros),
+            ttfb_us: micros(fetch.ttfb),
+            transfer_us: fetch.transfer.map(micros),
+            inflate_us: micros(fetch.inflate),
+            decode_us: micros(decode),
+        };
+        serde_json::to_writer(&mut self.writer, &line)?;
+        self.writer.write_all(b"\n")?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Returns the number of lines written.
+    pub fn finish(mut self) -> Result<u64> {
+        self.writer.flush()?;
+        Ok(self.lines)
+    }
+}
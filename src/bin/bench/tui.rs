@@ -0,0 +1,155 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode},
+    layout::{Constraint, Layout},
+    widgets::{BarChart, Block, Gauge, Paragraph, Sparkline},
+    Frame,
+};
+
+use crate::BlockRecord;
+
+/// Number of most recent blocks shown in the latency sparkline.
+const HISTORY: usize = 256;
+
+const SCRIPT_TYPES: [&str; 7] = [
+    "p2pkh", "p2sh", "p2pk02", "p2pk03", "p2pk04", "p2pk05", "raw",
+];
+
+#[derive(Default)]
+struct State {
+    title: String,
+    blocks: usize,
+    bytes: u64,
+    height: usize,
+    latencies: VecDeque<u64>, // [us], most recent last
+    count_by_type: [u64; 7],
+}
+
+/// Live view of a run, updated by the benchmark loop and rendered from a separate thread.
+pub struct Dashboard {
+    state: Mutex<State>,
+    total_blocks: usize,
+    started: Instant,
+    quit: AtomicBool,
+}
+
+impl Dashboard {
+    pub fn new(total_blocks: usize) -> Self {
+        Dashboard {
+            state: Mutex::default(),
+            total_blocks,
+            started: Instant::now(),
+            quit: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_title(&self, title: String) {
+        self.state.lock().unwrap().title = title;
+    }
+
+    pub fn on_block(&self, height: usize, bytes: usize, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.blocks += 1;
+        state.bytes += bytes as u64;
+        state.height = height;
+        if state.latencies.len() == HISTORY {
+            state.latencies.pop_front();
+        }
+        state.latencies.push_back(latency.as_micros() as u64);
+    }
+
+    pub fn on_chunk(&self, records: &[BlockRecord]) {
+        let mut state = self.state.lock().unwrap();
+        for r in records {
+            for (total, count) in state.count_by_type.iter_mut().zip(r.stats.count_by_type) {
+                *total += count;
+            }
+        }
+    }
+
+    /// Set when the user pressed `q`; the benchmark loop should stop at the next block.
+    pub fn quit_requested(&self) -> bool {
+        self.quit.load(Ordering::Relaxed)
+    }
+
+    /// Renders until `done` is set (or the user quits), restoring the terminal afterwards.
+    pub fn run(&self, done: &AtomicBool) -> io::Result<()> {
+        let mut terminal = ratatui::init();
+        let result = (|| {
+            while !done.load(Ordering::Relaxed) && !self.quit_requested() {
+                terminal.draw(|frame| self.draw(frame))?;
+                if event::poll(Duration::from_millis(250))? {
+                    if let Event::Key(key) = event::read()? {
+                        if key.code == KeyCode::Char('q') {
+                            self.quit.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })();
+        ratatui::restore();
+        result
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let state = self.state.lock().unwrap();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let [header, progress, latency, types] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Min(6),
+            Constraint::Length(12),
+        ])
+        .areas(frame.area());
+
+        let avg_us = match state.blocks {
+            0 => 0.0,
+            n => elapsed * 1e6 / n as f64,
+        };
+        let text = format!(
+            "height {}  |  {:.1} blocks/s  {:.2} MB/s  |  {:.0} [us/call] avg\nq: quit",
+            state.height,
+            state.blocks as f64 / elapsed,
+            state.bytes as f64 / elapsed / 1e6,
+            avg_us,
+        );
+        let block = Block::bordered().title(state.title.as_str());
+        frame.render_widget(Paragraph::new(text).block(block), header);
+
+        let ratio = state.blocks as f64 / self.total_blocks.max(1) as f64;
+        let gauge = Gauge::default()
+            .block(Block::bordered().title("progress"))
+            .ratio(ratio.min(1.0))
+            .label(format!("{}/{}", state.blocks, self.total_blocks));
+        frame.render_widget(gauge, progress);
+
+        let max = state.latencies.iter().copied().max().unwrap_or(0);
+        let latencies: Vec<u64> = state.latencies.iter().copied().collect();
+        let sparkline = Sparkline::default()
+            .block(Block::bordered().title(format!("latency [us], max {}", max)))
+            .data(&latencies);
+        frame.render_widget(sparkline, latency);
+
+        let bars: Vec<(&str, u64)> = SCRIPT_TYPES
+            .iter()
+            .copied()
+            .zip(state.count_by_type)
+            .collect();
+        let chart = BarChart::default()
+            .block(Block::bordered().title("script types"))
+            .bar_width(8)
+            .bar_gap(2)
+            .data(bars.as_slice());
+        frame.render_widget(chart, types);
+    }
+}
@@ -0,0 +1,289 @@
+//! Borrow-based `CBlockUndo` parsing, working directly on the response bytes.
+
+use bitcoin::Script;
+
+use crate::{decompress_amount, script_type::ScriptType, Result, SPECIAL_SCRIPTS};
+
+/// A compressed script, as stored by `ScriptCompression`.
+pub struct CompressedScript<'a> {
+    /// 0..=5 for the special script types, `SPECIAL_SCRIPTS` for a raw script.
+    pub kind: usize,
+    pub bytes: &'a [u8],
+}
+
+impl CompressedScript<'_> {
+    /// Length of the decompressed scriptPubKey, without decompressing it.
+    pub fn script_len(&self) -> usize {
+        match self.kind {
+            0 => 25,     // P2PKH
+            1 => 23,     // P2SH
+            2..=3 => 35, // P2PK (compressed)
+            4..=5 => 67, // P2PK (uncompressed)
+            _ => self.bytes.len(),
+        }
+    }
+
+    /// Type of the decompressed scriptPubKey, without decompressing it.
+    pub fn script_type(&self) -> ScriptType {
+        match self.kind {
+            0 => ScriptType::P2pkh,
+            1 => ScriptType::P2sh,
+            2..=5 => ScriptType::P2pk,
+            _ => ScriptType::of(Script::from_bytes(self.bytes)),
+        }
+    }
+}
+
+/// A spent coin (`TxInUndoFormatter`).
+pub struct Coin<'a> {
+    /// Height of the block that created the coin
+    pub height: u32,
+    pub coinbase: bool,
+    pub amount: u64,
+    pub script: CompressedScript<'a>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err("unexpected end of undo data".into());
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Bitcoin Core's `VARINT` (MSB base-128 with offset)
+    fn varint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        loop {
+            let b = self.u8()?;
+            n = n
+                .checked_mul(128)
+                .ok_or("VARINT overflow")?
+                .wrapping_add((b & 0x7F) as u64);
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+            n = n.checked_add(1).ok_or("VARINT overflow")?;
+        }
+    }
+
+    /// `CompactSize`, used for vector lengths
+    fn compact_size(&mut self) -> Result<u64> {
+        Ok(match self.u8()? {
+            0xFD => u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()) as u64,
+            0xFE => u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()) as u64,
+            0xFF => u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()),
+            n => n as u64,
+        })
+    }
+
+    fn coin(&mut self) -> Result<Coin<'a>> {
+        let height_coinbase = self.varint()?;
+        let height = u32::try_from(height_coinbase >> 1).map_err(|_| "invalid coin height")?;
+        if height > 0 {
+            let version = self.varint()?; // unused today
+            if version != 0 {
+                return Err(format!("unexpected coin version {}", version).into());
+            }
+        }
+        let amount = decompress_amount(self.varint()?);
+        let len = usize::try_from(self.varint()?).map_err(|_| "invalid script size")?;
+        let script = if len < SPECIAL_SCRIPTS {
+            let size = if len < 2 { 20 } else { 32 };
+            CompressedScript {
+                kind: len,
+                bytes: self.bytes(size)?,
+            }
+        } else {
+            CompressedScript {
+                kind: SPECIAL_SCRIPTS,
+                bytes: self.bytes(len - SPECIAL_SCRIPTS)?,
+            }
+        };
+        Ok(Coin {
+            height,
+            coinbase: height_coinbase & 1 == 1,
+            amount,
+            script,
+        })
+    }
+}
+
+/// Calls `visit` for every spent coin of the block, in order. Returns the number of bytes left
+/// after the undo data (none, if valid).
+pub fn visit<'a>(data: &'a [u8], mut visit: impl FnMut(Coin<'a>)) -> Result<usize> {
+    visit_txs(data, |_tx, coin| visit(coin))
+}
+
+/// Returns the number of (non-coinbase) transactions in `data`.
+pub fn tx_count(data: &[u8]) -> Result<usize> {
+    Ok(Reader { data }.compact_size()? as usize)
+}
+
+/// Like `visit`, also passing the index of the spending transaction (the coinbase excluded).
+pub fn visit_txs<'a>(data: &'a [u8], mut visit: impl FnMut(usize, Coin<'a>)) -> Result<usize> {
+    let mut r = Reader { data };
+    let tx_count = r.compact_size()?;
+    for tx in 0..tx_count as usize {
+        let txin_count = r.compact_size()?;
+        for _ in 0..txin_count {
+            visit(tx, r.coin()?);
+        }
+    }
+    Ok(r.data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tx_count, visit_txs, Reader};
+    use crate::{decompress_amount, script_type::ScriptType};
+
+    /// Bitcoin Core's `WriteVarInt`.
+    fn varint(mut n: u64) -> Vec<u8> {
+        let mut data = vec![(n & 0x7F) as u8];
+        while n > 0x7F {
+            n = (n >> 7) - 1;
+            data.push((n & 0x7F) as u8 | 0x80);
+        }
+        data.reverse();
+        data
+    }
+
+    /// A coinbase output's coin, spending a P2PKH script.
+    fn p2pkh_coin(height: u64) -> Vec<u8> {
+        let mut data = varint(height << 1 | 1);
+        data.extend(varint(0)); // version
+        data.extend(varint(9)); // amount
+        data.extend(varint(0)); // script type
+        data.extend([0xAB; 20]);
+        data
+    }
+
+    /// A coin spending a raw (P2WPKH) script.
+    fn p2wpkh_coin(height: u64) -> Vec<u8> {
+        let mut data = varint(height << 1);
+        data.extend(varint(0));
+        data.extend(varint(1));
+        data.extend(varint(6 + 22));
+        data.extend([0x00, 0x14]);
+        data.extend([0xCD; 20]);
+        data
+    }
+
+    #[test]
+    fn varint_roundtrip() {
+        let bounds = [
+            0,
+            1,
+            0x7F,
+            0x80,
+            0xFF,
+            0x407F,
+            0x4080,
+            1 << 32,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        for n in bounds {
+            let data = varint(n);
+            let mut r = Reader { data: &data };
+            assert_eq!(r.varint().unwrap(), n, "{:x?}", data);
+            assert!(r.data.is_empty());
+        }
+        assert_eq!(varint(0x80), [0x80, 0x00]);
+    }
+
+    #[test]
+    fn varint_truncated() {
+        for data in [&[][..], &[0x80], &[0xFF, 0x80]] {
+            let err = Reader { data }.varint().unwrap_err();
+            assert_eq!(err.to_string(), "unexpected end of undo data");
+        }
+    }
+
+    #[test]
+    fn varint_oversized() {
+        // One more digit than `u64::MAX` has
+        let mut data = varint(u64::MAX);
+        data.insert(0, 0x80);
+        for data in [&data[..], &[0xFF; 11]] {
+            let err = Reader { data }.varint().unwrap_err();
+            assert_eq!(err.to_string(), "VARINT overflow");
+        }
+    }
+
+    #[test]
+    fn compact_size() {
+        let sizes: [(&[u8], u64); 4] = [
+            (&[0xFC], 0xFC),
+            (&[0xFD, 0x34, 0x12], 0x1234),
+            (&[0xFE, 0x78, 0x56, 0x34, 0x12], 0x1234_5678),
+            (&[0xFF, 1, 0, 0, 0, 0, 0, 0, 0], 1),
+        ];
+        for (data, n) in sizes {
+            assert_eq!(Reader { data }.compact_size().unwrap(), n);
+        }
+        assert!(Reader {
+            data: &[0xFD, 0x34]
+        }
+        .compact_size()
+        .is_err());
+    }
+
+    #[test]
+    fn visits_coins() {
+        // Two transactions, spending one coin and two, and a trailing byte
+        let mut data = vec![2, 1];
+        data.extend(p2pkh_coin(500_000));
+        data.push(2);
+        data.extend(p2wpkh_coin(1));
+        data.extend(p2pkh_coin(7));
+        data.push(0xEE);
+        assert_eq!(tx_count(&data).unwrap(), 2);
+        let mut coins = vec![];
+        let rest = visit_txs(&data, |tx, coin| {
+            let script = (coin.script.script_type(), coin.script.script_len());
+            coins.push((tx, coin.height, coin.coinbase, coin.amount, script));
+        })
+        .unwrap();
+        assert_eq!(rest, 1);
+        let p2pkh = (ScriptType::P2pkh, 25);
+        let p2wpkh = (ScriptType::P2wpkh, 22);
+        assert_eq!(
+            coins,
+            [
+                (0, 500_000, true, decompress_amount(9), p2pkh),
+                (1, 1, false, decompress_amount(1), p2wpkh),
+                (1, 7, true, decompress_amount(9), p2pkh),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_coin_version() {
+        let mut data = vec![1, 1];
+        data.extend(varint(100 << 1));
+        data.extend(varint(1));
+        let err = visit_txs(&data, |_, _| {}).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected coin version 1");
+    }
+
+    #[test]
+    fn truncated_script() {
+        let mut data = vec![1, 1];
+        data.extend(p2wpkh_coin(7));
+        data.pop();
+        let err = visit_txs(&data, |_, _| {}).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected end of undo data");
+    }
+}
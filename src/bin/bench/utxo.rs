@@ -0,0 +1,374 @@
+//! `Utxo` benchmark: applies each block to an in-memory UTXO set (spending its inputs' coins and
+//! adding its outputs), modelling the cost of maintaining a chainstate.
+//!
+//! With `--state-db`, the set is saved after every chunk, and loaded again by the next run.
+
+use std::{
+    collections::HashMap,
+    mem::size_of,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    consensus::{deserialize, serialize, Decodable},
+    io::Cursor,
+    Block, OutPoint,
+};
+
+use crate::{
+    benchmarks::Benchmark, failures::OnError, state::StateDb, Args, Counters, Result, Scratch,
+};
+
+/// Of the coins in the `--state-db`, along with their outpoints.
+const TREE: &str = "utxo";
+
+/// Holds the height of the last block applied to the saved coins.
+const TIP: &[u8] = b"tip";
+
+/// Blocks before a coinbase's outputs can be spent.
+const COINBASE_MATURITY: usize = 100;
+
+struct Coin {
+    value: u64,
+    height: u32,
+    coinbase: bool,
+    script: Box<[u8]>,
+}
+
+impl Coin {
+    /// Value, height (both little-endian), coinbase flag and script.
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(13 + self.script.len());
+        data.extend_from_slice(&self.value.to_le_bytes());
+        data.extend_from_slice(&self.height.to_le_bytes());
+        data.push(self.coinbase as u8);
+        data.extend_from_slice(&self.script);
+        data
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self> {
+        if data.len() < 13 {
+            return Err(format!("saved coin of {} bytes", data.len()).into());
+        }
+        Ok(Coin {
+            value: u64::from_le_bytes(data[..8].try_into()?),
+            height: u32::from_le_bytes(data[8..12].try_into()?),
+            coinbase: data[12] != 0,
+            script: data[13..].into(),
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct Utxo {
+    coins: HashMap<OutPoint, Coin>,
+    /// Of the scripts in `coins`
+    script_bytes: usize,
+    /// Of the coins, in satoshis
+    value: u64,
+    peak: usize,
+    created: u64,
+    spent: u64,
+    /// Spending coins created before the benchmarked range
+    missing: u64,
+    /// Spending coinbase outputs before they matured (invalid by consensus)
+    immature: u64,
+    /// Updating the set (excluding decoding)
+    applying: Duration,
+    /// With `--state-db`
+    state: Option<StateDb>,
+    /// The last block applied to the set, skipping earlier ones (with `--state-db`)
+    tip: Option<usize>,
+    /// Coins created or spent since the set was last saved
+    changed: Vec<OutPoint>,
+}
+
+impl Utxo {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        // Spending coins of blocks not applied yet would count them as missing
+        if args.shuffle || args.sample.is_some() || !args.skip.is_empty() {
+            return Err(
+                "the utxo benchmark requires contiguous ascending heights, so it can't be used \
+                 with --shuffle, --sample or --skip"
+                    .into(),
+            );
+        }
+        // The set is shared by the endpoints, each of which would apply the range again
+        if args.urls.len() * args.http_clients.len() > 1 {
+            return Err(
+                "the utxo benchmark keeps a single UTXO set, so it can't be run against several \
+                 endpoints (--url, --http-client, --interleave or --paired)"
+                    .into(),
+            );
+        }
+        // A skipped block's coins would be missing, and the next block wouldn't follow the set
+        if args.on_error == OnError::Skip {
+            return Err(
+                "the utxo benchmark can't skip blocks, so it can't be used with --on-error skip"
+                    .into(),
+            );
+        }
+        let mut utxo = Utxo::default();
+        if let Some(dir) = &args.state_db {
+            let state = StateDb::open(dir)?;
+            for (key, value) in state.load(TREE)? {
+                if key == TIP {
+                    utxo.tip = Some(u64::from_be_bytes(value[..].try_into()?) as usize);
+                    continue;
+                }
+                let coin = Coin::deserialize(&value)?;
+                utxo.script_bytes += coin.script.len();
+                utxo.value += coin.value;
+                utxo.coins.insert(deserialize(&key)?, coin);
+            }
+            if let Some(tip) = utxo.tip {
+                tracing::info!("utxo: loaded {} coins at height {}", utxo.coins.len(), tip);
+            }
+            utxo.peak = utxo.coins.len();
+            utxo.state = Some(state);
+        }
+        Ok(Box::new(utxo))
+    }
+
+    /// Estimates the set's heap usage: the hash table's buckets (with a control byte each), and
+    /// the scripts.
+    fn memory(&self) -> usize {
+        self.coins.capacity() * (size_of::<(OutPoint, Coin)>() + 1) + self.script_bytes
+    }
+
+    fn apply(&mut self, block: &Block, height: usize) {
+        for tx in &block.txdata {
+            let coinbase = tx.is_coinbase();
+            if !coinbase {
+                for txin in &tx.input {
+                    match self.coins.remove(&txin.previous_output) {
+                        Some(coin) => {
+                            self.changed.push(txin.previous_output);
+                            self.script_bytes -= coin.script.len();
+                            self.value -= coin.value;
+                            self.spent += 1;
+                            if coin.coinbase && height - (coin.height as usize) < COINBASE_MATURITY
+                            {
+                                self.immature += 1;
+                            }
+                        }
+                        None => self.missing += 1,
+                    }
+                }
+            }
+            let txid = tx.compute_txid();
+            for (vout, txout) in tx.output.iter().enumerate() {
+                // Never spendable, so not kept by Bitcoin Core either
+                if txout.script_pubkey.is_op_return() {
+                    continue;
+                }
+                let coin = Coin {
+                    value: txout.value.to_sat(),
+                    height: height as u32,
+                    coinbase,
+                    script: txout.script_pubkey.as_bytes().into(),
+                };
+                self.script_bytes += coin.script.len();
+                self.value += coin.value;
+                let outpoint = OutPoint::new(txid, vout as u32);
+                // Duplicate coinbases (before BIP30) overwrite the unspent coin
+                if let Some(replaced) = self.coins.insert(outpoint, coin) {
+                    self.script_bytes -= replaced.script.len();
+                    self.value -= replaced.value;
+                }
+                self.changed.push(outpoint);
+                self.created += 1;
+            }
+        }
+        self.peak = self.peak.max(self.coins.len());
+        self.tip = Some(height);
+    }
+}
+
+impl Benchmark for Utxo {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let height = scratch.height;
+        if self.state.is_some() && self.tip.is_some_and(|tip| height <= tip) {
+            // Already in the saved set
+            return Ok(());
+        }
+        if let Some(tip) = self.tip.filter(|&tip| height != tip + 1) {
+            return Err(format!("utxo: block {} doesn't follow block {}", height, tip).into());
+        }
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+        let created = self.created;
+        let t = Instant::now();
+        self.apply(&block, height);
+        self.applying += t.elapsed();
+        counters.count += self.created - created;
+        Ok(())
+    }
+
+    /// Saves the coins changed since the previous checkpoint, with the new tip.
+    fn checkpoint(&mut self) -> Result<()> {
+        let (Some(state), Some(tip)) = (&self.state, self.tip) else {
+            return Ok(());
+        };
+        let mut batch: Vec<_> = self
+            .changed
+            .drain(..)
+            .map(|outpoint| {
+                let coin = self.coins.get(&outpoint).map(Coin::serialize);
+                (serialize(&outpoint), coin)
+            })
+            .collect();
+        batch.push((TIP.to_vec(), Some((tip as u64).to_be_bytes().to_vec())));
+        state.commit(TREE, batch)
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let applying = self.applying.as_secs_f64();
+        tracing::info!(
+            "utxo: {} coins (peak {}) of {:.8} BTC, ~{:.1} MB; {} created and {} spent in {:.3}[s], \
+             {:.0}[coins/s]; {} spends of coins created before the range",
+            self.coins.len(),
+            self.peak,
+            self.value as f64 / 1e8,
+            self.memory() as f64 / 1e6,
+            self.created,
+            self.spent,
+            applying,
+            (self.created + self.spent) as f64 / applying.max(f64::MIN_POSITIVE),
+            self.missing
+        );
+        if self.immature > 0 {
+            tracing::warn!(
+                "utxo: {} spends of immature coinbase outputs",
+                self.immature
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::LockTime, block, hashes::Hash, transaction::Version, Amount, Block, BlockHash,
+        CompactTarget, OutPoint, ScriptBuf, Transaction, TxIn, TxMerkleNode, TxOut,
+    };
+
+    use super::{Coin, Utxo, COINBASE_MATURITY};
+
+    fn tx(input: Vec<OutPoint>, values: &[u64]) -> Transaction {
+        let input = input.into_iter().map(|previous_output| TxIn {
+            previous_output,
+            ..TxIn::default()
+        });
+        let output = values.iter().map(|&value| TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51; value as usize % 7]),
+        });
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: input.collect(),
+            output: output.collect(),
+        }
+    }
+
+    /// A block of a coinbase paying `reward` (unique per `tag`), and `txs`.
+    fn block(tag: u32, reward: u64, txs: Vec<Transaction>) -> Block {
+        let mut coinbase = tx(vec![OutPoint::null()], &[reward]);
+        coinbase.lock_time = LockTime::from_consensus(tag);
+        let header = block::Header {
+            version: block::Version::TWO,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0),
+            nonce: 0,
+        };
+        let txdata = std::iter::once(coinbase).chain(txs).collect();
+        Block { header, txdata }
+    }
+
+    /// The outpoint of `block`'s `tx`th transaction's `vout`th output.
+    fn outpoint(block: &Block, tx: usize, vout: u32) -> OutPoint {
+        OutPoint::new(block.txdata[tx].compute_txid(), vout)
+    }
+
+    #[test]
+    fn coin_roundtrip() {
+        for script in [&[][..], &[0x51], &[0x00; 34]] {
+            let coin = Coin {
+                value: 21_000_000 * 100_000_000,
+                height: 840_000,
+                coinbase: true,
+                script: script.into(),
+            };
+            let data = coin.serialize();
+            assert_eq!(data.len(), 13 + script.len());
+            let decoded = Coin::deserialize(&data).unwrap();
+            assert_eq!(decoded.value, coin.value);
+            assert_eq!(decoded.height, coin.height);
+            assert!(decoded.coinbase);
+            assert_eq!(decoded.script, coin.script);
+        }
+        assert!(Coin::deserialize(&[0; 12]).is_err());
+    }
+
+    #[test]
+    fn spends_and_creates() {
+        let mut utxo = Utxo::default();
+        let first = block(1, 50, vec![]);
+        utxo.apply(&first, 1);
+        let mut op_return = tx(vec![outpoint(&first, 0, 0)], &[30, 20]);
+        op_return.output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return([1, 2, 3]),
+        });
+        let unknown = tx(vec![OutPoint::new(op_return.compute_txid(), 9)], &[1]);
+        let height = 1 + COINBASE_MATURITY;
+        utxo.apply(&block(2, 25, vec![op_return, unknown]), height);
+        // The second block's coinbase and first 2 outputs, and the third one's output
+        assert_eq!(utxo.coins.len(), 4);
+        assert_eq!(utxo.value, 25 + 30 + 20 + 1);
+        let script_bytes: usize = utxo.coins.values().map(|coin| coin.script.len()).sum();
+        assert_eq!(utxo.script_bytes, script_bytes);
+        assert_eq!((utxo.created, utxo.spent), (5, 1));
+        assert_eq!((utxo.missing, utxo.immature), (1, 0));
+        assert_eq!((utxo.peak, utxo.tip), (4, Some(height)));
+    }
+
+    #[test]
+    fn immature_spends() {
+        let mut utxo = Utxo::default();
+        let first = block(1, 50, vec![]);
+        utxo.apply(&first, 1);
+        let spend = tx(vec![outpoint(&first, 0, 0)], &[50]);
+        let second = block(2, 50, vec![spend]);
+        utxo.apply(&second, COINBASE_MATURITY);
+        assert_eq!((utxo.spent, utxo.immature), (1, 1));
+        // Not a coinbase output, or a mature one
+        let spends = vec![
+            tx(vec![outpoint(&second, 1, 0)], &[50]),
+            tx(vec![outpoint(&second, 0, 0)], &[50]),
+        ];
+        utxo.apply(&block(3, 50, spends), 2 * COINBASE_MATURITY);
+        assert_eq!((utxo.spent, utxo.immature), (3, 1));
+    }
+
+    #[test]
+    fn duplicate_coinbases() {
+        // Before BIP30, a coinbase could repeat an unspent one's txid, overwriting its coin
+        let mut utxo = Utxo::default();
+        utxo.apply(&block(1, 50, vec![]), 1);
+        utxo.apply(&block(1, 50, vec![]), 2);
+        assert_eq!(utxo.coins.len(), 1);
+        assert_eq!((utxo.value, utxo.script_bytes), (50, 1));
+        assert_eq!(utxo.created, 2);
+        let coin = utxo.coins.values().next().unwrap();
+        assert_eq!((coin.height, coin.coinbase), (2, true));
+    }
+}
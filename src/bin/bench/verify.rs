@@ -0,0 +1,146 @@
+//! `Verify` benchmark: cross-checks a block's undo data against its spent outputs.
+
+use bitcoin::{
+    consensus::{encode::VarInt, Decodable},
+    io::Cursor,
+    TxOut,
+};
+
+use crate::{
+    benchmarks::Benchmark, decompress_script, undo, Args, Counters, Result, Scratch,
+    SPECIAL_SCRIPTS,
+};
+
+/// Logged per block, so that a broken endpoint doesn't flood the output.
+const MAX_REPORTED: usize = 10;
+
+/// Counts the coins and mismatches of the whole run.
+#[derive(Default)]
+pub struct Verify {
+    coins: u64,
+    mismatches: u64,
+}
+
+impl Verify {
+    pub fn create(_args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::<Verify>::default())
+    }
+}
+
+impl Benchmark for Verify {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let (coins, mismatches) = (counters.count, counters.mismatches);
+        compare(
+            scratch.height,
+            responses[0],
+            responses[1],
+            counters,
+            scratch,
+        )?;
+        self.coins += counters.count - coins;
+        self.mismatches += counters.mismatches - mismatches;
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        if self.mismatches > 0 {
+            tracing::warn!(
+                "verify: {} mismatches in {} coins",
+                self.mismatches,
+                self.coins
+            );
+        } else {
+            tracing::info!("verify: all {} coins match", self.coins);
+        }
+        Ok(())
+    }
+}
+
+/// Compares the decompressed coins of `/rest/blockundo` with the explicit
+/// `TxOut`s of `/rest/spenttxouts`, counting discrepancies in `stats.mismatches`.
+fn compare(
+    height: usize,
+    undo: &[u8],
+    spent: &[u8],
+    counters: &mut Counters,
+    scratch: &mut Scratch,
+) -> Result<()> {
+    let outputs = spent_outputs(spent)?;
+    let mut index = 0;
+    let mut mismatches = Vec::new();
+    let mut result = Ok(());
+    undo::visit(undo, |coin| {
+        counters.count += 1;
+        counters.count_by_type[coin.script.kind] += 1;
+        counters.spent += coin.amount as u128;
+        counters.scripts += coin.script.script_len() as u64;
+        counters.add_spent_coin(scratch, coin.height, coin.amount);
+
+        let i = index;
+        index += 1;
+        let Some(expected) = outputs.get(i) else {
+            return;
+        };
+        if coin.amount != expected.value.to_sat() {
+            mismatches.push(format!(
+                "coin {}: amount {} != {}",
+                i,
+                coin.amount,
+                expected.value.to_sat()
+            ));
+        }
+        let script = if coin.script.kind < SPECIAL_SCRIPTS {
+            if let Err(e) = decompress_script(
+                coin.script.kind as u8,
+                coin.script.bytes,
+                &mut scratch.script,
+            ) {
+                result = Err(e);
+                return;
+            }
+            scratch.script.as_bytes()
+        } else {
+            coin.script.bytes
+        };
+        if script != expected.script_pubkey.as_bytes() {
+            mismatches.push(format!(
+                "coin {}: script {:x} != {:x}",
+                i,
+                bitcoin::Script::from_bytes(script),
+                expected.script_pubkey
+            ));
+        }
+    })?;
+    result?;
+    if index != outputs.len() {
+        mismatches.push(format!(
+            "{} undo coins != {} spent outputs",
+            index,
+            outputs.len()
+        ));
+    }
+    for mismatch in mismatches.iter().take(MAX_REPORTED) {
+        tracing::warn!("block {}: {}", height, mismatch);
+    }
+    counters.mismatches += mismatches.len() as u64;
+    Ok(())
+}
+
+/// Flattens the per-transaction spent outputs (the coinbase has none).
+fn spent_outputs(data: &[u8]) -> Result<Vec<TxOut>> {
+    let mut d = Cursor::new(data);
+    let mut outputs = Vec::new();
+    let tx_count = VarInt::consensus_decode(&mut d)?.0;
+    for _ in 0..tx_count {
+        let txin_count = VarInt::consensus_decode(&mut d)?.0;
+        for _ in 0..txin_count {
+            outputs.push(TxOut::consensus_decode(&mut d)?);
+        }
+    }
+    Ok(outputs)
+}
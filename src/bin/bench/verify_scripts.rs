@@ -0,0 +1,204 @@
+//! `VerifyScripts` benchmark: runs libbitcoinconsensus on every input of a block, given the
+//! outputs it spends.
+
+use std::time::{Duration, Instant};
+
+use bitcoin::{
+    consensus::{Decodable, Encodable},
+    io::Cursor,
+    Block, TxOut,
+};
+
+use crate::{benchmarks::Benchmark, spenttxouts_parse, Args, Counters, Result, Scratch};
+
+use imp::Verifier;
+
+/// Logged per run, so that a broken endpoint doesn't flood the output.
+const MAX_REPORTED: u64 = 10;
+
+pub struct VerifyScripts {
+    verifier: Verifier,
+    /// Outputs spent by each non-coinbase transaction of the block
+    spent: Vec<Vec<TxOut>>,
+    inputs: u64,
+    invalid: u64,
+    /// Running the script interpreter (excluding decoding)
+    verifying: Duration,
+}
+
+impl VerifyScripts {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        Ok(Box::new(VerifyScripts {
+            verifier: Verifier::new(args.network)?,
+            spent: Vec::new(),
+            inputs: 0,
+            invalid: 0,
+            verifying: Duration::ZERO,
+        }))
+    }
+}
+
+impl Benchmark for VerifyScripts {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        scratch: &mut Scratch,
+    ) -> Result<()> {
+        let height = scratch.height;
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+        spenttxouts_parse(responses[1], &mut self.spent)?;
+        let txs = block.txdata.get(1..).unwrap_or_default();
+        if txs.len() != self.spent.len() {
+            return Err(format!(
+                "block {}: {} transactions, but spent outputs of {}",
+                height,
+                txs.len(),
+                self.spent.len()
+            )
+            .into());
+        }
+        for (tx, spent) in txs.iter().zip(&self.spent) {
+            if tx.input.len() != spent.len() {
+                return Err(format!(
+                    "block {}: {} has {} inputs, but {} spent outputs",
+                    height,
+                    tx.compute_txid(),
+                    tx.input.len(),
+                    spent.len()
+                )
+                .into());
+            }
+            scratch.bytes.clear();
+            tx.consensus_encode(&mut scratch.bytes)?;
+
+            let t = Instant::now();
+            let invalid = self.verifier.verify_tx(height, &scratch.bytes, spent);
+            self.verifying += t.elapsed();
+
+            for (vin, e) in invalid {
+                self.invalid += 1;
+                if self.invalid <= MAX_REPORTED {
+                    tracing::warn!(
+                        "verify-scripts: block {}: {}:{} is invalid: {}",
+                        height,
+                        tx.compute_txid(),
+                        vin,
+                        e
+                    );
+                }
+            }
+            self.inputs += spent.len() as u64;
+            counters.count += spent.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let verifying = self.verifying.as_secs_f64();
+        tracing::info!(
+            "verify-scripts: {} inputs verified in {:.3}[s], {:.0}[inputs/s]; {} invalid",
+            self.inputs,
+            verifying,
+            self.inputs as f64 / verifying.max(f64::MIN_POSITIVE),
+            self.invalid
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bitcoinconsensus")]
+mod imp {
+    use bitcoin::TxOut;
+    use bitcoinconsensus::{Error, Utxo, VERIFY_ALL_PRE_TAPROOT, VERIFY_TAPROOT};
+
+    use crate::{Network, Result};
+
+    pub struct Verifier {
+        network: Network,
+        /// Of the transaction being verified, pointing into its spent outputs
+        utxos: Vec<Utxo>,
+    }
+
+    impl Verifier {
+        pub fn new(network: Network) -> Result<Self> {
+            Ok(Verifier {
+                network,
+                utxos: Vec::new(),
+            })
+        }
+
+        /// Mainnet blocks are verified under the soft forks active at their height, others
+        /// under all of them.
+        fn flags(&self, height: usize) -> u32 {
+            match self.network {
+                Network::Mainnet => bitcoinconsensus::height_to_flags(height as u32),
+                _ => VERIFY_ALL_PRE_TAPROOT | VERIFY_TAPROOT,
+            }
+        }
+
+        /// Verifies each input of the serialized `tx`, spending the respective `spent` output,
+        /// and returns the invalid ones.
+        pub fn verify_tx(
+            &mut self,
+            height: usize,
+            tx: &[u8],
+            spent: &[TxOut],
+        ) -> Vec<(usize, String)> {
+            let flags = self.flags(height);
+            // Taproot signatures commit to all the spent outputs
+            self.utxos.clear();
+            self.utxos.extend(spent.iter().map(|txout| Utxo {
+                script_pubkey: txout.script_pubkey.as_bytes().as_ptr(),
+                script_pubkey_len: txout.script_pubkey.len() as u32,
+                value: txout.value.to_sat() as i64,
+            }));
+            let utxos = &self.utxos;
+            spent
+                .iter()
+                .enumerate()
+                .filter_map(|(vin, txout)| {
+                    bitcoinconsensus::verify_with_flags(
+                        txout.script_pubkey.as_bytes(),
+                        txout.value.to_sat(),
+                        tx,
+                        Some(utxos),
+                        vin,
+                        flags,
+                    )
+                    .err()
+                    .map(|e| match e {
+                        // Returned for any script failure
+                        Error::ERR_SCRIPT => (vin, "script verification failed".to_owned()),
+                        e => (vin, e.to_string()),
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(not(feature = "bitcoinconsensus"))]
+mod imp {
+    use bitcoin::TxOut;
+
+    use crate::{Network, Result};
+
+    /// Stands in for the real verifier, so that `VerifyScripts` fails with a clear error.
+    pub struct Verifier(std::convert::Infallible);
+
+    impl Verifier {
+        pub fn new(_network: Network) -> Result<Self> {
+            Err("built without the `bitcoinconsensus` feature".into())
+        }
+
+        pub fn verify_tx(
+            &mut self,
+            _height: usize,
+            _tx: &[u8],
+            _spent: &[TxOut],
+        ) -> Vec<(usize, String)> {
+            match self.0 {}
+        }
+    }
+}
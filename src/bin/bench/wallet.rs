@@ -0,0 +1,231 @@
+//! `Wallet` benchmark: a watch-only wallet rescan, tracking the balances of the `--addresses`
+//! and `--descriptor`s by their scripthashes (credited by block outputs, and debited by the spent
+//! outputs), deriving more of the descriptors' scripts as they're used.
+
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{
+    consensus::Decodable,
+    hashes::{sha256, Hash},
+    io::Cursor,
+    Address, Block, ScriptBuf, SignedAmount, TxOut,
+};
+
+use crate::{
+    benchmarks::Benchmark, descriptors::Descriptors, spenttxouts_parse, Args, Counters, Result,
+    Scratch,
+};
+
+/// Logged per run, so that large address lists don't flood the output.
+const MAX_REPORTED: usize = 10;
+
+#[derive(Default)]
+struct Balance {
+    /// Net change over the benchmarked range, in satoshis
+    net: i64,
+    credits: u64,
+    debits: u64,
+}
+
+/// Reads the `--addresses` file (`bench` requires it, or a `--descriptor`): one address,
+/// `addr(<address>)` or `raw(<hex script>)` descriptor per line, ignoring blank lines and `#`
+/// comments. Returns each line (without a descriptor checksum) along with its script.
+pub fn watched_scripts(args: &Args, bench: &str) -> Result<Vec<(String, ScriptBuf)>> {
+    let Some(path) = &args.addresses else {
+        if args.descriptors.is_empty() {
+            return Err(format!(
+                "the {} benchmark requires --addresses or --descriptor",
+                bench
+            )
+            .into());
+        }
+        return Ok(Vec::new());
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut scripts = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .split_once('#')
+            .map_or(line, |(desc, _checksum)| desc.trim());
+        let script = if let Some(hex) = line.strip_prefix("raw(").and_then(|s| s.strip_suffix(')'))
+        {
+            ScriptBuf::from_hex(hex).map_err(|e| e.to_string())
+        } else {
+            let address = line
+                .strip_prefix("addr(")
+                .and_then(|s| s.strip_suffix(')'))
+                .unwrap_or(line);
+            Address::from_str(address)
+                .and_then(|a| a.require_network(args.network.bitcoin()))
+                .map(|a| a.script_pubkey())
+                .map_err(|e| e.to_string())
+        }
+        .map_err(|e| format!("{}: {}: {}", path.display(), line, e))?;
+        scripts.push((line.to_owned(), script));
+    }
+    Ok(scripts)
+}
+
+pub struct Wallet {
+    /// In the `--addresses` order, then as derived from the descriptors
+    addresses: Vec<String>,
+    /// The descriptor and derivation index of each of the `addresses` derived from one
+    origins: Vec<Option<(usize, u32)>>,
+    /// Indexed by scripthash
+    balances: HashMap<sha256::Hash, (usize, Balance)>,
+    descriptors: Descriptors,
+    /// Deriving scripts past the used ones (included in `scanning`)
+    deriving: Duration,
+    spent: Vec<Vec<TxOut>>,
+    credits: u64,
+    debits: u64,
+    /// Hashing and looking up the scripts (excluding decoding)
+    scanning: Duration,
+    blocks: u64,
+}
+
+impl Wallet {
+    pub fn create(args: &Args) -> Result<Box<dyn Benchmark>> {
+        let scripts = watched_scripts(args, "wallet")?;
+        let mut wallet = Wallet {
+            addresses: Vec::with_capacity(scripts.len()),
+            origins: Vec::with_capacity(scripts.len()),
+            balances: HashMap::with_capacity(scripts.len()),
+            descriptors: Descriptors::parse(args)?,
+            deriving: Duration::ZERO,
+            spent: Vec::new(),
+            credits: 0,
+            debits: 0,
+            scanning: Duration::ZERO,
+            blocks: 0,
+        };
+        for (label, script) in scripts {
+            wallet.watch(label, None, &script);
+        }
+        for (d, index, script) in wallet.descriptors.initial()? {
+            wallet.watch(
+                wallet.descriptors.label(d, index),
+                Some((d, index)),
+                &script,
+            );
+        }
+        tracing::info!("wallet: watching {} addresses", wallet.addresses.len());
+        Ok(Box::new(wallet))
+    }
+
+    fn watch(&mut self, label: String, origin: Option<(usize, u32)>, script: &ScriptBuf) {
+        let scripthash = sha256::Hash::hash(script.as_bytes());
+        self.balances
+            .insert(scripthash, (self.addresses.len(), Balance::default()));
+        self.addresses.push(label);
+        self.origins.push(origin);
+    }
+
+    /// Returns the balance of `script`'s address (and its position), if watched.
+    fn lookup(&mut self, script: &[u8]) -> Option<(usize, &mut Balance)> {
+        let scripthash = sha256::Hash::hash(script);
+        self.balances.get_mut(&scripthash).map(|(i, b)| (*i, b))
+    }
+
+    /// Derives more scripts past the `i`th address, if it's derived from a descriptor.
+    fn used(&mut self, i: usize) -> Result<()> {
+        let Some((d, index)) = self.origins[i] else {
+            return Ok(());
+        };
+        let t = Instant::now();
+        for (d, index, script) in self.descriptors.used(d, index)? {
+            self.watch(self.descriptors.label(d, index), Some((d, index)), &script);
+        }
+        self.deriving += t.elapsed();
+        Ok(())
+    }
+}
+
+impl Benchmark for Wallet {
+    fn decode(
+        &mut self,
+        responses: &[&[u8]],
+        counters: &mut Counters,
+        _scratch: &mut Scratch,
+    ) -> Result<()> {
+        let block = Block::consensus_decode(&mut Cursor::new(responses[0]))?;
+        spenttxouts_parse(responses[1], &mut self.spent)?;
+        let spent = std::mem::take(&mut self.spent);
+
+        let t = Instant::now();
+        let outputs = block.txdata.iter().flat_map(|tx| &tx.output);
+        let mut scripts = 0;
+        for txout in outputs {
+            scripts += 1;
+            if let Some((i, balance)) = self.lookup(txout.script_pubkey.as_bytes()) {
+                balance.net += txout.value.to_sat() as i64;
+                balance.credits += 1;
+                self.credits += 1;
+                self.used(i)?;
+            }
+        }
+        for txout in spent.iter().flatten() {
+            scripts += 1;
+            if let Some((i, balance)) = self.lookup(txout.script_pubkey.as_bytes()) {
+                balance.net -= txout.value.to_sat() as i64;
+                balance.debits += 1;
+                self.debits += 1;
+                self.used(i)?;
+            }
+        }
+        self.scanning += t.elapsed();
+
+        self.spent = spent;
+        self.blocks += 1;
+        counters.count += scripts;
+        counters.hashes += scripts;
+        Ok(())
+    }
+
+    fn summarize(&mut self) -> Result<()> {
+        let mut active: Vec<&(usize, Balance)> = self
+            .balances
+            .values()
+            .filter(|(_, b)| b.credits + b.debits > 0)
+            .collect();
+        active.sort_by_key(|(i, _)| *i);
+        let net: i64 = active.iter().map(|(_, b)| b.net).sum();
+        let scanning = self.scanning.as_secs_f64();
+        tracing::info!(
+            "wallet: {} of {} addresses active, net {}; {} credits and {} debits found in {:.3}[s], \
+             {:.0}[blocks/s]",
+            active.len(),
+            self.addresses.len(),
+            SignedAmount::from_sat(net),
+            self.credits,
+            self.debits,
+            scanning,
+            self.blocks as f64 / scanning.max(f64::MIN_POSITIVE)
+        );
+        if !self.descriptors.is_empty() {
+            tracing::info!(
+                "wallet: {} scripts derived from {} descriptors, {:.3}[s] deriving past the used ones",
+                self.descriptors.derived(),
+                self.descriptors.len(),
+                self.deriving.as_secs_f64()
+            );
+        }
+        for (i, balance) in active.iter().take(MAX_REPORTED) {
+            tracing::info!(
+                "wallet: {}: net {} ({} credits, {} debits)",
+                self.addresses[*i],
+                SignedAmount::from_sat(balance.net),
+                balance.credits,
+                balance.debits
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+//! Serves a recorded height range over bitcoind's REST API, so that `bench` can run without a
+//! synced node.
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::prelude::*;
+use bitcoin::{block::Header, consensus::Decodable, io::Cursor, BlockHash};
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+
+/// Endpoints recorded for every block (`/rest/<endpoint>/<hash>.bin`).
+const ENDPOINTS: [&str; 3] = ["block", "blockundo", "spenttxouts"];
+
+/// Same limit as bitcoind's `/rest/headers`.
+const MAX_HEADERS: usize = 2000;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+/// Mock bitcoind REST server
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetches a height range from a node into `--dir`
+    Record {
+        /// REST endpoint of a synced node
+        #[arg(long)]
+        url: String,
+
+        /// HTTP basic auth credentials (`user:password`)
+        #[arg(long)]
+        auth: Option<String>,
+
+        #[arg(long)]
+        start: usize,
+
+        #[arg(long)]
+        count: usize,
+
+        #[arg(long)]
+        dir: PathBuf,
+    },
+    /// Serves a range recorded into `--dir`
+    Serve {
+        #[arg(long)]
+        dir: PathBuf,
+
+        #[arg(long, default_value = "127.0.0.1:8332")]
+        listen: String,
+
+        /// Added to every response, to emulate a remote node (e.g. `5ms`)
+        #[arg(long, value_parser = humantime::parse_duration)]
+        delay: Option<Duration>,
+    },
+}
+
+/// `meta.json`, next to `headers.bin` and the `<endpoint>/<hash>.bin` responses.
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    chain: String,
+    start: usize,
+}
+
+#[derive(Deserialize)]
+struct ChainInfo {
+    chain: String,
+}
+
+fn get(agent: &ureq::Agent, auth: Option<&str>, url: &str) -> Result<Vec<u8>> {
+    let mut request = agent.get(url);
+    if let Some(auth) = auth {
+        let value = format!("Basic {}", BASE64_STANDARD.encode(auth));
+        request = request.header("Authorization", value);
+    }
+    let mut response = request.call().map_err(|e| format!("{}: {}", url, e))?;
+    Ok(response
+        .body_mut()
+        .with_config()
+        .limit(u64::MAX)
+        .read_to_vec()?)
+}
+
+fn record(url: &str, auth: Option<&str>, start: usize, count: usize, dir: &Path) -> Result<()> {
+    let agent = ureq::Agent::new_with_defaults();
+    let base = url.trim_end_matches('/');
+    let info: ChainInfo = serde_json::from_slice(&get(
+        &agent,
+        auth,
+        &format!("{}/rest/chaininfo.json", base),
+    )?)?;
+
+    let hash = get(
+        &agent,
+        auth,
+        &format!("{}/rest/blockhashbyheight/{}.hex", base, start),
+    )?;
+    let mut hash = String::from_utf8(hash)?.trim().to_owned();
+    let mut headers = Vec::with_capacity(count * Header::SIZE);
+    let mut hashes = Vec::with_capacity(count);
+    while hashes.len() < count {
+        let n = MAX_HEADERS.min(count - hashes.len() + 1);
+        let data = get(
+            &agent,
+            auth,
+            &format!("{}/rest/headers/{}/{}.bin", base, n, hash),
+        )?;
+        let mut c = Cursor::new(&data);
+        // Each batch after the first starts with the previous batch's last header
+        let skip = usize::from(!hashes.is_empty());
+        for i in 0..data.len() / Header::SIZE {
+            let header = Header::consensus_decode(&mut c)?;
+            if i < skip || hashes.len() == count {
+                continue;
+            }
+            hashes.push(header.block_hash());
+            headers.extend_from_slice(&data[i * Header::SIZE..(i + 1) * Header::SIZE]);
+        }
+        if data.len() / Header::SIZE <= skip {
+            break; // chain tip
+        }
+        hash = hashes.last().expect("non-empty").to_string();
+    }
+
+    for endpoint in ENDPOINTS {
+        std::fs::create_dir_all(dir.join(endpoint))?;
+    }
+    for (i, hash) in hashes.iter().enumerate() {
+        for endpoint in ENDPOINTS {
+            let data = get(
+                &agent,
+                auth,
+                &format!("{}/rest/{}/{}.bin", base, endpoint, hash),
+            )?;
+            std::fs::write(dir.join(endpoint).join(format!("{}.bin", hash)), data)?;
+        }
+        if (i + 1).is_multiple_of(1000) {
+            tracing::info!("recorded {} blocks", i + 1);
+        }
+    }
+    std::fs::write(dir.join("headers.bin"), &headers)?;
+    let meta = Meta {
+        chain: info.chain,
+        start,
+    };
+    std::fs::write(dir.join("meta.json"), serde_json::to_vec_pretty(&meta)?)?;
+    tracing::info!(
+        "recorded {} blocks from height {} into {}",
+        hashes.len(),
+        start,
+        dir.display()
+    );
+    Ok(())
+}
+
+/// The whole recorded range, kept in memory so that disk reads don't skew benchmarks.
+struct Chain {
+    meta: Meta,
+    headers: Vec<u8>,
+    heights: HashMap<BlockHash, usize>,
+    responses: HashMap<String, Vec<u8>>,
+}
+
+impl Chain {
+    fn load(dir: &Path) -> Result<Self> {
+        let meta: Meta = serde_json::from_slice(&std::fs::read(dir.join("meta.json"))?)?;
+        let headers = std::fs::read(dir.join("headers.bin"))?;
+        let mut heights = HashMap::new();
+        let mut responses = HashMap::new();
+        let mut c = Cursor::new(&headers);
+        for i in 0..headers.len() / Header::SIZE {
+            let hash = Header::consensus_decode(&mut c)?.block_hash();
+            heights.insert(hash, meta.start + i);
+            for endpoint in ENDPOINTS {
+                let path = format!("{}/{}.bin", endpoint, hash);
+                responses.insert(path.clone(), std::fs::read(dir.join(&path))?);
+            }
+        }
+        Ok(Chain {
+            meta,
+            headers,
+            heights,
+            responses,
+        })
+    }
+
+    fn tip(&self) -> usize {
+        self.meta.start + self.heights.len() - 1
+    }
+
+    /// Returns the content type and body, or `None` for a 404.
+    fn respond(&self, path: &str) -> Option<(&'static str, Vec<u8>)> {
+        let path = path.strip_prefix("/rest/")?;
+        if path == "chaininfo.json" {
+            let info = serde_json::json!({ "chain": self.meta.chain, "blocks": self.tip() });
+            return Some(("application/json", info.to_string().into_bytes()));
+        }
+        if let Some(height) = path.strip_prefix("blockhashbyheight/") {
+            let height: usize = height.strip_suffix(".hex")?.parse().ok()?;
+            let i = height.checked_sub(self.meta.start)?;
+            let header = self.headers.get(i * Header::SIZE..(i + 1) * Header::SIZE)?;
+            let hash = Header::consensus_decode(&mut Cursor::new(header))
+                .ok()?
+                .block_hash();
+            return Some(("text/plain", format!("{}\n", hash).into_bytes()));
+        }
+        if let Some(rest) = path.strip_prefix("headers/") {
+            let (count, hash) = rest.strip_suffix(".bin")?.split_once('/')?;
+            let count = count.parse::<usize>().ok()?.min(MAX_HEADERS);
+            let i = self.heights.get(&hash.parse::<BlockHash>().ok()?)? - self.meta.start;
+            let end = (i + count).min(self.heights.len());
+            let headers = &self.headers[i * Header::SIZE..end * Header::SIZE];
+            return Some(("application/octet-stream", headers.to_vec()));
+        }
+        let data = self.responses.get(path)?;
+        Some(("application/octet-stream", data.clone()))
+    }
+}
+
+/// HTTP/1.1 with keep-alive, one thread per connection.
+fn handle(chain: &Chain, stream: TcpStream, delay: Option<Duration>) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(()); // closed by the client
+        }
+        let mut parts = line.split_whitespace();
+        let (method, path, version) = (parts.next(), parts.next(), parts.next());
+        let mut close = version != Some("HTTP/1.1");
+        let mut header = String::new();
+        loop {
+            header.clear();
+            reader.read_line(&mut header)?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("Connection") {
+                    close = value.trim().eq_ignore_ascii_case("close");
+                }
+            }
+        }
+        if let Some(delay) = delay {
+            std::thread::sleep(delay);
+        }
+        let response = match (method, path) {
+            (Some("GET"), Some(path)) => chain.respond(path),
+            _ => None,
+        };
+        let (status, content_type, body) = match response {
+            Some((content_type, body)) => ("200 OK", content_type, body),
+            None => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+        };
+        write!(
+            writer,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}\r\n",
+            status,
+            content_type,
+            body.len(),
+            if close { "Connection: close\r\n" } else { "" },
+        )?;
+        writer.write_all(&body)?;
+        writer.flush()?;
+        tracing::debug!("{} {:?}", status, path);
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+fn serve(dir: &Path, listen: &str, delay: Option<Duration>) -> Result<()> {
+    let chain = Arc::new(Chain::load(dir)?);
+    let listener = TcpListener::bind(listen)?;
+    tracing::info!(
+        "serving {} blocks ({}, heights {}..={}) on http://{}",
+        chain.heights.len(),
+        chain.meta.chain,
+        chain.meta.start,
+        chain.tip(),
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let chain = Arc::clone(&chain);
+        std::thread::spawn(move || {
+            if let Err(e) = handle(&chain, stream, delay) {
+                tracing::warn!("connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    match Args::parse().command {
+        Command::Record {
+            url,
+            auth,
+            start,
+            count,
+            dir,
+        } => record(&url, auth.as_deref(), start, count, &dir),
+        Command::Serve { dir, listen, delay } => serve(&dir, &listen, delay),
+    }
+}
@@ -0,0 +1,95 @@
+//! Runs `bench` end to end against `mock-rest serve`, over a small regtest range recorded into
+//! `tests/fixtures/regtest` (heights 100..=119, with `mock-rest record`).
+
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+use serde_json::Value;
+
+const START: usize = 100;
+const COUNT: usize = 20;
+
+/// Kills the server when the test ends, even if it fails.
+struct Server {
+    child: Child,
+    url: String,
+}
+
+impl Server {
+    fn spawn() -> Server {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/regtest");
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mock-rest"))
+            .arg("serve")
+            .arg("--dir")
+            .arg(fixture)
+            .args(["--listen", "127.0.0.1:0"])
+            .env("NO_COLOR", "1")
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("mock-rest failed to start");
+        // Logged once listening, with the port the OS picked
+        let stderr = child.stderr.take().expect("piped");
+        let mut lines = BufReader::new(stderr).lines();
+        let url = loop {
+            let line = lines
+                .next()
+                .expect("mock-rest exited before listening")
+                .unwrap();
+            if let Some((_, url)) = line.split_once(" on ") {
+                break url.trim().to_owned();
+            }
+        };
+        Server { child, url }
+    }
+
+    /// Runs a `bench` subcommand over the whole range, returning its `--summary-out` run.
+    fn bench(&self, command: &str) -> Value {
+        let dir = std::env::temp_dir().join(format!("mock-rest-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join(format!("{}.json", command));
+        let status = Command::new(env!("CARGO_BIN_EXE_bench"))
+            .args(["--network", "regtest", "--url", &self.url])
+            .args(["--start", &START.to_string(), "--count", &COUNT.to_string()])
+            .arg("--summary-out")
+            .arg(&out)
+            .arg(command)
+            .stderr(Stdio::null())
+            .status()
+            .expect("bench failed to start");
+        assert!(status.success(), "bench {}: {}", command, status);
+        let report: Value = serde_json::from_slice(&std::fs::read(&out).unwrap()).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        let runs = report["runs"].as_array().unwrap();
+        assert_eq!(runs.len(), 1, "bench {}", command);
+        runs[0].clone()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn block_undo_spent() {
+    let server = Server::spawn();
+    let (block, undo, spent) = (
+        server.bench("block"),
+        server.bench("undo"),
+        server.bench("spent"),
+    );
+    for run in [&block, &undo, &spent] {
+        assert_eq!(run["blocks"], COUNT, "{}", run["name"]);
+        assert_eq!(run["stats"]["trailing"], 0, "{}", run["name"]);
+    }
+    assert!(block["stats"]["scripts"].as_u64().unwrap() > 0);
+    // Both describe the coins spent by the range
+    assert!(undo["stats"]["count"].as_u64().unwrap() > 0);
+    assert_eq!(undo["stats"]["count"], spent["stats"]["count"]);
+    assert_eq!(undo["stats"]["spent"], spent["stats"]["spent"]);
+}